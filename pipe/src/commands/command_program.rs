@@ -8,11 +8,16 @@ use flo_scene::commands::*;
 
 use futures::{pin_mut};
 use futures::prelude::*;
+use futures::future::{BoxFuture};
 use futures::stream::{BoxStream};
-use futures::channel::mpsc;
+use futures::channel::oneshot;
 
+use std::collections::{HashMap};
 use std::iter;
 
+/// The name of the variable that refers to the value piped into a command (`$input`), which can't be assigned to
+const PIPED_INPUT_VARIABLE: &str = "input";
+
 ///
 /// A connection to a simple command program
 ///
@@ -38,26 +43,15 @@ pub async fn command_connection_program(input: InputStream<CommandProgramSocketM
     while let Some(connection) = input.next().await {
         match connection {
             SocketMessage::Connection(connection) => {
-                // Create a channel to receive the responses on
-                // TODO: ideally we'd send the result of the 'spawn_command' routine to the connection here instead of relaying via another command
-                // (but that requires a two-stage connection)
-                let (send_response, recv_response) = mpsc::channel(0);
-                let command_input = connection.connect(recv_response);
+                // The responses for this connection are sent via a oneshot channel once the command processor has been spawned, so
+                // each connection gets its own output stream, which closes when the command processor for the connection finishes
+                let (send_responses, recv_responses) = oneshot::channel::<BoxStream<'static, CommandResponse>>();
+                let responses       = recv_responses.into_stream().filter_map(|responses| future::ready(responses.ok())).flatten();
+                let command_input   = connection.connect(responses);
 
-                // Spawn a reader for the command input
+                // Spawn a reader for the command input, and send its responses to the connection
                 if let Ok(responses) = context.spawn_command(CommandProcessor::new(command_target.clone()), command_input) {
-                    // ... and another task to relay the responses back to the socket
-                    context.spawn_command(FnCommand::<_, ()>::new(move |responses, _context| { 
-                        let mut send_response = send_response.clone(); 
-                        async move {
-                            let mut responses = responses;
-                            while let Some(response) = responses.next().await {
-                                if send_response.send(response).await.is_err() {
-                                    break;
-                                }
-                            }
-                        }
-                    }), responses).ok();
+                    send_responses.send(responses.boxed()).ok();
                 }
             }
         }
@@ -103,6 +97,185 @@ impl CommandProcessor {
             Ok(result_stream)   => result_stream.boxed()
         }
     }
+
+    ///
+    /// Runs a command by sending it directly to a specific target program, returning the response
+    ///
+    pub async fn run_command_for_target(&self, target: StreamTarget, command: CommandName, parameter: serde_json::Value, context: &SceneContext) -> BoxStream<'static, CommandResponse> {
+        // Send a RunCommand request straight to the target (which is usually a command launcher)
+        let command         = RunCommand::<serde_json::Value, CommandResponse>::new((), command, parameter);
+        let command_result  = context.spawn_query(ReadCommand::default(), command, target);
+
+        match command_result {
+            Err(err)            => stream::iter(iter::once(CommandResponse::Error(format!("Could not send command: {:?}", err)))).boxed(),
+            Ok(result_stream)   => result_stream.boxed()
+        }
+    }
+
+    ///
+    /// Evaluates a command request, returning the stream of responses that it generates
+    ///
+    /// The target is the program that commands should be sent to, or `None` to use the default target for this processor. The
+    /// variables are the values that have been assigned so far on the connection that is running the command.
+    ///
+    fn evaluate<'a>(&'a self, request: CommandRequest, target: Option<StreamTarget>, variables: &'a mut HashMap<VariableName, serde_json::Value>, context: &'a SceneContext) -> BoxFuture<'a, BoxStream<'static, CommandResponse>> {
+        async move {
+            use CommandRequest::*;
+
+            match request {
+                Command { command, argument } => {
+                    if let Some(target) = target {
+                        self.run_command_for_target(target, command, argument, context).await
+                    } else {
+                        self.run_command(command, argument, context).await
+                    }
+                }
+
+                CommandWithVariables { command, argument } => {
+                    // Replace the variable references in the argument with their values
+                    let argument = match substitute_variables(argument, variables) {
+                        Ok(argument)    => argument,
+                        Err(err)        => { return stream::iter(iter::once(CommandResponse::Error(err))).boxed(); }
+                    };
+
+                    self.evaluate(Command { command, argument }, target, variables, context).await
+                }
+
+                ForTarget { target, request } => {
+                    // Run the request, sending any commands to the specified target instead of the default one
+                    self.evaluate(*request, Some(target), variables, context).await
+                }
+
+                WithRequestId { id, request } => {
+                    // Tag every response to the request with its ID
+                    let responses = self.evaluate(*request, target, variables, context).await;
+
+                    responses.map(move |response| CommandResponse::WithRequestId(id.clone(), Box::new(response))).boxed()
+                }
+
+                Assign { variable, from } => {
+                    // The piped input variable can't be assigned, so it always refers to the value piped into a command
+                    if variable == VariableName(PIPED_INPUT_VARIABLE.to_string()) {
+                        return stream::iter(iter::once(CommandResponse::Error(format!("${} is reserved for the value piped into a command and can't be assigned", PIPED_INPUT_VARIABLE)))).boxed();
+                    }
+
+                    // Run the command and capture its JSON responses
+                    let mut responses       = self.evaluate(*from, target, variables, context).await;
+                    let mut values          = vec![];
+                    let mut other_responses = vec![];
+
+                    while let Some(response) = responses.next().await {
+                        match response {
+                            CommandResponse::Json(value)    => values.push(value),
+                            other                           => other_responses.push(other),
+                        }
+                    }
+
+                    // The variable is set to the JSON value that the command returned (or an array if it returned several)
+                    if values.len() == 1 {
+                        variables.insert(variable, values.pop().unwrap());
+                    } else if !values.is_empty() {
+                        variables.insert(variable, serde_json::Value::Array(values));
+                    }
+
+                    // Any messages or errors are passed on
+                    stream::iter(other_responses).boxed()
+                }
+
+                Pipe { from, to } => {
+                    // Run the 'from' command, and then feed its output into the 'to' command
+                    let mut from_responses  = self.evaluate(*from, target.clone(), variables, context).await;
+                    let mut responses       = vec![];
+
+                    while let Some(response) = from_responses.next().await {
+                        let piped_value = match response {
+                            // JSON values become the input to the 'to' command
+                            CommandResponse::Json(value) => value,
+
+                            // JSON commands take a single parameter, so the values in a background stream are passed to the 'to' command as an array once the stream finishes
+                            CommandResponse::BackgroundStream(values) => serde_json::Value::Array(values.collect::<Vec<_>>().await),
+
+                            CommandResponse::Error(err) => {
+                                // Errors stop the pipe
+                                responses.push(CommandResponse::Error(err));
+                                break;
+                            }
+
+                            // Tagged responses are passed on without being piped, as are any messages
+                            other => {
+                                responses.push(other);
+                                continue;
+                            }
+                        };
+
+                        // The 'to' command is evaluated once for each piped value, which is also available as $input while it runs
+                        let to_request      = with_piped_input((*to).clone(), piped_value.clone());
+                        let input_variable  = VariableName(PIPED_INPUT_VARIABLE.to_string());
+                        let previous_input  = variables.insert(input_variable.clone(), piped_value);
+
+                        let mut to_responses = self.evaluate(to_request, target.clone(), variables, context).await;
+
+                        match previous_input {
+                            Some(previous_input)    => { variables.insert(input_variable, previous_input); }
+                            None                    => { variables.remove(&input_variable); }
+                        }
+
+                        while let Some(response) = to_responses.next().await {
+                            responses.push(response);
+                        }
+                    }
+
+                    stream::iter(responses).boxed()
+                }
+            }
+        }.boxed()
+    }
+}
+
+///
+/// Sets the value piped in to a command request
+///
+/// If the command has no argument, the value becomes its argument. Otherwise the argument is left alone: it can refer to the
+/// piped value as `$input`, which is assigned while the command runs (`input` can't be assigned as a normal variable).
+///
+fn with_piped_input(request: CommandRequest, value: serde_json::Value) -> CommandRequest {
+    use CommandRequest::*;
+
+    match request {
+        Command { command, argument: serde_json::Value::Null } => Command { command, argument: value },
+
+        Command { command, argument }               => Command { command, argument },
+        CommandWithVariables { command, argument }  => CommandWithVariables { command, argument },
+        ForTarget { target, request }               => ForTarget { target, request: Box::new(with_piped_input(*request, value)) },
+        WithRequestId { id, request }               => WithRequestId { id, request: Box::new(with_piped_input(*request, value)) },
+        Pipe { from, to }                           => Pipe { from: Box::new(with_piped_input(*from, value)), to },
+        Assign { variable, from }                   => Assign { variable, from: Box::new(with_piped_input(*from, value)) },
+    }
+}
+
+///
+/// Replaces the variable references in a command argument with the values of the corresponding variables
+///
+/// It's an error to refer to a variable that has not been assigned, or to a path that is not in the value of the variable.
+///
+fn substitute_variables(argument: CommandArgument, variables: &HashMap<VariableName, serde_json::Value>) -> Result<serde_json::Value, String> {
+    use serde_json::Value;
+
+    match argument {
+        CommandArgument::Json(value) => Ok(value),
+
+        CommandArgument::Variable(VariableName(name), path) => {
+            let variable_value = variables.get(&VariableName(name.clone()))
+                .ok_or_else(|| format!("${} has not been assigned a value", name))?;
+
+            path.evaluate(variable_value)
+                .cloned()
+                .ok_or_else(|| format!("Path '{}' was not found in ${}", path, name))
+        }
+
+        CommandArgument::Array(items)   => Ok(Value::Array(items.into_iter().map(|item| substitute_variables(item, variables)).collect::<Result<_, _>>()?)),
+        CommandArgument::Object(fields) => Ok(Value::Object(fields.into_iter().map(|(key, value)| Ok((key, substitute_variables(value, variables)?))).collect::<Result<_, String>>()?)),
+    }
 }
 
 impl Command for CommandProcessor {
@@ -114,15 +287,13 @@ impl Command for CommandProcessor {
             pin_mut!(input);
             let mut our_responses = context.send::<CommandResponse>(()).unwrap();
 
-            while let Some(next_command) = input.next().await {
-                use CommandRequest::*;
+            // The values of the variables that have been assigned on this connection
+            let mut variables = HashMap::new();
 
+            while let Some(next_command) = input.next().await {
                 let mut command_responses = match next_command {
-                    Ok(Command     { command, argument }) => { self.run_command(command, argument, &context).await }
-                    Ok(Pipe        { from, to })          => { stream::iter(iter::once(CommandResponse::Error("Not implemented yet".into()))).boxed() }
-                    Ok(Assign      { variable, from })    => { stream::iter(iter::once(CommandResponse::Error("Not implemented yet".into()))).boxed() }
-                    Ok(ForTarget   { target, request })   => { stream::iter(iter::once(CommandResponse::Error("Not implemented yet".into()))).boxed() }
-                    Err(_)                                => { stream::iter(iter::once(CommandResponse::Error("Not implemented yet".into()))).boxed() }
+                    Ok(request) => { self.evaluate(request, None, &mut variables, &context).await }
+                    Err(_)      => { stream::iter(iter::once(CommandResponse::Error("Could not parse command".into()))).boxed() }
                 };
 
                 while let Some(response) = command_responses.next().await {
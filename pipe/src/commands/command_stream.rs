@@ -1,3 +1,5 @@
+use super::parse_error::*;
+use super::json_path::*;
 use crate::parser::*;
 
 use flo_scene::*;
@@ -18,6 +20,7 @@ use std::collections::{VecDeque};
 use std::fmt;
 use std::fmt::{Debug, Formatter};
 use std::iter;
+use std::mem;
 use std::task::{Poll};
 
 ///
@@ -32,12 +35,62 @@ pub struct CommandName(pub String);
 #[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
 pub struct VariableName(pub String);
 
+///
+/// An identifier supplied by a client with a command request, which is echoed back with the responses to that request
+///
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub struct RequestId(pub String);
+
 ///
 /// An argument to a command sent to a stream
 ///
+/// Arguments are JSON values that can also contain references to variables (eg, `[ $x, 2 ]`), which are replaced with the
+/// values of the variables when the command is run. Strings are never treated as variable references, so `"$x"` is always
+/// just a string. Arrays and objects are only represented as `Array` or `Object` if they contain a variable reference.
+///
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CommandArgument {
-    Json(serde_json::Value)
+    /// A JSON value that does not refer to any variables
+    Json(serde_json::Value),
+
+    /// The value of a variable, or a part of it selected by a path (`$name` or `$name.items[0]`)
+    Variable(VariableName, JsonPath),
+
+    /// An array containing variable references
+    Array(Vec<CommandArgument>),
+
+    /// An object containing variable references
+    Object(Vec<(String, CommandArgument)>),
+}
+
+impl CommandArgument {
+    ///
+    /// Creates an array argument, which is just a JSON value if none of the items refer to a variable
+    ///
+    pub fn array(items: Vec<CommandArgument>) -> CommandArgument {
+        let values = items.iter()
+            .map(|item| if let CommandArgument::Json(value) = item { Some(value.clone()) } else { None })
+            .collect::<Option<Vec<_>>>();
+
+        match values {
+            Some(values)    => CommandArgument::Json(serde_json::Value::Array(values)),
+            None            => CommandArgument::Array(items),
+        }
+    }
+
+    ///
+    /// Creates an object argument, which is just a JSON value if none of the fields refer to a variable
+    ///
+    pub fn object(fields: Vec<(String, CommandArgument)>) -> CommandArgument {
+        let values = fields.iter()
+            .map(|(key, value)| if let CommandArgument::Json(value) = value { Some((key.clone(), value.clone())) } else { None })
+            .collect::<Option<serde_json::Map<_, _>>>();
+
+        match values {
+            Some(values)    => CommandArgument::Json(serde_json::Value::Object(values)),
+            None            => CommandArgument::Object(fields),
+        }
+    }
 }
 
 ///
@@ -46,12 +99,23 @@ pub enum CommandArgument {
 /// Commands have the format `<CommandName> <Argument>`, where the command name is an identifier and the arguments is a single
 /// JSON value (multiple values can be passed by chained together commands using '|' operator)
 ///
+/// As the argument is a JSON value, a string argument is written as a double-quoted JSON string, which can contain spaces and
+/// the standard JSON escape sequences (eg, `echo "say \"hello world\""`).
+///
+/// The argument can refer to a variable assigned by an earlier command (`x = some::command`), either as the whole argument or as
+/// part of an array or object (eg, `another::command [ $x, $x.items[0] ]`). Commands like this are parsed as `CommandWithVariables`.
+///
+/// A command can be preceded by a request ID of the form `@<id>` (eg, `@12 some::command [ 1, 2 ]`). The responses to the command
+/// are tagged with the same ID, which lets a client that sends several commands at once match up the responses with the requests.
+///
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub enum CommandRequest {
-    Command     { command: CommandName, argument: serde_json::Value },
-    Pipe        { from: Box<CommandRequest>, to: Box<CommandRequest> },
-    Assign      { variable: VariableName, from: Box<CommandRequest> },
-    ForTarget   { target: StreamTarget, request: Box<CommandRequest> }
+    Command                 { command: CommandName, argument: serde_json::Value },
+    CommandWithVariables    { command: CommandName, argument: CommandArgument },
+    Pipe                    { from: Box<CommandRequest>, to: Box<CommandRequest> },
+    Assign                  { variable: VariableName, from: Box<CommandRequest> },
+    ForTarget               { target: StreamTarget, request: Box<CommandRequest> },
+    WithRequestId           { id: RequestId, request: Box<CommandRequest> },
 }
 
 ///
@@ -79,8 +143,15 @@ pub enum CommandResponse {
     /// spread across several lines. When the stream is closed, a '<EOS <n>' message is generated.
     BackgroundStream(BoxStream<'static, serde_json::Value>),
 
+    /// Stops a background stream that was started by an earlier command, using the number it was announced with. The stream
+    /// is dropped and a '<EOS <n>' message is generated as if it had finished.
+    CloseBackgroundStream(usize),
+
     /// An error message, written as '!!! <error>'
     Error(String),    
+
+    /// A response to a request that was tagged with a request ID. When displayed, the response is prefixed with '@<id> '
+    WithRequestId(RequestId, Box<CommandResponse>),
 }
 
 ///
@@ -138,6 +209,12 @@ impl From<ListCommandResponse> for CommandResponse {
     }
 }
 
+impl From<CommandDescription> for CommandResponse {
+    fn from(description: CommandDescription) -> Self {
+        CommandResponse::Json(description.serialize(serde_json::value::Serializer).unwrap())
+    }
+}
+
 impl<TResponseData> TryInto<CommandResponse> for CommandResponseData<TResponseData> 
 where
     TResponseData: Serialize
@@ -175,13 +252,31 @@ impl TryInto<ListCommandResponse> for CommandResponse {
     }
 }
 
+impl TryInto<CommandDescription> for CommandResponse {
+    type Error = CommandError;
+
+    fn try_into(self) -> Result<CommandDescription, CommandError> {
+        match self {
+            CommandResponse::Json(json) => {
+                CommandDescription::deserialize(json)
+                    .map_err(|_| CommandError::CannotConvertResponse)
+            }
+
+            // Other types of response cannot be JSON requests
+            _ => Err(CommandError::CannotConvertResponse)
+        }
+    }
+}
+
 impl Debug for CommandResponse {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
-            CommandResponse::Message(msg)           => write!(f, "Message({:?})", msg),
-            CommandResponse::Json(json)             => write!(f, "Json({:?})", json),
-            CommandResponse::BackgroundStream(_)    => write!(f, "BackgroundStream(...)"),
-            CommandResponse::Error(err)             => write!(f, "Error({:?})", err),
+            CommandResponse::Message(msg)               => write!(f, "Message({:?})", msg),
+            CommandResponse::Json(json)                 => write!(f, "Json({:?})", json),
+            CommandResponse::BackgroundStream(_)        => write!(f, "BackgroundStream(...)"),
+            CommandResponse::CloseBackgroundStream(n)   => write!(f, "CloseBackgroundStream({})", n),
+            CommandResponse::Error(err)                 => write!(f, "Error({:?})", err),
+            CommandResponse::WithRequestId(id, r)       => write!(f, "WithRequestId({:?}, {:?})", id, r),
         }
     }
 }
@@ -202,6 +297,23 @@ impl QueryRequest for CommandRequest {
     }
 }
 
+impl CommandResponse {
+    ///
+    /// Separates the request ID (if there is one) from this response
+    ///
+    pub fn split_request_id(self) -> (Option<RequestId>, CommandResponse) {
+        match self {
+            CommandResponse::WithRequestId(id, response) => {
+                // If the response has been tagged more than once, the outermost ID is the one that's used
+                let (_, response) = response.split_request_id();
+                (Some(id), response)
+            }
+
+            response => (None, response),
+        }
+    }
+}
+
 impl CommandRequest {
     ///
     /// Creates a command by parsing a string
@@ -216,6 +328,31 @@ impl CommandRequest {
 
         Ok(parser.finish().map_err(|_| ())?)
     }
+
+    ///
+    /// Creates a command by parsing a string, describing where the problem is if the command can't be parsed
+    ///
+    /// This accepts the same syntax as `parse()`, but the error gives the location of the first token that could not be parsed (or
+    /// the end of the string if the command is incomplete) along with a description of what was expected there.
+    ///
+    pub async fn parse_with_errors(command: &str) -> Result<CommandRequest, CommandParseError> {
+        let mut parser      = Parser::new();
+        let mut tokenizer   = Tokenizer::new(stream::iter(command.bytes()).ready_chunks(256));
+
+        tokenizer.with_command_matchers();
+
+        let result = command_parse_expecting(&mut parser, &mut tokenizer).await;
+        let result = result.and_then(|()| parser.finish().map_err(|_| "the end of the command"));
+
+        result.map_err(|expected| {
+            // The tokenizer has read past any tokens left in the lookahead, so the error is at the start of the first of these
+            let offset = parser.return_lookahead().next()
+                .map(|token| token.start)
+                .unwrap_or(tokenizer.position());
+
+            CommandParseError { location: CommandLocation::with_offset(command, offset), expected: expected.to_string() }
+        })
+    }
 }
 
 ///
@@ -226,14 +363,30 @@ impl CommandRequest {
 /// Commands are relatively simple, they have the structure `<name> <parameters>` where the name is an identifier (containing alphanumeric characters, 
 /// alongside '_', '.' and ':'). Parameters are just JSON values, and commands are ended by a newline character that is outside of a JSON value.
 ///
+/// Comments start with `//` or `#` and carry on to the end of the line, so a file of commands can be annotated. Commands can be preceded
+/// by a request ID, which starts with `@` (eg, `@12 some::command`).
+///
+/// JSON arguments are limited to the sizes in `JsonLimits::default()`: use `parse_command_stream_with_limits()` to change this.
+///
 pub fn parse_command_stream(input: impl 'static + Send + Unpin + Stream<Item=Vec<u8>>) -> impl 'static + Send + Unpin + Stream<Item=Result<CommandRequest, ()>> {
+    parse_command_stream_with_limits(input, JsonLimits::default())
+}
+
+///
+/// As for `parse_command_stream()`, except with custom limits on the size and nesting depth of the JSON arguments to the commands
+///
+/// A command with an argument that exceeds the limits is returned as an error, in the same way as any other command that can't be
+/// parsed. The limits can be set when starting a socket program by using a closure to create the input stream, for example
+/// `move |input| parse_command_stream_with_limits(input, limits)`.
+///
+pub fn parse_command_stream_with_limits(input: impl 'static + Send + Unpin + Stream<Item=Vec<u8>>, limits: JsonLimits) -> impl 'static + Send + Unpin + Stream<Item=Result<CommandRequest, ()>> {
     generator_stream(move |yield_value| async move {
         let mut tokenizer   = Tokenizer::new(input);
         let mut parser      = Parser::new();
 
         tokenizer.with_command_matchers();
+        tokenizer.with_json_limits(limits);
 
-        // TODO: loop until EOF
         loop {
             // Read the next command
             let next_command = command_parse(&mut parser, &mut tokenizer).await;
@@ -247,12 +400,31 @@ pub fn parse_command_stream(input: impl 'static + Send + Unpin + Stream<Item=Vec
 
                 Err(()) => {
                     // Throw away the contents of the parser
-                    parser.abort();
+                    let partial_command = parser.abort();
+                    let lookahead       = parser.return_lookahead().collect::<Vec<_>>();
 
-                    // TODO: Discard tokens until we encounter a newline
+                    // Stop once the input stream is exhausted (it's an error if the stream ended part-way through a command)
+                    if lookahead.is_empty() && tokenizer.at_end_of_stream().await {
+                        if !partial_command.is_empty() {
+                            yield_value(Err(())).await;
+                        }
 
-                    // TODO: parse until EOF
-                    break;
+                        break;
+                    }
+
+                    // Report the error for this command
+                    yield_value(Err(())).await;
+
+                    // Discard tokens until we encounter a newline, then carry on with the next command
+                    if let Some(newline_pos) = lookahead.iter().position(|token| token.token == Some(CommandToken::Newline)) {
+                        // Any tokens after the newline are returned to the tokenizer to be parsed again
+                        let remaining = lookahead[(newline_pos+1)..].iter()
+                            .map(|token| token.fragment.clone())
+                            .collect::<String>();
+                        tokenizer.return_characters(remaining);
+                    } else {
+                        tokenizer.skip_to_newline().await;
+                    }
                 }
             }
         }
@@ -262,11 +434,15 @@ pub fn parse_command_stream(input: impl 'static + Send + Unpin + Stream<Item=Vec
 ///
 /// Displays the result of a command
 ///
-async fn display_response(yield_value: &(impl Send + Fn(String) -> BoxFuture<'static, ()>), put_stream_in_background: &mut (impl Send + Unpin + Sink<BoxStream<'static, serde_json::Value>>), response: CommandResponse) {
+async fn display_response(yield_value: &(impl Send + Fn(String) -> BoxFuture<'static, ()>), put_stream_in_background: &mut (impl Send + Unpin + Sink<BackgroundStreamRequest>), response: CommandResponse) {
+    // Responses to requests with an ID are prefixed with '@<id> '
+    let (request_id, response)  = response.split_request_id();
+    let prefix                  = request_id_prefix(&request_id);
+
     match response {
         CommandResponse::Message(msg) => {
             let msg = msg.replace("\n", "\n  ");
-            yield_value(format!("  {}\n", msg)).await;
+            yield_value(format!("{}  {}\n", prefix, msg)).await;
         }
 
         CommandResponse::Json(json) => {
@@ -274,24 +450,55 @@ async fn display_response(yield_value: &(impl Send + Fn(String) -> BoxFuture<'st
             let json_string = serde_json::to_string_pretty(&json);
 
             if let Ok(json_string) = json_string {
-                yield_value(format!("{}\n", json_string)).await;
+                yield_value(format!("{}{}\n", prefix, json_string)).await;
             } else {
-                yield_value(format!("!!! {:?}\n", "Could not format JSON response")).await;
+                yield_value(format!("{}!!! {:?}\n", prefix, "Could not format JSON response")).await;
             }
         },
 
         CommandResponse::BackgroundStream(stream) => {
-            // This requires moving the stream to the background
-            put_stream_in_background.send(stream).await.ok();
+            // This requires moving the stream to the background (the request ID is displayed when the stream is announced)
+            put_stream_in_background.send(BackgroundStreamRequest::Start(request_id, stream)).await.ok();
+        },
+
+        CommandResponse::CloseBackgroundStream(stream_num) => {
+            // The stream is closed by the background stream monitor, which generates the '<EOS <n>' message
+            put_stream_in_background.send(BackgroundStreamRequest::Close(request_id, stream_num)).await.ok();
         },
 
         CommandResponse::Error(error_message) => {
             // '!!! <error>' if there's a problem
-            yield_value(format!("!!! {}\n", error_message)).await;
+            yield_value(format!("{}!!! {}\n", prefix, error_message)).await;
         }
+
+        CommandResponse::WithRequestId(_, _) => {
+            // split_request_id() removes all of the request IDs
+            unreachable!()
+        }
+    }
+}
+
+///
+/// Returns the prefix used to display a response to a request with an ID
+///
+fn request_id_prefix(request_id: &Option<RequestId>) -> String {
+    match request_id {
+        Some(RequestId(id)) => format!("@{} ", id),
+        None                => String::new(),
     }
 }
 
+///
+/// A request to change the set of background streams that are being monitored
+///
+enum BackgroundStreamRequest {
+    /// A background stream generated by a command, along with the ID of the request that created it
+    Start(Option<RequestId>, BoxStream<'static, serde_json::Value>),
+
+    /// Stops monitoring the background stream with the specified number (the request ID is used if the stream can't be found)
+    Close(Option<RequestId>, usize),
+}
+
 ///
 /// A display request is used as the internal message type for receiving command responses or messages from background streams
 ///
@@ -299,8 +506,8 @@ enum DisplayRequest {
     /// Standard command response
     CommandResponse(CommandResponse),
 
-    /// A new background stream was created
-    NewBackgroundStream(usize),
+    /// A new background stream was created (by the request with the specified ID)
+    NewBackgroundStream(usize, Option<RequestId>),
 
     /// A background stream was closed
     ClosedBackgroundStream(usize),
@@ -315,9 +522,9 @@ enum DisplayRequest {
 ///
 /// Creates a stream that multiplexes background streams and writes to the output
 ///
-fn background_command_streams() -> (impl 'static + Send + Unpin + Stream<Item=DisplayRequest>, impl 'static + Send + Unpin + Sink<BoxStream<'static, serde_json::Value>, Error=mpsc::SendError>) {
-    // Create the channel where new background streams can be sent
-    let (send_new_streams, new_streams) = mpsc::channel::<BoxStream<'static, serde_json::Value>>(1);
+fn background_command_streams() -> (impl 'static + Send + Unpin + Stream<Item=DisplayRequest>, impl 'static + Send + Unpin + Sink<BackgroundStreamRequest, Error=mpsc::SendError>) {
+    // Create the channel where new background streams can be sent (this is unbounded as the same task that sends requests also reads the stream that processes them)
+    let (send_new_streams, new_streams) = mpsc::unbounded::<BackgroundStreamRequest>();
 
     // The stream we return reads from any stream passed in to the new streams list
     // TODO: this isn't very efficient (fine for small numbers of streams but we should probably use a context that only polls the streams that are needed)
@@ -330,19 +537,41 @@ fn background_command_streams() -> (impl 'static + Send + Unpin + Stream<Item=Di
         if let Some(new_streams) = &mut maybe_new_streams {
             match new_streams.poll_next_unpin(context) {
                 Poll::Pending                   => { }
-                Poll::Ready(None)               => { maybe_new_streams = None;}
-                Poll::Ready(Some(new_stream))   => { 
+                Poll::Ready(None)               => { maybe_new_streams = None; }
+                Poll::Ready(Some(BackgroundStreamRequest::Start(request_id, new_stream))) => { 
                     let stream_num = next_stream_num;
                     next_stream_num += 1;
 
                     monitored_streams.push_back((stream_num, new_stream));
 
                     // Generates a 'new background stream' message
-                    return Poll::Ready(Some(DisplayRequest::NewBackgroundStream(stream_num)));
+                    return Poll::Ready(Some(DisplayRequest::NewBackgroundStream(stream_num, request_id)));
+                }
+
+                Poll::Ready(Some(BackgroundStreamRequest::Close(request_id, stream_num))) => {
+                    // Dropping the stream stops it, and then it's reported as closed in the same way as a stream that has finished
+                    if let Some(stream_idx) = monitored_streams.iter().position(|(num, _)| *num == stream_num) {
+                        monitored_streams.remove(stream_idx);
+
+                        return Poll::Ready(Some(DisplayRequest::ClosedBackgroundStream(stream_num)));
+                    } else {
+                        let error = CommandResponse::Error(format!("Background stream {} is not running", stream_num));
+                        let error = if let Some(request_id) = request_id { CommandResponse::WithRequestId(request_id, Box::new(error)) } else { error };
+
+                        return Poll::Ready(Some(DisplayRequest::CommandResponse(error)));
+                    }
                 }
             }
         }
 
+        // Once the sender is dropped, the display is finishing: close any streams that are still running
+        if maybe_new_streams.is_none() {
+            return match monitored_streams.pop_front() {
+                Some((stream_num, _stream)) => Poll::Ready(Some(DisplayRequest::ClosedBackgroundStream(stream_num))),
+                None                        => Poll::Ready(None),
+            };
+        }
+
         // Poll all of the monitored streams until we get a response, or we find they're all pending
         // The VecDeque rotation we do here ensures that if there is one very active stream it can't drown out the others
         let num_streams = monitored_streams.len();
@@ -418,8 +647,8 @@ pub fn display_command_responses(input: impl 'static + Send + Unpin + Stream<Ite
                                 display_response(&yield_value, &mut background_stream_sender, response).await;
                             }
 
-                            DisplayRequest::NewBackgroundStream(stream_num) => {
-                                yield_value(format!("<<< {}\n", stream_num)).await;
+                            DisplayRequest::NewBackgroundStream(stream_num, request_id) => {
+                                yield_value(format!("{}<<< {}\n", request_id_prefix(&request_id), stream_num)).await;
                             }
 
                             DisplayRequest::ClosedBackgroundStream(stream_num) => {
@@ -458,7 +687,133 @@ pub fn display_command_responses(input: impl 'static + Send + Unpin + Stream<Ite
             yield_value("\n> ".into()).await;
         }
 
+        // Close any background streams that are still running (dropping the sender closes the remaining streams)
+        mem::drop(background_stream_sender);
+
+        while let Some(request) = input.next().await {
+            match request {
+                DisplayRequest::NewBackgroundStream(stream_num, id) => { yield_value(format!("{}<<< {}\n", request_id_prefix(&id), stream_num)).await; }
+                DisplayRequest::ClosedBackgroundStream(stream_num)  => { yield_value(format!("<EOS {}\n", stream_num)).await; }
+                _                                                   => { }
+            }
+        }
+
         // Sign out
         yield_value("\n\n.\n".into()).await;
     }).map(|string| string.into_bytes()).boxed()
 }
+
+///
+/// Converts a response to the JSON object used to represent it by `display_command_responses_json()`
+///
+fn display_request_json(request: DisplayRequest) -> Option<serde_json::Value> {
+    use serde_json::json;
+
+    let (request_id, json) = match request {
+        DisplayRequest::CommandResponse(response) => {
+            let (request_id, response) = response.split_request_id();
+
+            let json = match response {
+                CommandResponse::Json(value)            => json!({ "type": "json", "value": value }),
+                CommandResponse::Message(message)       => json!({ "type": "message", "message": message }),
+                CommandResponse::Error(message)         => json!({ "type": "error", "message": message }),
+
+                // Background streams are announced when they start, and when they end
+                CommandResponse::BackgroundStream(_)        |
+                CommandResponse::CloseBackgroundStream(_)   => { return None; }
+                CommandResponse::WithRequestId(_, _)    => { unreachable!() }
+            };
+
+            (request_id, json)
+        }
+
+        DisplayRequest::NewBackgroundStream(stream_num, request_id) => (request_id, json!({ "type": "stream_start", "id": stream_num })),
+        DisplayRequest::ClosedBackgroundStream(stream_num)          => (None, json!({ "type": "stream_end", "id": stream_num })),
+        DisplayRequest::StreamMessage(stream_num, value)            => (None, json!({ "type": "stream_event", "id": stream_num, "value": value })),
+        DisplayRequest::Stop                                        => { return None; }
+    };
+
+    // Responses to requests with an ID have a 'request_id' field
+    let mut json = json;
+    if let Some(RequestId(request_id)) = request_id {
+        json["request_id"] = serde_json::Value::String(request_id);
+    }
+
+    Some(json)
+}
+
+///
+/// Displays the output of the responses to a set of commands as a stream of JSON objects, one per line
+///
+/// This is an alternative to `display_command_responses()` for clients that are programs rather than people: there are no prompts,
+/// and every line is a JSON object with a `type` field:
+///
+/// * `{ "type": "json", "value": <value> }` for a JSON response
+/// * `{ "type": "message", "message": "<message>" }` for a message
+/// * `{ "type": "error", "message": "<error>" }` for an error
+/// * `{ "type": "stream_start", "id": <n> }` when a command starts a background stream
+/// * `{ "type": "stream_event", "id": <n>, "value": <value> }` for each value generated by a background stream
+/// * `{ "type": "stream_end", "id": <n> }` when a background stream finishes
+///
+/// Responses to requests that have an ID, and the `stream_start` objects for the background streams they create, also have a
+/// `"request_id": "<id>"` field.
+///
+pub fn display_command_responses_json(input: impl 'static + Send + Unpin + Stream<Item=CommandResponse>) -> BoxStream<'static, Vec<u8>> {
+    generator_stream::<serde_json::Value, _, _>(|yield_value| async move {
+        let (background_messages, background_stream_sender) = background_command_streams();
+
+        let input = input.map(DisplayRequest::CommandResponse).chain(stream::iter(iter::once(DisplayRequest::Stop)));
+        let input = stream::select(input, background_messages);
+
+        pin_mut!(input);
+
+        let mut background_stream_sender = background_stream_sender;
+
+        while let Some(request) = input.next().await {
+            match request {
+                DisplayRequest::Stop => {
+                    break;
+                }
+
+                DisplayRequest::CommandResponse(response) => {
+                    match response.split_request_id() {
+                        (request_id, CommandResponse::BackgroundStream(stream)) => {
+                            // Background streams are announced once they've been added to the set of monitored streams
+                            background_stream_sender.send(BackgroundStreamRequest::Start(request_id, stream)).await.ok();
+                        }
+
+                        (request_id, CommandResponse::CloseBackgroundStream(stream_num)) => {
+                            // The end of the stream is reported by the background stream monitor
+                            background_stream_sender.send(BackgroundStreamRequest::Close(request_id, stream_num)).await.ok();
+                        }
+
+                        (request_id, response) => {
+                            let response = if let Some(request_id) = request_id { CommandResponse::WithRequestId(request_id, Box::new(response)) } else { response };
+
+                            if let Some(json) = display_request_json(DisplayRequest::CommandResponse(response)) {
+                                yield_value(json).await;
+                            }
+                        }
+                    }
+                }
+
+                request => {
+                    if let Some(json) = display_request_json(request) {
+                        yield_value(json).await;
+                    }
+                }
+            }
+        }
+
+        // Close any background streams that are still running (dropping the sender closes the remaining streams)
+        mem::drop(background_stream_sender);
+
+        while let Some(request) = input.next().await {
+            if matches!(request, DisplayRequest::NewBackgroundStream(_, _) | DisplayRequest::ClosedBackgroundStream(_)) {
+                if let Some(json) = display_request_json(request) {
+                    yield_value(json).await;
+                }
+            }
+        }
+    }).map(|json| format!("{}\n", json).into_bytes()).boxed()
+}
@@ -4,6 +4,7 @@ use flo_scene::*;
 use flo_scene::commands::*;
 
 use futures::prelude::*;
+use futures::future::{BoxFuture};
 
 use serde::*;
 use serde_json;
@@ -24,6 +25,15 @@ pub trait JsonCommandLauncherExt {
         TFuture:            'static + Send + Future,
         TFuture::Output:    'static + TryInto<CommandResponse>,
         TParameter:         'static + Send + for<'a> Deserialize<'a>;
+
+    ///
+    /// Adds a JSON command along with a description of what it does, which is listed by the 'help' command
+    ///
+    fn with_json_command_described<TParameter, TFuture>(self, command_name: impl Into<String>, description: impl Into<String>, command: impl 'static + Send + Sync + Fn(TParameter, SceneContext) -> TFuture) -> Self
+    where
+        TFuture:            'static + Send + Future,
+        TFuture::Output:    'static + TryInto<CommandResponse>,
+        TParameter:         'static + Send + for<'a> Deserialize<'a>;
 }
 
 impl JsonCommandLauncherExt for CommandLauncher<serde_json::Value, CommandResponse> {
@@ -37,33 +47,53 @@ impl JsonCommandLauncherExt for CommandLauncher<serde_json::Value, CommandRespon
         TFuture::Output:    'static + TryInto<CommandResponse>,
         TParameter:         'static + Send + for<'a> Deserialize<'a>,
     {
-        let command = Arc::new(command);
+        self.with_command(command_name, json_command_fn(command))
+    }
+
+    fn with_json_command_described<TParameter, TFuture>(self, command_name: impl Into<String>, description: impl Into<String>, command: impl 'static + Send + Sync + Fn(TParameter, SceneContext) -> TFuture) -> Self
+    where
+        TFuture:            'static + Send + Future,
+        TFuture::Output:    'static + TryInto<CommandResponse>,
+        TParameter:         'static + Send + for<'a> Deserialize<'a>,
+    {
+        self.with_command_described(command_name, description, json_command_fn(command))
+    }
+}
+
+///
+/// Wraps a function that takes a deserialized parameter as a command function that takes a JSON parameter and sends its result as a `CommandResponse`
+///
+fn json_command_fn<TParameter, TFuture>(command: impl 'static + Send + Sync + Fn(TParameter, SceneContext) -> TFuture) -> impl 'static + Send + Sync + Fn(&serde_json::Value, SceneContext) -> BoxFuture<'static, ()>
+where
+    TFuture:            'static + Send + Future,
+    TFuture::Output:    'static + TryInto<CommandResponse>,
+    TParameter:         'static + Send + for<'a> Deserialize<'a>,
+{
+    let command = Arc::new(command);
 
-        self.with_command(command_name, move |json, context|
-            {
-                let command     = Arc::clone(&command);
-                let parameter   = TParameter::deserialize(json);
+    move |json, context| {
+        let command     = Arc::clone(&command);
+        let parameter   = TParameter::deserialize(json);
 
-                async move {
-                    // Connect to the output stream to generate the response
-                    let response     = context.send::<CommandResponse>(());
-                    let mut response = if let Ok(response) = response { response } else { return; };
+        async move {
+            // Connect to the output stream to generate the response
+            let response     = context.send::<CommandResponse>(());
+            let mut response = if let Ok(response) = response { response } else { return; };
 
-                    if let Ok(parameter) = parameter {
-                        // Invoke the command to get the response
-                        let command_result = command(parameter, context).await;
+            if let Ok(parameter) = parameter {
+                // Invoke the command to get the response
+                let command_result = command(parameter, context).await;
 
-                        if let Ok(command_result) = command_result.try_into().map_err(|_| ()) {
-                            response.send(command_result).await.ok();
-                        } else {
-                            // Could not serialize the result
-                            response.send(CommandError::CannotConvertResponse.into()).await.ok();
-                        }
-                    } else {
-                        // Could not deserialize parameter
-                        response.send(CommandError::IncorrectParameterFormat.into()).await.ok();
-                    }
+                if let Ok(command_result) = command_result.try_into().map_err(|_| ()) {
+                    response.send(command_result).await.ok();
+                } else {
+                    // Could not serialize the result
+                    response.send(CommandError::CannotConvertResponse.into()).await.ok();
                 }
-            })
+            } else {
+                // Could not deserialize parameter
+                response.send(CommandError::IncorrectParameterFormat.into()).await.ok();
+            }
+        }.boxed()
     }
 }
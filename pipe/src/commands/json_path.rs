@@ -0,0 +1,170 @@
+use super::command_stream::*;
+
+use serde::{Deserialize, Serialize};
+use serde_json;
+
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+///
+/// A single step in a JSON path
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum JsonPathElement {
+    /// Reads a key from a JSON object (`.key`)
+    Key(String),
+
+    /// Reads an item from a JSON array (`[index]`)
+    Index(usize),
+}
+
+///
+/// A path that selects a value from within a JSON value, such as `.items[0].name`
+///
+/// Paths are made up of object keys, written as `.key`, and array indices, written as `[index]`. An empty path selects
+/// the whole value.
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct JsonPath(pub Vec<JsonPathElement>);
+
+impl JsonPath {
+    ///
+    /// Parses a JSON path from a string (eg, `.items[0].name`)
+    ///
+    /// Keys are made up of the same characters as a variable name (letters, digits, '_' and ':')
+    ///
+    #[allow(clippy::result_unit_err)]   // There's only one way for parsing to fail
+    pub fn parse(path: &str) -> Result<JsonPath, ()> {
+        let mut elements    = vec![];
+        let mut characters  = path.chars().peekable();
+
+        while let Some(next_chr) = characters.next() {
+            match next_chr {
+                '.' => {
+                    // Key is all of the characters up to the next separator
+                    let mut key = String::new();
+
+                    while let Some(key_chr) = characters.peek() {
+                        if key_chr.is_alphabetic() || key_chr.is_ascii_digit() || *key_chr == '_' || *key_chr == ':' {
+                            key.push(*key_chr);
+                            characters.next();
+                        } else {
+                            break;
+                        }
+                    }
+
+                    if key.is_empty() { return Err(()); }
+                    elements.push(JsonPathElement::Key(key));
+                }
+
+                '[' => {
+                    // Index is a number followed by a closing bracket
+                    let mut index = String::new();
+
+                    loop {
+                        match characters.next() {
+                            Some(']')                                       => { break; }
+                            Some(index_chr) if index_chr.is_ascii_digit()   => { index.push(index_chr); }
+                            _                                               => { return Err(()); }
+                        }
+                    }
+
+                    let index = index.parse::<usize>().map_err(|_| ())?;
+                    elements.push(JsonPathElement::Index(index));
+                }
+
+                _ => { return Err(()); }
+            }
+        }
+
+        Ok(JsonPath(elements))
+    }
+
+    ///
+    /// Parses a variable reference that can be followed by a path (eg, `$x.items[0].name`), returning the variable name and the path
+    ///
+    #[allow(clippy::result_unit_err)]   // There's only one way for parsing to fail
+    pub fn parse_variable_reference(reference: &str) -> Result<(VariableName, JsonPath), ()> {
+        let reference   = reference.strip_prefix('$').ok_or(())?;
+        let path_start  = reference.find(['.', '[']).unwrap_or(reference.len());
+
+        let (name, path) = reference.split_at(path_start);
+        if name.is_empty() { return Err(()); }
+
+        Ok((VariableName(name.to_string()), JsonPath::parse(path)?))
+    }
+
+    ///
+    /// True if this path selects the whole value
+    ///
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    ///
+    /// Retrieves the value that this path refers to, or `None` if the value does not contain this path
+    ///
+    pub fn evaluate<'a>(&self, value: &'a serde_json::Value) -> Option<&'a serde_json::Value> {
+        self.0.iter().try_fold(value, |value, element| {
+            match element {
+                JsonPathElement::Key(key)       => value.as_object()?.get(key),
+                JsonPathElement::Index(index)   => value.as_array()?.get(*index),
+            }
+        })
+    }
+}
+
+impl Display for JsonPath {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for element in self.0.iter() {
+            match element {
+                JsonPathElement::Key(key)       => write!(f, ".{}", key)?,
+                JsonPathElement::Index(index)   => write!(f, "[{}]", index)?,
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn parse_keys_and_indices() {
+        let path = JsonPath::parse(".items[0].name");
+        assert!(path == Ok(JsonPath(vec![JsonPathElement::Key("items".to_string()), JsonPathElement::Index(0), JsonPathElement::Key("name".to_string())])), "{:?}", path);
+    }
+
+    #[test]
+    fn parse_invalid_path() {
+        assert!(JsonPath::parse(".items[0").is_err());
+        assert!(JsonPath::parse(".items[a]").is_err());
+        assert!(JsonPath::parse("..items").is_err());
+        assert!(JsonPath::parse("items").is_err());
+    }
+
+    #[test]
+    fn parse_variable_reference_with_path() {
+        let reference = JsonPath::parse_variable_reference("$x.items[1]");
+        assert!(reference == Ok((VariableName("x".to_string()), JsonPath(vec![JsonPathElement::Key("items".to_string()), JsonPathElement::Index(1)]))), "{:?}", reference);
+    }
+
+    #[test]
+    fn evaluate_nested_path() {
+        let value   = serde_json::json!({ "items": [ { "name": "first" }, { "name": "second" } ] });
+        let path    = JsonPath::parse(".items[1].name").unwrap();
+
+        assert!(path.evaluate(&value) == Some(&serde_json::Value::String("second".to_string())));
+    }
+
+    #[test]
+    fn evaluate_missing_path() {
+        let value = serde_json::json!({ "items": [ { "name": "first" } ] });
+
+        assert!(JsonPath::parse(".items[1].name").unwrap().evaluate(&value).is_none());
+        assert!(JsonPath::parse(".other").unwrap().evaluate(&value).is_none());
+        assert!(JsonPath::parse(".items.name").unwrap().evaluate(&value).is_none());
+    }
+}
@@ -3,9 +3,13 @@ mod command_stream;
 pub (crate) mod parse_command;
 mod json_command;
 mod json_command_launcher;
+mod json_path;
+mod parse_error;
 
 pub use command_program::*;
 pub use command_stream::*;
 pub use parse_command::*;
 pub use json_command::*;
 pub use json_command_launcher::*;
+pub use json_path::*;
+pub use parse_error::*;
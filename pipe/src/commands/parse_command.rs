@@ -1,7 +1,10 @@
 use super::command_stream::*;
+use super::json_path::*;
 use crate::parser::*;
 
 use futures::prelude::*;
+use futures::future::{BoxFuture};
+use itertools::*;
 
 ///
 /// Tokens from the command stream
@@ -20,7 +23,13 @@ pub enum CommandToken {
     /// The '=' symbol, used to record a command result in a variable
     Equals,
 
-    /// A '// comment'
+    /// A '$variable' reference, used to refer to a value stored by an assignment
+    Variable,
+
+    /// A '#id' request ID, used to tag the responses to a command
+    RequestId,
+
+    /// A '// comment' or '# comment'
     Comment,
 
     /// Whitespace ending in a newline (also used to end a command)
@@ -56,6 +65,8 @@ impl TokenMatcher<CommandToken> for CommandToken {
         match self {
             CommandToken::Command   => match_command(lookahead, eof),
             CommandToken::Comment   => match_command_comment(lookahead, eof),
+            CommandToken::Variable  => match_variable(lookahead, eof),
+            CommandToken::RequestId => match_request_id(lookahead, eof),
             CommandToken::Pipe      => if lookahead.starts_with("|") { TokenMatchResult::Matches(CommandToken::Pipe, 1) } else { TokenMatchResult::LookaheadCannotMatch },
             CommandToken::SemiColon => if lookahead.starts_with(";") { TokenMatchResult::Matches(CommandToken::SemiColon, 1) } else { TokenMatchResult::LookaheadCannotMatch },
             CommandToken::Equals    => if lookahead.starts_with("=") { TokenMatchResult::Matches(CommandToken::Equals, 1) } else { TokenMatchResult::LookaheadCannotMatch },
@@ -99,6 +110,8 @@ impl<TStream> Tokenizer<CommandToken, TStream> {
             .with_matcher(CommandToken::Pipe)
             .with_matcher(CommandToken::SemiColon)
             .with_matcher(CommandToken::Equals)
+            .with_matcher(CommandToken::Variable)
+            .with_matcher(CommandToken::RequestId)
             .with_matcher(CommandToken::Newline);
 
         self
@@ -108,10 +121,18 @@ impl<TStream> Tokenizer<CommandToken, TStream> {
 ///
 /// Matches against the command syntax
 ///
+/// Commands that start with ':' must start with '::', so the ':' that separates the key and value in a JSON object is not read as a command
+///
 fn match_command(lookahead: &str, eof: bool) -> TokenMatchResult<CommandToken> {
     let mut characters = lookahead.chars();
     let mut len = 0;
 
+    match lookahead.chars().take(2).collect::<Vec<_>>().as_slice() {
+        [':']                                   => { return if eof { TokenMatchResult::LookaheadCannotMatch } else { TokenMatchResult::LookaheadIsPrefix }; }
+        [':', second_chr] if *second_chr != ':' => { return TokenMatchResult::LookaheadCannotMatch; }
+        _                                       => { }
+    }
+
     if let Some(first_chr) = characters.next() {
         if first_chr.is_alphabetic() || first_chr == '_' || first_chr == ':' {
             // Will match a command of some description
@@ -141,41 +162,133 @@ fn match_command(lookahead: &str, eof: bool) -> TokenMatchResult<CommandToken> {
 }
 
 ///
-/// Matches against the comment syntax
+/// Matches against the variable syntax ('$' followed by an identifier, which can be followed by a JSON path such as `.items[0]`)
 ///
-fn match_command_comment(lookahead: &str, eof: bool) -> TokenMatchResult<CommandToken> {
-    let mut chrs = lookahead.chars();
+fn match_variable(lookahead: &str, eof: bool) -> TokenMatchResult<CommandToken> {
+    let mut characters = lookahead.chars();
+
+    match characters.next() {
+        Some('$')   => { }
+        Some(_)     => { return TokenMatchResult::LookaheadCannotMatch; }
+        None        => { return TokenMatchResult::LookaheadIsPrefix; }
+    }
+
+    // The name is made up of the same characters as a command name (the path is checked when the variable is parsed). A ']' is only
+    // part of the path if it closes a '[', so that a variable can be the last item in an array (eg, `[$x]`)
+    let mut len         = 1;
+    let mut in_index    = false;
+
+    for next_chr in characters {
+        let is_name_chr = next_chr.is_alphabetic() || next_chr.is_ascii_digit() || next_chr == '_' || next_chr == ':';
+        let is_path_chr = len > 1 && if in_index { next_chr == ']' } else { next_chr == '.' || next_chr == '[' };
 
-    if let Some(chr) = chrs.next() {
-        // Starts with '//'
-        if chr != '/' { return TokenMatchResult::LookaheadCannotMatch; }
+        if is_name_chr || is_path_chr {
+            if next_chr == '[' { in_index = true; }
+            if next_chr == ']' { in_index = false; }
 
-        if let Some(chr) = chrs.next() {
-            if chr != '/' { return TokenMatchResult::LookaheadCannotMatch; }
-        } else if !eof {
-            return TokenMatchResult::LookaheadIsPrefix;
+            len += 1;
+        } else if len > 1 {
+            return TokenMatchResult::Matches(CommandToken::Variable, len);
         } else {
             return TokenMatchResult::LookaheadCannotMatch;
         }
+    }
 
-        // Everthing up to the next '\n' matches
-        let mut len = 2;
-        while let Some(chr) = chrs.next() {
-            if chr == '\n' || chr == '\r' {
-                return TokenMatchResult::Matches(CommandToken::Comment, len+1);
-            }
+    if !eof {
+        TokenMatchResult::LookaheadIsPrefix
+    } else if len > 1 {
+        TokenMatchResult::Matches(CommandToken::Variable, len)
+    } else {
+        TokenMatchResult::LookaheadCannotMatch
+    }
+}
 
-            len += 1;
-        }
+///
+/// Matches against the request ID syntax ('@' followed by letters, digits or any of '_', '-', '.' or ':')
+///
+/// '@' is used rather than '#' so that request IDs can't be confused with comments (eg, `#TODO fix this` is always a comment)
+///
+fn match_request_id(lookahead: &str, eof: bool) -> TokenMatchResult<CommandToken> {
+    let mut characters = lookahead.chars();
+
+    match characters.next() {
+        Some('@')   => { }
+        Some(_)     => { return TokenMatchResult::LookaheadCannotMatch; }
+        None        => { return TokenMatchResult::LookaheadIsPrefix; }
+    }
+
+    let mut len = 1;
 
-        if !eof {
-            TokenMatchResult::LookaheadIsPrefix
+    for next_chr in characters {
+        if is_request_id_chr(next_chr) {
+            len += 1;
+        } else if len > 1 {
+            return TokenMatchResult::Matches(CommandToken::RequestId, len);
         } else {
-            return TokenMatchResult::Matches(CommandToken::Comment, len);
+            return TokenMatchResult::LookaheadCannotMatch;
         }
+    }
+
+    if !eof {
+        TokenMatchResult::LookaheadIsPrefix
+    } else if len > 1 {
+        TokenMatchResult::Matches(CommandToken::RequestId, len)
     } else {
+        TokenMatchResult::LookaheadCannotMatch
+    }
+}
+
+///
+/// True if a character can be part of a request ID
+///
+#[inline]
+fn is_request_id_chr(chr: char) -> bool {
+    chr.is_alphanumeric() || chr == '_' || chr == '-' || chr == '.' || chr == ':'
+}
+
+///
+/// Matches against the comment syntax
+///
+/// Comments start with '//' or '#' and carry on to the end of the line (the newline itself is not part of the comment, so it still
+/// ends any command that the comment follows).
+///
+fn match_command_comment(lookahead: &str, eof: bool) -> TokenMatchResult<CommandToken> {
+    let mut chrs = lookahead.chars();
+
+    let mut len = match chrs.next() {
+        Some('/') => {
+            // Starts with '//'
+            match chrs.next() {
+                Some('/')           => 2,
+                None if !eof        => { return TokenMatchResult::LookaheadIsPrefix; }
+                _                   => { return TokenMatchResult::LookaheadCannotMatch; }
+            }
+        }
+
+        Some('#') => {
+            // Starts with '#'
+            1
+        }
+
+        Some(_) => { return TokenMatchResult::LookaheadCannotMatch; }
+
         // Empty string can be a prefix of anything
+        None    => { return TokenMatchResult::LookaheadIsPrefix; }
+    };
+
+    // Everything up to the next newline matches
+    for chr in chrs {
+        if chr == '\n' || chr == '\r' {
+            return TokenMatchResult::Matches(CommandToken::Comment, len);
+        }
+
+        len += 1;
+    }
+
+    if !eof {
         TokenMatchResult::LookaheadIsPrefix
+    } else {
+        TokenMatchResult::Matches(CommandToken::Comment, len)
     }
 }
 
@@ -190,9 +303,10 @@ where
         // Acquire a token from the tokenizer
         let next_match = tokenizer.match_token().await?;
 
-        // Skip over whitespace, then return the first 'sold' value
+        // Skip over whitespace and comments, then return the first 'sold' value
         match next_match.token {
             Some(CommandToken::Json(JsonToken::Whitespace)) => { }
+            Some(CommandToken::Comment)                     => { }
             _ => { break Some(next_match); }
         }
     }
@@ -202,6 +316,17 @@ where
 /// Parses a command from an input stream
 ///
 pub async fn command_parse<TStream>(parser: &mut Parser<TokenMatch<CommandToken>, CommandRequest>, tokenizer: &mut Tokenizer<CommandToken, TStream>) -> Result<(), ()> 
+where
+    TStream: Send + Stream<Item=Vec<u8>>,
+{
+    command_parse_expecting(parser, tokenizer).await.map_err(|_| ())
+}
+
+///
+/// As for `command_parse()`, except the error describes what the parser expected to find (the first token in the lookahead
+/// is the one that could not be parsed, or the lookahead is empty if the input ended early)
+///
+pub (crate) async fn command_parse_expecting<TStream>(parser: &mut Parser<TokenMatch<CommandToken>, CommandRequest>, tokenizer: &mut Tokenizer<CommandToken, TStream>) -> Result<(), &'static str> 
 where
     TStream: Send + Stream<Item=Vec<u8>>,
 {
@@ -210,38 +335,145 @@ where
 
         if let Some(lookahead) = lookahead {
             match lookahead.token {
-                Some(CommandToken::Newline) => { parser.skip_token(); }
-                Some(CommandToken::Command) => { command_parse_command(parser, tokenizer).await?; break Ok(()); }
+                Some(CommandToken::Newline)     |
+                Some(CommandToken::SemiColon)   => { parser.skip_token(); }
+                Some(CommandToken::Command)     => { command_parse_command(parser, tokenizer).await?; break Ok(()); }
+                Some(CommandToken::RequestId)   => { command_parse_with_request_id(parser, tokenizer).await?; break Ok(()); }
 
-                _ => { break Err(()); }
+                _ => { break Err("a command"); }
             }
         } else {
             // No symbol
-            break Err(());
+            break Err("a command");
         }
     }
 }
 
+///
+/// Parses a command that's preceded by a request ID, at the point where the lookahead contains the 'RequestId' token
+///
+async fn command_parse_with_request_id<TStream>(parser: &mut Parser<TokenMatch<CommandToken>, CommandRequest>, tokenizer: &mut Tokenizer<CommandToken, TStream>) -> Result<(), &'static str>
+where
+    TStream: Send + Stream<Item=Vec<u8>>,
+{
+    // Lookahead must be a 'RequestId'
+    let request_id = parser.lookahead(0, tokenizer, |tokenizer| command_read_token(tokenizer).boxed()).await.ok_or("a request ID")?;
+    if request_id.token != Some(CommandToken::RequestId) { return Err("a request ID"); }
+
+    parser.accept_token().map_err(|_| "a request ID")?;
+
+    // The ID must be followed by a command
+    let command_name = parser.lookahead(0, tokenizer, |tokenizer| command_read_token(tokenizer).boxed()).await.ok_or("a command after the request ID")?;
+    if command_name.token != Some(CommandToken::Command) { return Err("a command after the request ID"); }
+
+    command_parse_command(parser, tokenizer).await?;
+
+    parser.reduce(2, |tagged| {
+        let id      = tagged[0].token().unwrap().fragment[1..].to_string();
+        let request = tagged[1].node().unwrap().clone();
+
+        CommandRequest::WithRequestId { id: RequestId(id), request: Box::new(request) }
+    }).map_err(|_| "a command after the request ID")?;
+
+    Ok(())
+}
+
 ///
 /// Parses a command, at the point where the lookahead contains the 'Command' token
 ///
+/// This is either a pipeline of command invocations (`<name> <argument> | <name> <argument>`) or an assignment (`<variable> = <pipeline>`)
 ///
-async fn command_parse_command<TStream>(parser: &mut Parser<TokenMatch<CommandToken>, CommandRequest>, tokenizer: &mut Tokenizer<CommandToken, TStream>) -> Result<(), ()>
+async fn command_parse_command<TStream>(parser: &mut Parser<TokenMatch<CommandToken>, CommandRequest>, tokenizer: &mut Tokenizer<CommandToken, TStream>) -> Result<(), &'static str>
 where
     TStream: Send + Stream<Item=Vec<u8>>,
- {
+{
     // Lookahead must be a 'Command'
-    let command_name = parser.lookahead(0, tokenizer, |tokenizer| command_read_token(tokenizer).boxed()).await.ok_or(())?;
-    if command_name.token != Some(CommandToken::Command) { return Err(()); }
+    let command_name = parser.lookahead(0, tokenizer, |tokenizer| command_read_token(tokenizer).boxed()).await.ok_or("a command")?;
+    if command_name.token != Some(CommandToken::Command) { return Err("a command"); }
 
-    parser.accept_token().map_err(|_| ())?;
+    // If the command is followed by an '=' then it's an assignment
+    let maybe_equals = parser.lookahead(1, tokenizer, |tokenizer| command_read_token(tokenizer).boxed()).await;
+
+    if maybe_equals.map(|token| token.token) == Some(Some(CommandToken::Equals)) {
+        // Assignment is '<variable> = <command>'
+        parser.accept_token().map_err(|_| "a command")?;
+        parser.accept_token().map_err(|_| "a command")?;
+
+        // The value is the following command
+        let command_name = parser.lookahead(0, tokenizer, |tokenizer| command_read_token(tokenizer).boxed()).await.ok_or("a command after '='")?;
+        if command_name.token != Some(CommandToken::Command) { return Err("a command after '='"); }
+
+        command_parse_pipeline(parser, tokenizer).await?;
+
+        parser.reduce(3, |assignment| {
+            let variable    = assignment[0].token().unwrap().fragment.clone();
+            let from        = assignment[2].node().unwrap().clone();
+
+            CommandRequest::Assign { variable: VariableName(variable), from: Box::new(from) }
+        }).map_err(|_| "a command")?;
+
+        Ok(())
+    } else {
+        // Just a command on its own
+        command_parse_pipeline(parser, tokenizer).await
+    }
+}
+
+///
+/// Parses a command invocation, followed by any number of '| <command>' sections
+///
+async fn command_parse_pipeline<TStream>(parser: &mut Parser<TokenMatch<CommandToken>, CommandRequest>, tokenizer: &mut Tokenizer<CommandToken, TStream>) -> Result<(), &'static str>
+where
+    TStream: Send + Stream<Item=Vec<u8>>,
+{
+    // The first command is the start of the pipeline
+    command_parse_invocation(parser, tokenizer).await?;
+
+    loop {
+        // Carry on for as long as the command is followed by a pipe
+        let maybe_pipe = parser.lookahead(0, tokenizer, |tokenizer| command_read_token(tokenizer).boxed()).await;
+        if maybe_pipe.map(|token| token.token) != Some(Some(CommandToken::Pipe)) { break; }
+
+        parser.accept_token().map_err(|_| "a command after '|'")?;
+
+        // Pipe must be followed by another command
+        let command_name = parser.lookahead(0, tokenizer, |tokenizer| command_read_token(tokenizer).boxed()).await.ok_or("a command after '|'")?;
+        if command_name.token != Some(CommandToken::Command) { return Err("a command after '|'"); }
+
+        command_parse_invocation(parser, tokenizer).await?;
+
+        // Pipes are left-associative, so 'a | b | c' is '(a | b) | c'
+        parser.reduce(3, |pipe| {
+            let from    = pipe[0].node().unwrap().clone();
+            let to      = pipe[2].node().unwrap().clone();
+
+            CommandRequest::Pipe { from: Box::new(from), to: Box::new(to) }
+        }).map_err(|_| "a command after '|'")?;
+    }
+
+    Ok(())
+}
+
+///
+/// Parses a command invocation, at the point where the lookahead contains the 'Command' token
+///
+async fn command_parse_invocation<TStream>(parser: &mut Parser<TokenMatch<CommandToken>, CommandRequest>, tokenizer: &mut Tokenizer<CommandToken, TStream>) -> Result<(), &'static str>
+where
+    TStream: Send + Stream<Item=Vec<u8>>,
+{
+    // Lookahead must be a 'Command'
+    let command_name = parser.lookahead(0, tokenizer, |tokenizer| command_read_token(tokenizer).boxed()).await.ok_or("a command")?;
+    if command_name.token != Some(CommandToken::Command) { return Err("a command"); }
+
+    parser.accept_token().map_err(|_| "a command")?;
 
     // Next lookahead determines the type of command
     let maybe_argument = parser.lookahead(0, tokenizer, |tokenizer| command_read_token(tokenizer).boxed()).await;
     if let Some(maybe_argument) = maybe_argument {
         match maybe_argument.token {
-            Some(CommandToken::Json(_)) => {
-                // Argument is a JSON value which may be followed by a pipe or an equals
+            Some(CommandToken::Json(_))     |
+            Some(CommandToken::Variable)    => {
+                // Argument is a JSON value, which can contain variable references that are substituted when the command is run
                 command_parse_argument(parser, tokenizer).await?;
 
                 parser.reduce(2, |cmd| {
@@ -249,59 +481,249 @@ where
                     let argument    = cmd[1].node().unwrap().clone();
 
                     match argument {
-                        CommandRequest::Command { argument, .. }    => CommandRequest::Command { command: CommandName(name), argument: argument },
-                        _                                           => { unreachable!() }
+                        CommandRequest::Command { argument, .. }                => CommandRequest::Command { command: CommandName(name), argument },
+                        CommandRequest::CommandWithVariables { argument, .. }   => CommandRequest::CommandWithVariables { command: CommandName(name), argument },
+                        _                                                       => { unreachable!() }
                     }
-                }).map_err(|_| ())?;
-
-                // TODO: command can use pipe or an equals here
+                }).map_err(|_| "a command")?;
             }
 
             Some(CommandToken::Newline)     |
-            Some(CommandToken::SemiColon)   => {
-                // Command has no argument
-                parser.accept_token().map_err(|_| ())?;
-
-                parser.reduce(2, |cmd| {
+            Some(CommandToken::SemiColon)   |
+            Some(CommandToken::Pipe)        => {
+                // Command has no argument (the terminating token is left in the lookahead)
+                parser.reduce(1, |cmd| {
                     let name = cmd[0].token().unwrap().fragment.clone();
                     CommandRequest::Command { command: CommandName(name), argument: serde_json::Value::Null }
-                }).map_err(|_| ())?;
+                }).map_err(|_| "a command")?;
             }
 
-            // TODO: command can have no arguments and be followed by a pipe or an equals
-
-            _ => { return Err(()); }
+            _ => { return Err("an argument, '|', ';' or the end of the line"); }
         }
     } else {
         // No argument, so just a command
         parser.reduce(1, |cmd| {
             let name = cmd[0].token().unwrap().fragment.clone();
             CommandRequest::Command { command: CommandName(name), argument: serde_json::Value::Null }
-        }).map_err(|_| ())?;
+        }).map_err(|_| "a command")?;
     }
 
     Ok(())
 }
 
 ///
-/// Parses an argument to a command (resulting in a CommandRequest::Command with no name)
+/// Parses an argument to a command (resulting in a `CommandRequest::Command` or a `CommandRequest::CommandWithVariables` with no name)
 ///
-async fn command_parse_argument<TStream>(parser: &mut Parser<TokenMatch<CommandToken>, CommandRequest>, tokenizer: &mut Tokenizer<CommandToken, TStream>) -> Result<(), ()> 
+async fn command_parse_argument<TStream>(parser: &mut Parser<TokenMatch<CommandToken>, CommandRequest>, tokenizer: &mut Tokenizer<CommandToken, TStream>) -> Result<(), &'static str> 
 where
     TStream: Send + Stream<Item=Vec<u8>>,
 {
-    // Create a JSON parser to read the following JSON value
-    let mut json_parser = Parser::with_lookahead_from(parser);
-    json_parse_value(&mut json_parser, tokenizer).await?;
+    // Create a parser to read the argument, which is a JSON value that can contain variable references
+    let mut argument_parser = Parser::with_lookahead_from(parser);
+    let start               = tokenizer.position();
+    let parse_result        = command_parse_argument_value(&mut argument_parser, tokenizer, start, 0).await;
 
-    // Restore any lookahead to the original parser
-    parser.take_lookahead_from(&mut json_parser);
+    // Restore any lookahead to the original parser (on error, this includes the token that could not be parsed)
+    parser.take_lookahead_from(&mut argument_parser);
+    parse_result.map_err(|_| "a JSON value")?;
 
-    // Fetch the JSON value for the argument
-    let json_value = json_parser.finish().map_err(|_| ())?;
+    // Fetch the argument
+    let argument = argument_parser.finish().map_err(|_| "a JSON value")?;
 
-    // Add as a node to the current parser
-    parser.reduce(0, |_| CommandRequest::Command { command: CommandName("".to_string()), argument: json_value }).map_err(|_| ())?;
+    // Add as a node to the current parser (arguments without any variable references are just JSON values)
+    parser.reduce(0, |_| match argument {
+        CommandArgument::Json(argument) => CommandRequest::Command { command: CommandName("".to_string()), argument },
+        argument                        => CommandRequest::CommandWithVariables { command: CommandName("".to_string()), argument },
+    }).map_err(|_| "a JSON value")?;
+
+    Ok(())
+}
+
+///
+/// Parses a JSON value that can contain variable references, leaving the argument on top of the stack in the parser
+///
+/// The value is nested inside `depth` arrays or objects, and the argument started at the tokenizer position `start`: these are used to
+/// apply the JSON limits that are set for the tokenizer.
+///
+fn command_parse_argument_value<'a, TStream>(parser: &'a mut Parser<TokenMatch<CommandToken>, CommandArgument>, tokenizer: &'a mut Tokenizer<CommandToken, TStream>, start: usize, depth: usize) -> BoxFuture<'a, Result<(), ()>>
+where
+    TStream: Send + Stream<Item=Vec<u8>>,
+{
+    async move {
+        let lookahead = parser.lookahead(0, tokenizer, |tokenizer| json_read_token(tokenizer).boxed()).await.ok_or(())?;
+
+        match lookahead.token {
+            Some(CommandToken::Variable) => {
+                // '$name' or '$name.path'
+                let (name, path) = JsonPath::parse_variable_reference(&lookahead.fragment)?;
+                parser.accept_token().map_err(|_| ())?.reduce(1, |_| CommandArgument::Variable(name, path)).map_err(|_| ())?;
+
+                Ok(())
+            }
+
+            Some(CommandToken::Json(JsonToken::Character('['))) => command_parse_argument_array(parser, tokenizer, start, depth).await,
+            Some(CommandToken::Json(JsonToken::Character('{'))) => command_parse_argument_object(parser, tokenizer, start, depth).await,
+
+            _ => {
+                // Other values can't contain variable references, so they're parsed as plain JSON
+                let mut json_parser = Parser::with_lookahead_from(parser);
+                let parse_result    = json_parse_value(&mut json_parser, tokenizer).await;
+
+                parser.take_lookahead_from(&mut json_parser);
+                parse_result?;
+
+                let value = json_parser.finish().map_err(|_| ())?;
+                parser.reduce(0, |_| CommandArgument::Json(value)).map_err(|_| ())?;
+
+                Ok(())
+            }
+        }
+    }.boxed()
+}
+
+///
+/// Parses an array that can contain variable references, at the point where the lookahead contains the '['
+///
+async fn command_parse_argument_array<TStream>(parser: &mut Parser<TokenMatch<CommandToken>, CommandArgument>, tokenizer: &mut Tokenizer<CommandToken, TStream>, start: usize, depth: usize) -> Result<(), ()>
+where
+    TStream: Send + Stream<Item=Vec<u8>>,
+{
+    // Values can only be nested up to the maximum depth
+    if depth >= tokenizer.json_limits().max_depth { return Err(()); }
+
+    // Accept the initial '['
+    parser.accept_token().map_err(|_| ())?;
+    let mut num_tokens = 1;
+
+    loop {
+        // ']' to finish the array, or else a value
+        let lookahead = parser.lookahead(0, tokenizer, |tokenizer| json_read_token(tokenizer).boxed()).await.ok_or(())?;
+        json_check_size(tokenizer, start)?;
+
+        if lookahead.token == Some(CommandToken::Json(JsonToken::Character(']'))) {
+            parser.accept_token().map_err(|_| ())?;
+            num_tokens += 1;
+            break;
+        }
+
+        command_parse_argument_value(parser, tokenizer, start, depth + 1).await?;
+        num_tokens += 1;
+
+        // ',' for more array or ']' for the end of the array
+        let lookahead = parser.lookahead(0, tokenizer, |tokenizer| json_read_token(tokenizer).boxed()).await.ok_or(())?;
+        json_check_size(tokenizer, start)?;
+
+        match lookahead.token {
+            Some(CommandToken::Json(JsonToken::Character(','))) => {
+                parser.accept_token().map_err(|_| ())?;
+                num_tokens += 1;
+            }
+
+            Some(CommandToken::Json(JsonToken::Character(']'))) => {
+                parser.accept_token().map_err(|_| ())?;
+                num_tokens += 1;
+                break;
+            }
+
+            _ => { return Err(()); }
+        }
+    }
+
+    // Reduce to an argument
+    parser.reduce(num_tokens, |items| {
+        let items = items.into_iter()
+            .skip(1)
+            .tuples()
+            .map(|(item, _comma_or_bracket)| item.to_node().unwrap());
+
+        CommandArgument::array(items.collect())
+    }).map_err(|_| ())?;
+
+    Ok(())
+}
+
+///
+/// Parses an object that can contain variable references, at the point where the lookahead contains the '{'
+///
+async fn command_parse_argument_object<TStream>(parser: &mut Parser<TokenMatch<CommandToken>, CommandArgument>, tokenizer: &mut Tokenizer<CommandToken, TStream>, start: usize, depth: usize) -> Result<(), ()>
+where
+    TStream: Send + Stream<Item=Vec<u8>>,
+{
+    // Values can only be nested up to the maximum depth
+    if depth >= tokenizer.json_limits().max_depth { return Err(()); }
+
+    // Accept the initial '{'
+    parser.accept_token().map_err(|_| ())?;
+    let mut num_tokens = 1;
+
+    loop {
+        // Read two tokens ahead, so the ':' is available after a key
+        parser.ensure_lookahead(1, tokenizer, |tokenizer| json_read_token(tokenizer).boxed()).await;
+
+        // '}' to finish the object, or else a key
+        let lookahead = parser.lookahead(0, tokenizer, |tokenizer| json_read_token(tokenizer).boxed()).await.ok_or(())?;
+        json_check_size(tokenizer, start)?;
+
+        match lookahead.token {
+            Some(CommandToken::Json(JsonToken::Character('}'))) => {
+                parser.accept_token().map_err(|_| ())?;
+                num_tokens += 1;
+                break;
+            }
+
+            Some(CommandToken::Json(JsonToken::String)) => {
+                // <String> : <Value>
+                let key = serde_json::from_str::<String>(&lookahead.fragment).map_err(|_| ())?;
+                parser.accept_token().map_err(|_| ())?.reduce(1, |_| CommandArgument::Json(serde_json::Value::String(key))).map_err(|_| ())?;
+                num_tokens += 1;
+
+                parser.accept_expected_token(|token| token.token == Some(CommandToken::Json(JsonToken::Character(':')))).map_err(|_| ())?;
+                num_tokens += 1;
+
+                command_parse_argument_value(parser, tokenizer, start, depth + 1).await?;
+                num_tokens += 1;
+
+                // ',' for more fields or '}' for the end of the object
+                let lookahead = parser.lookahead(0, tokenizer, |tokenizer| json_read_token(tokenizer).boxed()).await.ok_or(())?;
+                json_check_size(tokenizer, start)?;
+
+                match lookahead.token {
+                    Some(CommandToken::Json(JsonToken::Character(','))) => {
+                        parser.accept_token().map_err(|_| ())?;
+                        num_tokens += 1;
+                    }
+
+                    Some(CommandToken::Json(JsonToken::Character('}'))) => {
+                        parser.accept_token().map_err(|_| ())?;
+                        num_tokens += 1;
+                        break;
+                    }
+
+                    _ => { return Err(()); }
+                }
+            }
+
+            _ => { return Err(()); }
+        }
+    }
+
+    // Reduce to an argument
+    parser.reduce(num_tokens, |fields| {
+        let fields = fields.into_iter()
+            .skip(1)
+            .tuples()
+            .map(|(key, _colon, value, _comma_or_brace)| {
+                // Key should be a string node
+                let key = match key.to_node() {
+                    Some(CommandArgument::Json(serde_json::Value::String(key))) => key,
+                    _                                                           => panic!(),
+                };
+
+                (key, value.to_node().unwrap())
+            });
+
+        CommandArgument::object(fields.collect())
+    }).map_err(|_| ())?;
 
     Ok(())
 }
@@ -313,6 +735,25 @@ mod test {
     use serde_json::*;
     use futures::executor;
 
+    #[test]
+    fn match_hash_comment() {
+        let match_result = match_command_comment("# comment\nnext", false);
+        assert!(match_result == TokenMatchResult::Matches(CommandToken::Comment, "# comment".chars().count()), "{:?}", match_result);
+    }
+
+    #[test]
+    fn hash_followed_by_word_is_comment() {
+        let match_result = match_command_comment("#TODO fix this
+next", false);
+        assert!(match_result == TokenMatchResult::Matches(CommandToken::Comment, "#TODO fix this".chars().count()), "{:?}", match_result);
+    }
+
+    #[test]
+    fn request_id_is_not_comment() {
+        let match_result = match_command_comment("@12 some::command", false);
+        assert!(match_result == TokenMatchResult::LookaheadCannotMatch, "{:?}", match_result);
+    }
+
     #[test]
     fn match_simple_command() {
         let match_result = match_command("test::command", true);
@@ -401,6 +842,38 @@ mod test {
         });
     }
 
+    #[test]
+    fn parse_command_with_quoted_string() {
+        let argument        = stream::iter(r#"echo "hello world""#.bytes()).ready_chunks(2);
+        let mut tokenizer   = Tokenizer::new(argument);
+        let mut parser      = Parser::new();
+
+        tokenizer.with_command_matchers();
+
+        executor::block_on(async {
+            command_parse(&mut parser, &mut tokenizer).await.unwrap();
+            let result = parser.finish().unwrap();
+
+            assert!(result == CommandRequest::Command { command: CommandName("echo".to_string()), argument: json!{"hello world"} }, "{:?}", result);
+        });
+    }
+
+    #[test]
+    fn parse_command_with_escaped_quotes() {
+        let argument        = stream::iter(r#"echo "say \"hello\"\n\tworld \\ \u0021""#.bytes()).ready_chunks(2);
+        let mut tokenizer   = Tokenizer::new(argument);
+        let mut parser      = Parser::new();
+
+        tokenizer.with_command_matchers();
+
+        executor::block_on(async {
+            command_parse(&mut parser, &mut tokenizer).await.unwrap();
+            let result = parser.finish().unwrap();
+
+            assert!(result == CommandRequest::Command { command: CommandName("echo".to_string()), argument: json!{"say \"hello\"\n\tworld \\ !"} }, "{:?}", result);
+        });
+    }
+
     #[test]
     fn parse_command_with_arguments_and_newline() {
         let argument        = stream::iter("some::command [ 1, 2, 3, 4 ]\n".bytes()).ready_chunks(2);
@@ -463,4 +936,285 @@ mod test {
             assert!(result == CommandRequest::Command { command: CommandName("and_another".to_string()), argument: json!{["Hello"]} });
         });
     }
+
+    #[test]
+    fn command_stream_continues_after_error() {
+        let input       = stream::iter("some::command [ 1, 2 }\nanother::command\n@@@\none_more [ 1 ]\n".bytes()).ready_chunks(2);
+        let commands    = parse_command_stream(input.boxed());
+
+        executor::block_on(async {
+            let commands = commands.collect::<Vec<_>>().await;
+
+            assert!(commands.len() == 4, "{:?}", commands);
+            assert!(commands[0].is_err(), "{:?}", commands);
+            assert!(commands[1] == Ok(CommandRequest::Command { command: CommandName("another::command".to_string()), argument: serde_json::Value::Null }), "{:?}", commands);
+            assert!(commands[2].is_err(), "{:?}", commands);
+            assert!(commands[3] == Ok(CommandRequest::Command { command: CommandName("one_more".to_string()), argument: json!{[1]} }), "{:?}", commands);
+        });
+    }
+
+    #[test]
+    fn command_stream_skips_comments() {
+        let script      = "# Commands with comments\n\nsome::command [ 1, 2 ] # Trailing comment\n  # Indented comment\n// Another comment\nanother::command # Comment after a command with no argument\n#TODO fix this\n@12 tagged::command\n#\n";
+        let input       = stream::iter(script.bytes()).ready_chunks(2);
+        let commands    = parse_command_stream(input.boxed());
+
+        executor::block_on(async {
+            let commands = commands.collect::<Vec<_>>().await;
+
+            assert!(commands.len() == 3, "{:?}", commands);
+            assert!(commands[0] == Ok(CommandRequest::Command { command: CommandName("some::command".to_string()), argument: json!{[1, 2]} }), "{:?}", commands);
+            assert!(commands[1] == Ok(CommandRequest::Command { command: CommandName("another::command".to_string()), argument: serde_json::Value::Null }), "{:?}", commands);
+            assert!(commands[2] == Ok(CommandRequest::WithRequestId {
+                id:         RequestId("12".to_string()),
+                request:    Box::new(CommandRequest::Command { command: CommandName("tagged::command".to_string()), argument: serde_json::Value::Null }),
+            }), "{:?}", commands);
+        });
+    }
+
+    #[test]
+    fn command_stream_rejects_arguments_over_limits() {
+        let deep_value  = format!("{}{}", "[".repeat(20), "]".repeat(20));
+        let large_value = format!("[ {} 1 ]", "1, ".repeat(100));
+        let script      = format!("deep::command {}\nlarge::command {}\nsmall::command [ [ 1, 2 ] ]\n", deep_value, large_value);
+        let input       = stream::iter(script.into_bytes()).ready_chunks(16);
+        let commands    = parse_command_stream_with_limits(input.boxed(), JsonLimits { max_bytes: 256, max_depth: 8 });
+
+        executor::block_on(async {
+            let commands = commands.collect::<Vec<_>>().await;
+
+            assert!(commands.len() == 3, "{:?}", commands);
+            assert!(commands[0].is_err(), "{:?}", commands);
+            assert!(commands[1].is_err(), "{:?}", commands);
+            assert!(commands[2] == Ok(CommandRequest::Command { command: CommandName("small::command".to_string()), argument: json!{[[1, 2]]} }), "{:?}", commands);
+        });
+    }
+
+    #[test]
+    fn command_stream_error_for_partial_command() {
+        let input       = stream::iter("some::command [ 1, 2".bytes()).ready_chunks(2);
+        let commands    = parse_command_stream(input.boxed());
+
+        executor::block_on(async {
+            let commands = commands.collect::<Vec<_>>().await;
+
+            assert!(commands.len() == 1, "{:?}", commands);
+            assert!(commands[0].is_err(), "{:?}", commands);
+        });
+    }
+
+    #[test]
+    fn match_variable_reference() {
+        let match_result = match_variable("$some_var ", false);
+        assert!(match_result == TokenMatchResult::Matches(CommandToken::Variable, "$some_var".chars().count()), "{:?}", match_result);
+    }
+
+    #[test]
+    fn match_variable_reference_with_path() {
+        let match_result = match_variable("$some_var.items[0].name ", false);
+        assert!(match_result == TokenMatchResult::Matches(CommandToken::Variable, "$some_var.items[0].name".chars().count()), "{:?}", match_result);
+    }
+
+    #[test]
+    fn match_variable_at_end_of_array() {
+        let match_result = match_variable("$some_var] ", false);
+        assert!(match_result == TokenMatchResult::Matches(CommandToken::Variable, "$some_var".chars().count()), "{:?}", match_result);
+    }
+
+    #[test]
+    fn match_request_id_token() {
+        let match_result = match_request_id("@req-12 ", false);
+        assert!(match_result == TokenMatchResult::Matches(CommandToken::RequestId, "@req-12".chars().count()), "{:?}", match_result);
+    }
+
+    #[test]
+    fn parse_command_with_request_id() {
+        let argument        = stream::iter("@12 some::command [ 1, 2 ]\nanother::command\n".bytes()).ready_chunks(2);
+        let mut tokenizer   = Tokenizer::new(argument);
+        let mut parser      = Parser::new();
+
+        tokenizer.with_command_matchers();
+
+        executor::block_on(async {
+            command_parse(&mut parser, &mut tokenizer).await.unwrap();
+            let result = parser.finish().unwrap();
+            assert!(result == CommandRequest::WithRequestId {
+                id:         RequestId("12".to_string()),
+                request:    Box::new(CommandRequest::Command { command: CommandName("some::command".to_string()), argument: json!{[1, 2]} }),
+            }, "{:?}", result);
+
+            // The ID only applies to the one command
+            command_parse(&mut parser, &mut tokenizer).await.unwrap();
+            let result = parser.finish().unwrap();
+            assert!(result == CommandRequest::Command { command: CommandName("another::command".to_string()), argument: serde_json::Value::Null }, "{:?}", result);
+        });
+    }
+
+    #[test]
+    fn parse_assignment_with_request_id() {
+        let argument        = stream::iter("@a x = some::command 5\n".bytes()).ready_chunks(2);
+        let mut tokenizer   = Tokenizer::new(argument);
+        let mut parser      = Parser::new();
+
+        tokenizer.with_command_matchers();
+
+        executor::block_on(async {
+            command_parse(&mut parser, &mut tokenizer).await.unwrap();
+            let result = parser.finish().unwrap();
+
+            assert!(result == CommandRequest::WithRequestId {
+                id:         RequestId("a".to_string()),
+                request:    Box::new(CommandRequest::Assign { 
+                    variable:   VariableName("x".to_string()), 
+                    from:       Box::new(CommandRequest::Command { command: CommandName("some::command".to_string()), argument: json!{5} }),
+                }),
+            }, "{:?}", result);
+        });
+    }
+
+    #[test]
+    fn parse_assignment() {
+        let argument        = stream::iter("x = some::command 5\n".bytes()).ready_chunks(2);
+        let mut tokenizer   = Tokenizer::new(argument);
+        let mut parser      = Parser::new();
+
+        tokenizer.with_command_matchers();
+
+        executor::block_on(async {
+            command_parse(&mut parser, &mut tokenizer).await.unwrap();
+            let result = parser.finish().unwrap();
+
+            assert!(result == CommandRequest::Assign { 
+                variable:   VariableName("x".to_string()), 
+                from:       Box::new(CommandRequest::Command { command: CommandName("some::command".to_string()), argument: json!{5} }),
+            }, "{:?}", result);
+        });
+    }
+
+    #[test]
+    fn parse_assignment_followed_by_command() {
+        let argument        = stream::iter("x = some::command\nanother::command $x\n".bytes()).ready_chunks(2);
+        let mut tokenizer   = Tokenizer::new(argument);
+        let mut parser      = Parser::new();
+
+        tokenizer.with_command_matchers();
+
+        executor::block_on(async {
+            command_parse(&mut parser, &mut tokenizer).await.unwrap();
+            let result = parser.finish().unwrap();
+            assert!(result == CommandRequest::Assign { 
+                variable:   VariableName("x".to_string()), 
+                from:       Box::new(CommandRequest::Command { command: CommandName("some::command".to_string()), argument: serde_json::Value::Null }),
+            }, "{:?}", result);
+
+            command_parse(&mut parser, &mut tokenizer).await.unwrap();
+            let result = parser.finish().unwrap();
+            assert!(result == CommandRequest::CommandWithVariables { command: CommandName("another::command".to_string()), argument: CommandArgument::Variable(VariableName("x".to_string()), JsonPath(vec![])) }, "{:?}", result);
+        });
+    }
+
+    #[test]
+    fn parse_object_argument() {
+        let argument        = stream::iter("some::command { \"a\": 1, \"b\":2 }\n".bytes()).ready_chunks(2);
+        let mut tokenizer   = Tokenizer::new(argument);
+        let mut parser      = Parser::new();
+
+        tokenizer.with_command_matchers();
+
+        executor::block_on(async {
+            command_parse(&mut parser, &mut tokenizer).await.unwrap();
+            let result = parser.finish().unwrap();
+
+            assert!(result == CommandRequest::Command { command: CommandName("some::command".to_string()), argument: json!{{ "a": 1, "b": 2 }} }, "{:?}", result);
+        });
+    }
+
+    #[test]
+    fn parse_variables_in_argument() {
+        let argument        = stream::iter("some::command [ $x, \"$y\", { \"z\": $z.items[0] }, [$x] ]\n".bytes()).ready_chunks(2);
+        let mut tokenizer   = Tokenizer::new(argument);
+        let mut parser      = Parser::new();
+
+        tokenizer.with_command_matchers();
+
+        executor::block_on(async {
+            command_parse(&mut parser, &mut tokenizer).await.unwrap();
+            let result = parser.finish().unwrap();
+
+            // Strings are never variable references, even if they start with '$'
+            let x = CommandArgument::Variable(VariableName("x".to_string()), JsonPath(vec![]));
+            let z = CommandArgument::Variable(VariableName("z".to_string()), JsonPath(vec![JsonPathElement::Key("items".to_string()), JsonPathElement::Index(0)]));
+
+            assert!(result == CommandRequest::CommandWithVariables { 
+                command:    CommandName("some::command".to_string()), 
+                argument:   CommandArgument::Array(vec![
+                    x.clone(),
+                    CommandArgument::Json(json!{"$y"}),
+                    CommandArgument::Object(vec![("z".to_string(), z)]),
+                    CommandArgument::Array(vec![x]),
+                ]),
+            }, "{:?}", result);
+        });
+    }
+
+    #[test]
+    fn parse_string_that_looks_like_variable() {
+        let argument        = stream::iter("echo { \"price\": \"$total\" }\n".bytes()).ready_chunks(2);
+        let mut tokenizer   = Tokenizer::new(argument);
+        let mut parser      = Parser::new();
+
+        tokenizer.with_command_matchers();
+
+        executor::block_on(async {
+            command_parse(&mut parser, &mut tokenizer).await.unwrap();
+            let result = parser.finish().unwrap();
+
+            assert!(result == CommandRequest::Command { command: CommandName("echo".to_string()), argument: json!{{ "price": "$total" }} }, "{:?}", result);
+        });
+    }
+
+    #[test]
+    fn parse_pipe() {
+        let argument        = stream::iter("first::command | second::command [ 1, 2 ] | third::command\n".bytes()).ready_chunks(2);
+        let mut tokenizer   = Tokenizer::new(argument);
+        let mut parser      = Parser::new();
+
+        tokenizer.with_command_matchers();
+
+        executor::block_on(async {
+            command_parse(&mut parser, &mut tokenizer).await.unwrap();
+            let result = parser.finish().unwrap();
+
+            let first   = CommandRequest::Command { command: CommandName("first::command".to_string()), argument: serde_json::Value::Null };
+            let second  = CommandRequest::Command { command: CommandName("second::command".to_string()), argument: json!{[1, 2]} };
+            let third   = CommandRequest::Command { command: CommandName("third::command".to_string()), argument: serde_json::Value::Null };
+
+            assert!(result == CommandRequest::Pipe { 
+                from:   Box::new(CommandRequest::Pipe { from: Box::new(first), to: Box::new(second) }),
+                to:     Box::new(third),
+            }, "{:?}", result);
+        });
+    }
+
+    #[test]
+    fn parse_assign_pipe() {
+        let argument        = stream::iter("x = first::command 1 | second::command\n".bytes()).ready_chunks(2);
+        let mut tokenizer   = Tokenizer::new(argument);
+        let mut parser      = Parser::new();
+
+        tokenizer.with_command_matchers();
+
+        executor::block_on(async {
+            command_parse(&mut parser, &mut tokenizer).await.unwrap();
+            let result = parser.finish().unwrap();
+
+            let first   = CommandRequest::Command { command: CommandName("first::command".to_string()), argument: json!{1} };
+            let second  = CommandRequest::Command { command: CommandName("second::command".to_string()), argument: serde_json::Value::Null };
+
+            assert!(result == CommandRequest::Assign { 
+                variable:   VariableName("x".to_string()),
+                from:       Box::new(CommandRequest::Pipe { from: Box::new(first), to: Box::new(second) }),
+            }, "{:?}", result);
+        });
+    }
 }
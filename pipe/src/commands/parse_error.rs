@@ -0,0 +1,59 @@
+use serde::{Deserialize, Serialize};
+
+use std::fmt;
+use std::fmt::{Display, Formatter};
+
+///
+/// A location within the source text of a command
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CommandLocation {
+    /// The offset in bytes from the start of the source text
+    pub offset: usize,
+
+    /// The line number (the first line is line 1)
+    pub line: usize,
+
+    /// The column number, counted in characters (the first character of a line is column 1)
+    pub column: usize,
+}
+
+///
+/// An error generated while parsing a command, with the location where the parser found a problem
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct CommandParseError {
+    /// The location of the first token that could not be parsed (or the end of the source text if the command was incomplete)
+    pub location: CommandLocation,
+
+    /// A description of what the parser was expecting to find at this location (eg, `a command`)
+    pub expected: String,
+}
+
+impl CommandLocation {
+    ///
+    /// Finds the line and column of a byte offset within some source text
+    ///
+    pub fn with_offset(source: &str, offset: usize) -> CommandLocation {
+        let preceding   = source.get(..offset).unwrap_or(source);
+        let line_start  = preceding.rfind('\n').map(|newline_pos| newline_pos + 1).unwrap_or(0);
+
+        CommandLocation {
+            offset,
+            line:   preceding.matches('\n').count() + 1,
+            column: preceding[line_start..].chars().count() + 1,
+        }
+    }
+}
+
+impl Display for CommandLocation {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+impl Display for CommandParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "Expected {} at {}", self.expected, self.location)
+    }
+}
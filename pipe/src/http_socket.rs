@@ -0,0 +1,230 @@
+use crate::commands::*;
+use crate::socket::*;
+
+use flo_scene::*;
+use flo_scene::programs::*;
+
+use futures::prelude::*;
+use futures::stream;
+use futures::{pin_mut};
+
+use hyper::{Method, Request, Response, StatusCode};
+use hyper::body::{Bytes, Incoming};
+use hyper::header::{CONTENT_TYPE};
+use hyper::server::conn::http1;
+use hyper::service::{service_fn};
+use hyper_util::rt::{TokioIo, TokioTimer};
+use http_body_util::{BodyExt, Full, LengthLimitError, Limited};
+
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+use std::convert::{Infallible};
+use std::iter;
+use std::time::{Duration};
+
+/// The largest request body that will be accepted as a command (larger requests receive a `413 Payload Too Large` response)
+const MAX_REQUEST_BYTES: usize = 1024 * 1024;
+
+///
+/// Starts a sub-program that runs single commands sent as HTTP requests
+///
+/// Each `POST` request to the socket should have a body containing a single command in the same format that is accepted by
+/// `parse_command_stream()`. Rather than using the streaming protocol with prompts, the response body is the JSON generated
+/// by the command: a command that returns several values produces a JSON array, and a command that returns no values
+/// produces `null`. Messages and background streams generated by the command are not included in the response.
+///
+/// Errors are returned as a JSON object of the form `{ "error": "<message>" }`. The status code is `400 Bad Request` if the
+/// command could not be parsed, or `500 Internal Server Error` if the command itself reported an error. Requests using any
+/// method other than `POST` receive a `405 Method Not Allowed` response, and requests with a body larger than 1MiB receive a
+/// `413 Payload Too Large` response.
+///
+/// Commands are sent to `command_target` (which is usually `()` to use the default command dispatcher). Each connection is run
+/// as its own subprogram, so this requires that the scene has a `SceneControl` program (ie, it was created with `Scene::default()`).
+/// Sending `SocketListenerMessage::Shutdown` to the program will stop it from accepting new connections, and
+/// `SocketListenerMessage::SetMaxConnections` will limit the number of connections that are served at once.
+///
+/// Each connection serves a single request and is then closed, so a client can't hold on to a connection slot by keeping the
+/// connection alive. `SocketListenerMessage::SetIdleTimeout` sets how long a client has to send the headers for its request before
+/// it is disconnected (hyper's default of 30 seconds is used if no idle timeout is set).
+///
+pub fn start_http_command_socket(
+        scene:          &Scene,
+        program_id:     SubProgramId,
+        address:        impl 'static + Send + ToSocketAddrs,
+        command_target: impl Into<StreamTarget>,
+    ) -> Result<(), ConnectionError> {
+    let command_target = command_target.into();
+
+    scene.add_subprogram(program_id, move |input: InputStream<SocketListenerMessage>, context| async move {
+        // The listener requires an await to start, so we create it as part of the program
+        let listener = TcpListener::bind(address).await
+            .map_err(|tokio_err| ConnectionError::IoError(format!("{}", tokio_err)))
+            .unwrap();
+
+        // Read connections from the listener until it stops accepting them
+        let connection_limit    = ConnectionLimit::new();
+        let connections         = stream::unfold((listener, connection_limit.clone()), |(listener, connection_limit)| async move {
+            // Wait until there's room for another connection
+            let permit = connection_limit.acquire().await;
+
+            match listener.accept().await {
+                Ok((socket, _addr)) => Some(((socket, permit), (listener, connection_limit))),
+                Err(_)              => None,
+            }
+        });
+
+        // We also read shutdown and connection limit requests from the input stream
+        enum ListenerEvent {
+            Connection((TcpStream, ConnectionPermit)),
+            Message(SocketListenerMessage),
+        }
+
+        let connections = connections.map(ListenerEvent::Connection);
+        let input       = input.map(ListenerEvent::Message);
+        let input       = stream::select(connections, input);
+
+        pin_mut!(input);
+        let mut idle_timeout = None;
+
+        while let Some(next_event) = input.next().await {
+            match next_event {
+                ListenerEvent::Message(SocketListenerMessage::Shutdown) => {
+                    break;
+                }
+
+                ListenerEvent::Message(SocketListenerMessage::SetMaxConnections(max_connections)) => {
+                    connection_limit.set_max_connections(max_connections);
+                }
+
+                ListenerEvent::Message(SocketListenerMessage::SetIdleTimeout(timeout)) => {
+                    idle_timeout = timeout;
+                }
+
+                ListenerEvent::Connection((socket, permit)) => {
+                    socket.set_nodelay(true).ok();
+
+                    // Each connection is served by its own subprogram, so slow requests don't block the listener
+                    let command_target      = command_target.clone();
+                    let connection_program  = SubProgramId::new();
+                    let connection_program  = SceneControl::start_program(connection_program, move |_: InputStream<()>, context| async move {
+                        serve_http_connection(socket, context, command_target, idle_timeout).await;
+
+                        // The connection's slot is freed once it has been served
+                        drop(permit);
+                    }, 0);
+
+                    context.send_message(connection_program).await.ok();
+                }
+            }
+        }
+    }, 0);
+
+    // Success
+    Ok(())
+}
+
+///
+/// Serves the HTTP request received on a connection (the connection is closed after the first request)
+///
+async fn serve_http_connection(socket: TcpStream, context: SceneContext, command_target: StreamTarget, idle_timeout: Option<Duration>) {
+    let service = service_fn(move |request| {
+        let context         = context.clone();
+        let command_target  = command_target.clone();
+
+        async move { Ok::<_, Infallible>(http_command_response(request, &context, command_target).await) }
+    });
+
+    let mut builder = http1::Builder::new();
+    builder
+        .keep_alive(false)
+        .timer(TokioTimer::new());
+
+    if let Some(idle_timeout) = idle_timeout {
+        builder.header_read_timeout(idle_timeout);
+    }
+
+    builder
+        .serve_connection(TokioIo::new(socket), service)
+        .await
+        .ok();
+}
+
+///
+/// Creates a HTTP response with a JSON body
+///
+fn json_response(status: StatusCode, json: serde_json::Value) -> Response<Full<Bytes>> {
+    let mut response = Response::new(Full::new(Bytes::from(json.to_string())));
+
+    *response.status_mut() = status;
+    response.headers_mut().insert(CONTENT_TYPE, "application/json".parse().unwrap());
+
+    response
+}
+
+///
+/// Creates a HTTP response indicating an error
+///
+fn error_response(status: StatusCode, error: impl Into<String>) -> Response<Full<Bytes>> {
+    json_response(status, serde_json::json!({ "error": error.into() }))
+}
+
+///
+/// Runs the command in the body of a HTTP request and generates the response
+///
+async fn http_command_response(request: Request<Incoming>, context: &SceneContext, command_target: StreamTarget) -> Response<Full<Bytes>> {
+    // Only POST requests can contain commands
+    if request.method() != Method::POST {
+        return error_response(StatusCode::METHOD_NOT_ALLOWED, "Commands must be sent as POST requests");
+    }
+
+    // Read the command from the body
+    let body = match Limited::new(request.into_body(), MAX_REQUEST_BYTES).collect().await {
+        Ok(body)                                    => body.to_bytes(),
+        Err(err) if err.is::<LengthLimitError>()    => { return error_response(StatusCode::PAYLOAD_TOO_LARGE, "Command is too large"); }
+        Err(err)                                    => { return error_response(StatusCode::BAD_REQUEST, format!("Could not read request: {}", err)); }
+    };
+
+    let command = match String::from_utf8(body.to_vec()) {
+        Ok(command) => command,
+        Err(_)      => { return error_response(StatusCode::BAD_REQUEST, "Command is not valid UTF-8"); }
+    };
+
+    let command = match CommandRequest::parse(&command).await {
+        Ok(command) => command,
+        Err(())     => { return error_response(StatusCode::BAD_REQUEST, "Could not parse command"); }
+    };
+
+    // Run the command using a command processor, which supports the same syntax as a command socket
+    let responses = context.spawn_command(CommandProcessor::new(command_target), stream::iter(iter::once(Ok(command))));
+    let responses = match responses {
+        Ok(responses)   => responses,
+        Err(err)        => { return error_response(StatusCode::INTERNAL_SERVER_ERROR, format!("Could not run command: {:?}", err)); }
+    };
+
+    // Gather the JSON values returned by the command, stopping at the first error
+    let mut values      = vec![];
+    let mut responses   = Box::pin(responses);
+
+    while let Some(response) = responses.next().await {
+        // The request ID isn't needed as there's only one request per HTTP request
+        let (_, response) = response.split_request_id();
+
+        match response {
+            CommandResponse::Json(value)                => values.push(value),
+            CommandResponse::Error(err)                 => { return error_response(StatusCode::INTERNAL_SERVER_ERROR, err); }
+            CommandResponse::Message(_)                 |
+            CommandResponse::BackgroundStream(_)        |
+            CommandResponse::CloseBackgroundStream(_)   |
+            CommandResponse::WithRequestId(_, _)        => { }
+        }
+    }
+
+    // A single value is returned directly, otherwise the values are returned as an array
+    let json = match values.len() {
+        0 => serde_json::Value::Null,
+        1 => values.pop().unwrap(),
+        _ => serde_json::Value::Array(values),
+    };
+
+    json_response(StatusCode::OK, json)
+}
@@ -22,6 +22,11 @@ pub enum InternalSocketMessage {
     /// Creates an internal socket connection
     ///
     CreateInternalSocket(Box<dyn Send + AsyncRead>, Box<dyn Send + AsyncWrite>),
+
+    ///
+    /// Closes the input of all of the internal sockets, then stops the program once they have finished writing their output
+    ///
+    Shutdown,
 }
 
 impl SceneMessage for InternalSocketMessage { }
@@ -208,16 +213,18 @@ where
 
     // The internal socket program responds to InternalSocketMessages and sends subscriptions from the inner program
     scene.add_subprogram(program_id, move |input, context| async move {
-        let mut input = input;
+        let mut input       = input;
+        let connections     = SocketConnectionTracker::new();
 
         while let Some(request) = input.next().await {
             match request {
                 InternalSocketMessage::CreateInternalSocket(async_reader, async_writer) => {
                     // Create the socket connection from the reader
                     let reader_stream = create_reader_stream(Box::into_pin(async_reader));
-                    let reader_stream = create_input_messages(reader_stream.boxed());
+                    let reader_stream = connections.track_input(create_input_messages(reader_stream.boxed()));
 
                     let create_output_messages  = Arc::clone(&create_output_messages);
+                    let open_connection         = connections.open_connection();
                     let socket_connection       = SocketConnection::new(&context, reader_stream, move |context, output_stream| {
                         // Create a stream that converts to bytes
                         let mut output_byte_stream = create_output_messages(output_stream);
@@ -245,7 +252,7 @@ where
                                 }
                             }
                         };
-                        let byte_writer = Mutex::new(Some(byte_writer));
+                        let byte_writer = Mutex::new(Some(open_connection.track_output(byte_writer)));
 
                         // Ask the scene to create a subprogram that writes the output (won't work if the main 'scene' program isn't running)
                         context.spawn_command(FnCommand::<(), ()>::new(move |_input, _context| {
@@ -261,6 +268,12 @@ where
                     // Send this connection to anything connected to this socket
                     context.send_message(SocketMessage::<TInputStream::Item, TOutputMessage>::Connection(socket_connection)).await.ok();
                 },
+
+                InternalSocketMessage::Shutdown => {
+                    // Close the existing connections and wait for them to finish
+                    connections.shutdown().await;
+                    return;
+                },
             }
         }
     }, 0);
@@ -18,3 +18,13 @@ pub use tcp_socket::*;
 
 pub use commands::{JsonCommandLauncherExt};
 pub use standard_json_commands::{StandardCommandsLauncherExt, StandardCommandsSceneExt};
+
+#[cfg(feature = "websocket")]
+mod websocket_socket;
+#[cfg(feature = "http")]
+mod http_socket;
+
+#[cfg(feature = "websocket")]
+pub use websocket_socket::*;
+#[cfg(feature = "http")]
+pub use http_socket::*;
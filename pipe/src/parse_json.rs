@@ -18,6 +18,45 @@ pub enum JsonToken {
     Character(char),
 }
 
+///
+/// Limits on the JSON values that will be accepted by the parser
+///
+/// These protect against clients that send values that are big enough to exhaust the available memory, or that are nested
+/// deeply enough to overflow the stack while they are being parsed. Values that exceed the limits are rejected as soon as the
+/// limit is reached, so the rest of the value is never read into memory.
+///
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct JsonLimits {
+    /// The maximum number of bytes that can be read while parsing a single JSON value (this is also the maximum length of a token)
+    pub max_bytes: usize,
+
+    /// The maximum number of arrays and objects that can be nested inside each other (a value with no arrays or objects has a depth of 0)
+    pub max_depth: usize,
+}
+
+impl Default for JsonLimits {
+    fn default() -> Self {
+        JsonLimits {
+            max_bytes: 16 * 1024 * 1024,
+            max_depth: 128,
+        }
+    }
+}
+
+impl JsonLimits {
+    ///
+    /// Limits that will accept JSON values of any size or depth
+    ///
+    /// These should only be used for trusted input, as a deeply nested value can still overflow the stack
+    ///
+    pub fn unlimited() -> Self {
+        JsonLimits {
+            max_bytes: usize::MAX,
+            max_depth: usize::MAX,
+        }
+    }
+}
+
 /// Matches a string against the JSON whitespace syntax
 pub (crate) fn match_whitespace(lookahead: &str, eof: bool) -> TokenMatchResult<JsonToken> {
     // "^(([ \t]*[\r\n])|([ \t]+))"
@@ -311,6 +350,24 @@ where
     }
 }
 
+impl<TToken, TStream> Tokenizer<TToken, TStream> {
+    ///
+    /// Sets the limits on the JSON values that can be parsed from this tokenizer
+    ///
+    pub fn with_json_limits(&mut self, limits: JsonLimits) -> &mut Self {
+        self.json_limits = limits;
+        self
+    }
+
+    ///
+    /// Retrieves the limits on the JSON values that can be parsed from this tokenizer
+    ///
+    #[inline]
+    pub fn json_limits(&self) -> JsonLimits {
+        self.json_limits
+    }
+}
+
 ///
 /// Reads a JSON token from the tokenizer
 ///
@@ -332,11 +389,39 @@ where
     }
 }
 
+///
+/// Fails if the tokenizer has moved past the maximum size of a JSON value since the `start` position
+///
+#[inline]
+pub (crate) fn json_check_size<TToken, TStream>(tokenizer: &Tokenizer<TToken, TStream>, start: usize) -> Result<(), ()> {
+    if tokenizer.position().saturating_sub(start) > tokenizer.json_limits().max_bytes {
+        Err(())
+    } else {
+        Ok(())
+    }
+}
+
 ///
 /// Attempts to parse a JSON value starting at the current location in the tokenizer, leaving the result on top of the stack in the parser
 /// (or returning an error state if the value is not recognised)
 ///
+/// The value is rejected if it's larger or more deeply nested than the limits set for the tokenizer by `with_json_limits()`. The
+/// size is measured from the position of the tokenizer when this is called.
+///
 pub fn json_parse_value<'a, TStream, TToken>(parser: &'a mut Parser<TokenMatch<TToken>, serde_json::Value>, tokenizer: &'a mut Tokenizer<TToken, TStream>) -> BoxFuture<'a, Result<(), ()>>
+where
+    TStream:        Send + Stream<Item=Vec<u8>>,
+    TToken:         Clone + Send + TryInto<JsonToken>,
+    TToken::Error:  Send
+{
+    let start = tokenizer.position();
+    json_parse_value_within_limits(parser, tokenizer, start, 0)
+}
+
+///
+/// Parses a JSON value that's nested inside `depth` arrays or objects, where the enclosing value started at the tokenizer position `start`
+///
+fn json_parse_value_within_limits<'a, TStream, TToken>(parser: &'a mut Parser<TokenMatch<TToken>, serde_json::Value>, tokenizer: &'a mut Tokenizer<TToken, TStream>, start: usize, depth: usize) -> BoxFuture<'a, Result<(), ()>>
 where
     TStream:        Send + Stream<Item=Vec<u8>>,
     TToken:         Clone + Send + TryInto<JsonToken>,
@@ -350,8 +435,8 @@ where
             match lookahead.token.clone().map(|token| token.try_into()) {
                 Some(Ok(JsonToken::String))         => json_parse_string(parser, tokenizer).await,
                 Some(Ok(JsonToken::Number))         => json_parse_number(parser, tokenizer).await,
-                Some(Ok(JsonToken::Character('{'))) => json_parse_object(parser, tokenizer).await,
-                Some(Ok(JsonToken::Character('['))) => json_parse_array(parser, tokenizer).await,
+                Some(Ok(JsonToken::Character('{'))) => json_parse_object_within_limits(parser, tokenizer, start, depth).await,
+                Some(Ok(JsonToken::Character('['))) => json_parse_array_within_limits(parser, tokenizer, start, depth).await,
                 Some(Ok(JsonToken::True))           => { parser.accept_token().map_err(|_| ())?.reduce(1, |_| serde_json::Value::Bool(true)).map_err(|_| ())?; Ok(()) },
                 Some(Ok(JsonToken::False))          => { parser.accept_token().map_err(|_| ())?.reduce(1, |_| serde_json::Value::Bool(false)).map_err(|_| ())?; Ok(()) },
                 Some(Ok(JsonToken::Null))           => { parser.accept_token().map_err(|_| ())?.reduce(1, |_| serde_json::Value::Null).map_err(|_| ())?; Ok(()) },
@@ -374,6 +459,22 @@ where
     TToken:         Clone + Send + TryInto<JsonToken>,
     TToken::Error:  Send
 {
+    let start = tokenizer.position();
+    json_parse_object_within_limits(parser, tokenizer, start, 0).await
+}
+
+///
+/// Parses a JSON object that's nested inside `depth` arrays or objects, where the enclosing value started at the tokenizer position `start`
+///
+async fn json_parse_object_within_limits<TStream, TToken>(parser: &mut Parser<TokenMatch<TToken>, serde_json::Value>, tokenizer: &mut Tokenizer<TToken, TStream>, start: usize, depth: usize) -> Result<(), ()>
+where
+    TStream:        Send + Stream<Item=Vec<u8>>,
+    TToken:         Clone + Send + TryInto<JsonToken>,
+    TToken::Error:  Send
+{
+    // Values can only be nested up to the maximum depth
+    if depth >= tokenizer.json_limits().max_depth { return Err(()); }
+
     let lookahead = parser.lookahead(0, tokenizer, |tokenizer| json_read_token(tokenizer).boxed()).await;
     let lookahead = if let Some(lookahead) = lookahead { lookahead } else { return Err(()) };
 
@@ -390,6 +491,7 @@ where
             // Look to the next value to decide what to do
             let lookahead = parser.lookahead(0, tokenizer, |tokenizer| json_read_token(tokenizer).boxed()).await;
             let lookahead = if let Some(lookahead) = lookahead { lookahead } else { return Err(()) };
+            json_check_size(tokenizer, start)?;
 
             match lookahead.token.clone().map(|token| token.try_into()) {
                 Some(Ok(JsonToken::Character('}'))) => {
@@ -415,12 +517,13 @@ where
                     }).map_err(|_| ())?;
                     num_tokens += 1;
 
-                    json_parse_value(parser, tokenizer).await?;
+                    json_parse_value_within_limits(parser, tokenizer, start, depth + 1).await?;
                     num_tokens += 1;
 
                     // ',' or '}'
                     let lookahead = parser.lookahead(0, tokenizer, |tokenizer| json_read_token(tokenizer).boxed()).await;
                     let lookahead = if let Some(lookahead) = lookahead { lookahead } else { return Err(()) };
+                    json_check_size(tokenizer, start)?;
 
                     match lookahead.token.clone().map(|token| token.try_into()) {
                         Some(Ok(JsonToken::Character('}'))) => {
@@ -485,6 +588,22 @@ where
     TToken:         Clone + Send + TryInto<JsonToken>,
     TToken::Error:  Send
 {
+    let start = tokenizer.position();
+    json_parse_array_within_limits(parser, tokenizer, start, 0).await
+}
+
+///
+/// Parses a JSON array that's nested inside `depth` arrays or objects, where the enclosing value started at the tokenizer position `start`
+///
+async fn json_parse_array_within_limits<TStream, TToken>(parser: &mut Parser<TokenMatch<TToken>, serde_json::Value>, tokenizer: &mut Tokenizer<TToken, TStream>, start: usize, depth: usize) -> Result<(), ()>
+where
+    TStream:        Send + Stream<Item=Vec<u8>>,
+    TToken:         Clone + Send + TryInto<JsonToken>,
+    TToken::Error:  Send
+{
+    // Values can only be nested up to the maximum depth
+    if depth >= tokenizer.json_limits().max_depth { return Err(()); }
+
     let lookahead = parser.lookahead(0, tokenizer, |tokenizer| json_read_token(tokenizer).boxed()).await;
     let lookahead = if let Some(lookahead) = lookahead { lookahead } else { return Err(()) };
 
@@ -499,6 +618,7 @@ where
             // Look ahead to the next value
             let lookahead = parser.lookahead(0, tokenizer, |tokenizer| json_read_token(tokenizer).boxed()).await;
             let lookahead = if let Some(lookahead) = lookahead { lookahead } else { return Err(()) };
+            json_check_size(tokenizer, start)?;
 
             // ']' to finish the array, or else a JSON value
             match lookahead.token.clone().map(|token| token.try_into()) {
@@ -513,12 +633,13 @@ where
 
                 _ => {
                     // Read the next value
-                    json_parse_value(parser, tokenizer).await?;
+                    json_parse_value_within_limits(parser, tokenizer, start, depth + 1).await?;
                     num_tokens += 1;
 
                     // Next token should be a ',' for more array or ']' for the end of the array
                     let lookahead = parser.lookahead(0, tokenizer, |tokenizer| json_read_token(tokenizer).boxed()).await;
                     let lookahead = if let Some(lookahead) = lookahead { lookahead } else { return Err(()) };
+                    json_check_size(tokenizer, start)?;
 
                     match lookahead.token.clone().map(|token| token.try_into()) {
                         Some(Ok(JsonToken::Character(','))) => {
@@ -1146,4 +1267,80 @@ mod test {
             assert!(result == json!([ 1, 2, 3, 4 ]));
         })
     }
+
+    #[test]
+    pub fn parse_value_within_limits() {
+        use serde_json::json;
+
+        let test_value      = r#"{ "a": [ [ 1 ], 2 ] }"#;
+        let mut tokenizer   = Tokenizer::<JsonToken, _>::new(stream::iter(test_value.bytes()).ready_chunks(2));
+        let mut parser      = Parser::new();
+        tokenizer.with_json_matchers();
+        tokenizer.with_json_limits(JsonLimits { max_bytes: test_value.len(), max_depth: 3 });
+
+        executor::block_on(async move {
+            json_parse_value(&mut parser, &mut tokenizer).await.unwrap();
+
+            let result = parser.finish().unwrap();
+
+            assert!(result == json!({ "a": [ [ 1 ], 2 ] }));
+        })
+    }
+
+    #[test]
+    pub fn reject_over_deep_value() {
+        let test_value      = r#"{ "a": [ [ [ 1 ] ], 2 ] }"#;
+        let mut tokenizer   = Tokenizer::<JsonToken, _>::new(stream::iter(test_value.bytes()).ready_chunks(2));
+        let mut parser      = Parser::new();
+        tokenizer.with_json_matchers();
+        tokenizer.with_json_limits(JsonLimits { max_depth: 3, ..JsonLimits::default() });
+
+        executor::block_on(async move {
+            assert!(json_parse_value(&mut parser, &mut tokenizer).await.is_err());
+        })
+    }
+
+    #[test]
+    pub fn reject_endlessly_nested_value() {
+        // An array that never ends: this should be rejected once it's nested too deeply rather than overflowing the stack
+        let mut tokenizer   = Tokenizer::<JsonToken, _>::new(stream::repeat(b"[ ".to_vec()));
+        let mut parser      = Parser::new();
+        tokenizer.with_json_matchers();
+
+        executor::block_on(async move {
+            assert!(json_parse_value(&mut parser, &mut tokenizer).await.is_err());
+            assert!(tokenizer.position() <= 2 * JsonLimits::default().max_depth + 16, "{}", tokenizer.position());
+        })
+    }
+
+    #[test]
+    pub fn reject_endless_array() {
+        // An array that never ends: this should be rejected once it's read the maximum number of bytes rather than running out of memory
+        let limits          = JsonLimits { max_bytes: 4096, ..JsonLimits::default() };
+        let input           = stream::iter(vec![b"[ ".to_vec()]).chain(stream::repeat(b"1, ".to_vec()));
+        let mut tokenizer   = Tokenizer::<JsonToken, _>::new(input);
+        let mut parser      = Parser::new();
+        tokenizer.with_json_matchers();
+        tokenizer.with_json_limits(limits);
+
+        executor::block_on(async move {
+            assert!(json_parse_value(&mut parser, &mut tokenizer).await.is_err());
+            assert!(tokenizer.position() <= limits.max_bytes + 64, "{}", tokenizer.position());
+        })
+    }
+
+    #[test]
+    pub fn reject_endless_string() {
+        // A string that never ends is a single token, so this checks that the tokenizer stops reading it once it's too long
+        let limits          = JsonLimits { max_bytes: 4096, ..JsonLimits::default() };
+        let input           = stream::iter(vec![b"\"".to_vec()]).chain(stream::repeat(b"abcdefgh".to_vec()));
+        let mut tokenizer   = Tokenizer::<JsonToken, _>::new(input);
+        let mut parser      = Parser::new();
+        tokenizer.with_json_matchers();
+        tokenizer.with_json_limits(limits);
+
+        executor::block_on(async move {
+            assert!(json_parse_value(&mut parser, &mut tokenizer).await.is_err());
+        })
+    }
 }
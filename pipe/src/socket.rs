@@ -2,17 +2,38 @@ use flo_scene::*;
 use flo_scene::programs::*;
 
 use futures::prelude::{Stream, Future};
+use futures::future;
+use futures::future::{BoxFuture, Either, FutureExt, Shared};
 use futures::stream;
 use futures::stream::{BoxStream, StreamExt};
+use futures::channel::{mpsc, oneshot};
 use futures::{pin_mut};
+use futures_timer::{Delay};
 
 use tokio::io::*;
 
+use std::fmt;
+use std::net::{SocketAddr};
+use std::path::{PathBuf};
 use std::result::{Result};
 use std::sync::*;
+use std::task::{Poll, Waker};
+use std::time::{Duration, SystemTime};
 
 // TODO: maybe just send the connections as an output instead of using subscriptions (doesn't really make sense to have multiple things connecting sockets)
 
+///
+/// The address of the peer at the other end of a socket connection
+///
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum PeerAddress {
+    /// A connection from an IP address (eg, via a TCP socket)
+    Ip(SocketAddr),
+
+    /// A connection via a UNIX domain socket, with the path that the peer is bound to (clients are usually not bound to a path, so this is often `None`)
+    Unix(Option<PathBuf>),
+}
+
 ///
 /// Represents an incoming socket connection. When a socket is connected, we retrieve an input stream, and need to respond with an output stream.
 ///
@@ -27,6 +48,12 @@ pub struct SocketConnection<TInputMessage, TOutputMessage> {
 
     /// Sends the output of a stream as the response to a socket (set to None once the socket is created)
     create_output_stream: Option<Box<dyn Send + FnOnce(&SceneContext, BoxStream<'static, TOutputMessage>) -> ()>>,
+
+    /// The address of the peer that made this connection, if known
+    peer_address: Option<PeerAddress>,
+
+    /// The time when this connection was made
+    connected_at: SystemTime,
 }
 
 ///
@@ -45,7 +72,85 @@ pub enum SocketMessage<TInputMessage, TOutputMessage> {
     Connection(SocketConnection<TInputMessage, TOutputMessage>)
 }
 
+///
+/// Requests that can be made to a socket listener program (such as the ones created by `start_unix_socket_program()` or
+/// `start_unencrpted_tcp_socket()`)
+///
+pub enum SocketListenerMessage {
+    ///
+    /// Stops accepting new connections and closes the input of any existing connections. The program will stop once all of the
+    /// connections have finished writing their responses.
+    ///
+    Shutdown,
+
+    ///
+    /// Sets the maximum number of connections that can be open at once (or `None` to allow any number of connections)
+    ///
+    /// When the limit is reached, the program stops accepting new connections until one of the existing connections has finished
+    /// writing its output. Connections that arrive in the meantime are queued by the operating system rather than rejected.
+    ///
+    SetMaxConnections(Option<usize>),
+
+    ///
+    /// Sets how long a connection can go without receiving any data before its input is closed (or `None` to leave idle
+    /// connections open indefinitely)
+    ///
+    /// The timeout is reset whenever data arrives, so a command that's sent slowly is not interrupted as long as the client keeps
+    /// sending data. Closing the input lets the connection finish writing any responses before it's closed. This only affects
+    /// connections that are accepted after the timeout is set.
+    ///
+    SetIdleTimeout(Option<Duration>),
+}
+
+///
+/// Tracks the connections made by a socket program so that they can be shut down in an orderly fashion
+///
+/// The input of every connection is closed when a shutdown is requested, which lets the output finish writing any responses
+/// that are still pending before the connection is closed.
+///
+pub (crate) struct SocketConnectionTracker {
+    /// Sends a message to indicate that the connections should be shut down
+    send_shutdown: Option<oneshot::Sender<()>>,
+
+    /// Future that completes when a shutdown has been requested
+    shutdown: Shared<BoxFuture<'static, ()>>,
+
+    /// Each connection's output holds a copy of this sender until it has finished writing
+    connections_open: Option<mpsc::Sender<()>>,
+
+    /// Closes once all of the connections have finished writing
+    connections_closed: mpsc::Receiver<()>,
+}
+
+///
+/// Limits the number of connections that a socket listener will have open at once
+///
+#[derive(Clone)]
+pub (crate) struct ConnectionLimit(Arc<Mutex<ConnectionLimitState>>);
+
+struct ConnectionLimitState {
+    /// The maximum number of connections that can be open at once
+    max_connections: Option<usize>,
+
+    /// The number of connections that are currently open
+    open_connections: usize,
+
+    /// Woken when a connection slot might have become available
+    waiting: Option<Waker>,
+}
+
+///
+/// Holds a slot in a `ConnectionLimit`, which is freed when this is dropped
+///
+pub (crate) struct ConnectionPermit(ConnectionLimit);
+
+///
+/// Represents a connection tracked by a `SocketConnectionTracker` that has not finished writing its output yet
+///
+pub (crate) struct OpenSocketConnection(Option<mpsc::Sender<()>>);
+
 impl<TInputMessage, TOutputMessage> SceneMessage for SocketMessage<TInputMessage, TOutputMessage> { }
+impl SceneMessage for SocketListenerMessage { }
 
 impl<TInputMessage, TOutputMessage> SocketConnection<TInputMessage, TOutputMessage> 
 where
@@ -60,9 +165,35 @@ where
             context:                context.clone(),
             input_stream:           Some(input.boxed()),
             create_output_stream:   Some(Box::new(send_output)),
+            peer_address:           None,
+            connected_at:           SystemTime::now(),
         }
     }
 
+    ///
+    /// Sets the address of the peer that made this connection
+    ///
+    pub fn with_peer_address(mut self, peer_address: PeerAddress) -> Self {
+        self.peer_address = Some(peer_address);
+        self
+    }
+
+    ///
+    /// Returns the address of the peer that made this connection, if it's known
+    ///
+    /// This is `None` for connections that are not made via a network or UNIX socket (for example, internal sockets)
+    ///
+    pub fn peer_address(&self) -> Option<&PeerAddress> {
+        self.peer_address.as_ref()
+    }
+
+    ///
+    /// Returns the time when this connection was made
+    ///
+    pub fn connected_at(&self) -> SystemTime {
+        self.connected_at
+    }
+
     ///
     /// Sets the stream that will send the resulting output to the socket, and returns the input stream that can be used to read incoming data
     ///
@@ -79,6 +210,156 @@ where
     }
 }
 
+impl fmt::Display for PeerAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PeerAddress::Ip(address)        => write!(f, "{}", address),
+            PeerAddress::Unix(Some(path))   => write!(f, "{}", path.display()),
+            PeerAddress::Unix(None)         => write!(f, "(unnamed unix socket)"),
+        }
+    }
+}
+
+impl ConnectionLimit {
+    ///
+    /// Creates a new connection limit, which initially allows any number of connections
+    ///
+    pub (crate) fn new() -> Self {
+        ConnectionLimit(Arc::new(Mutex::new(ConnectionLimitState {
+            max_connections:    None,
+            open_connections:   0,
+            waiting:            None,
+        })))
+    }
+
+    ///
+    /// Changes the maximum number of connections (connections that are already open are left open if this reduces the limit)
+    ///
+    pub (crate) fn set_max_connections(&self, max_connections: Option<usize>) {
+        let waiting = {
+            let mut state = self.0.lock().unwrap();
+
+            state.max_connections = max_connections;
+            state.waiting.take()
+        };
+
+        if let Some(waiting) = waiting {
+            waiting.wake();
+        }
+    }
+
+    ///
+    /// Waits until there's room for a new connection, then returns a permit that holds its slot
+    ///
+    pub (crate) async fn acquire(&self) -> ConnectionPermit {
+        future::poll_fn(|context| {
+            let mut state   = self.0.lock().unwrap();
+            let has_room    = match state.max_connections {
+                None                    => true,
+                Some(max_connections)   => state.open_connections < max_connections,
+            };
+
+            if has_room {
+                state.open_connections += 1;
+                Poll::Ready(ConnectionPermit(self.clone()))
+            } else {
+                state.waiting = Some(context.waker().clone());
+                Poll::Pending
+            }
+        }).await
+    }
+}
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        let waiting = {
+            let mut state = (self.0).0.lock().unwrap();
+
+            state.open_connections -= 1;
+            state.waiting.take()
+        };
+
+        if let Some(waiting) = waiting {
+            waiting.wake();
+        }
+    }
+}
+
+impl SocketConnectionTracker {
+    ///
+    /// Creates a new connection tracker
+    ///
+    pub (crate) fn new() -> Self {
+        let (send_shutdown, recv_shutdown)          = oneshot::channel();
+        let (connections_open, connections_closed)  = mpsc::channel(0);
+
+        // The connections are only shut down when explicitly requested (they're left running if the tracker is just dropped)
+        let shutdown = recv_shutdown.then(|result| async move {
+            if result.is_err() {
+                future::pending::<()>().await;
+            }
+        }).boxed().shared();
+
+        SocketConnectionTracker {
+            send_shutdown:      Some(send_shutdown),
+            shutdown,
+            connections_open:   Some(connections_open),
+            connections_closed,
+        }
+    }
+
+    ///
+    /// Returns a version of the input stream for a connection that will be closed when the socket is shut down
+    ///
+    pub (crate) fn track_input<TStream>(&self, input: TStream) -> impl 'static + Send + Stream<Item=TStream::Item>
+    where
+        TStream: 'static + Send + Stream,
+    {
+        input.take_until(self.shutdown.clone())
+    }
+
+    ///
+    /// Registers a new connection: the shutdown will wait for its output to finish writing
+    ///
+    pub (crate) fn open_connection(&self) -> OpenSocketConnection {
+        OpenSocketConnection(self.connections_open.clone())
+    }
+
+    ///
+    /// Closes the input for all of the connections, then waits for them to finish writing their output
+    ///
+    pub (crate) async fn shutdown(mut self) {
+        // Close the input streams
+        if let Some(send_shutdown) = self.send_shutdown.take() {
+            send_shutdown.send(()).ok();
+        }
+
+        // Wait for every connection to finish writing (the receiver closes once all the senders are dropped)
+        self.connections_open.take();
+        while self.connections_closed.next().await.is_some() { }
+    }
+}
+
+impl OpenSocketConnection {
+    ///
+    /// Returns a version of the future that writes the output for this connection that will mark it as closed when it completes
+    ///
+    pub (crate) fn track_output<TFuture>(self, writer: TFuture) -> impl 'static + Send + Future<Output=TFuture::Output>
+    where
+        TFuture: 'static + Send + Future,
+    {
+        let OpenSocketConnection(connection_open) = self;
+
+        async move {
+            let result = writer.await;
+
+            // The connection is finished once the writer has completed
+            drop(connection_open);
+            result
+        }
+    }
+}
+
 ///
 /// Creates a stream that reads blocks of data from an AsyncRead
 ///
@@ -106,10 +387,59 @@ pub (crate) fn create_reader_stream(reader: impl 'static + Send + AsyncRead) ->
     })
 }
 
+///
+/// Closes a stream of bytes if no data arrives on it for the specified amount of time
+///
+pub (crate) fn with_idle_timeout(input: BoxStream<'static, Vec<u8>>, timeout: Duration) -> BoxStream<'static, Vec<u8>> {
+    stream::unfold(input, move |mut input| async move {
+        // The input stream is dropped (closing the connection) once it has timed out
+        match future::select(input.next(), Delay::new(timeout)).await {
+            Either::Left((Some(bytes), _))  => Some((bytes, input)),
+            Either::Left((None, _))         => None,
+            Either::Right(_)                => None,
+        }
+    }).boxed()
+}
+
+///
+/// Function that writes a stream of bytes to a connection, returning a future that completes once the whole stream has been written
+///
+pub (crate) type ConnectionWriter = Box<dyn Send + FnOnce(BoxStream<'static, Vec<u8>>) -> BoxFuture<'static, ()>>;
+
+///
+/// Writes a stream of bytes to an AsyncWrite target
+///
+async fn write_byte_stream(async_writer: impl 'static + Send + AsyncWrite, output_byte_stream: BoxStream<'static, Vec<u8>>) {
+    // Write each block as it arrives from the output byte stream to the socket target
+    let mut async_writer        = Box::pin(async_writer);
+    let mut output_byte_stream  = output_byte_stream;
+
+    while let Some(bytes) = output_byte_stream.next().await {
+        // Loop until we've written all of the bytes
+        let mut write_pos = 0;
+
+        while write_pos < bytes.len() {
+            match async_writer.write(&bytes[write_pos..(bytes.len())]).await {
+                Ok(0)           => break,
+                Err(_)          => break,
+                Ok(num_written) => {
+                    write_pos += num_written;
+                    if write_pos >= bytes.len() {
+                        break;
+                    }
+                }
+            }
+        }
+    }
+}
+
 ///
 /// Runs a socket listener suprogram. This accepts 'Subscribe' messages from subprograms that wish to receive connections (subscription messages are sent in a round-robin fashion),
 /// and calls the 'accept_message' function to receive incoming connections
 ///
+/// This listener runs until `accept_connection` returns an error. Use `socket_listener_subprogram_with_input()` for a listener that can be shut down
+/// and configured with `SocketListenerMessage`s.
+///
 pub async fn socket_listener_subprogram<TFutureStream, TReadStream, TWriteStream, TInputStream, TOutputMessage>(
     context:                SceneContext, 
     accept_connection:      impl 'static + Send + Fn() -> TFutureStream,
@@ -121,65 +451,160 @@ where
     TWriteStream:   'static + Send + AsyncWrite,
     TInputStream:   'static + Send + Stream,
     TOutputMessage: 'static + Send ,
+{
+    socket_listener_subprogram_with_input(stream::empty(), context, accept_connection, create_input_messages, create_output_messages).await;
+}
+
+///
+/// Runs a socket listener subprogram, as for `socket_listener_subprogram()`, which also reads `SocketListenerMessage`s from an input stream
+///
+/// The listener stops accepting connections when it receives `SocketListenerMessage::Shutdown`, and finishes once all of the existing
+/// connections have finished writing their output. `SocketListenerMessage::SetMaxConnections` can be used to limit the number of
+/// connections that are open at once, and `SocketListenerMessage::SetIdleTimeout` to close connections that stop sending data.
+///
+pub async fn socket_listener_subprogram_with_input<TFutureStream, TReadStream, TWriteStream, TInputStream, TOutputMessage>(
+    input:                  impl 'static + Send + Stream<Item=SocketListenerMessage>,
+    context:                SceneContext, 
+    accept_connection:      impl 'static + Send + Fn() -> TFutureStream,
+    create_input_messages:  impl 'static + Send + Sync + Fn(BoxStream<'static, Vec<u8>>) -> TInputStream,
+    create_output_messages: impl 'static + Send + Sync + Fn(BoxStream<'static, TOutputMessage>) -> BoxStream<'static, Vec<u8>>)
+where
+    TFutureStream:  Send + Future<Output=Result<(TReadStream, TWriteStream), ConnectionError>>,
+    TReadStream:    'static + Send + AsyncRead,
+    TWriteStream:   'static + Send + AsyncWrite,
+    TInputStream:   'static + Send + Stream,
+    TOutputMessage: 'static + Send ,
+{
+    // The connections have no peer address
+    let accept_connection = move || accept_connection().map(|connection| connection.map(|(async_reader, async_writer)| (async_reader, async_writer, None)));
+
+    socket_listener_subprogram_with_peer_address(input, context, accept_connection, create_input_messages, create_output_messages).await;
+}
+
+///
+/// Runs a socket listener subprogram, as for `socket_listener_subprogram_with_input()`, where the `accept_connection` function also returns the address of
+/// the peer that made each connection (which is made available via `SocketConnection::peer_address()`)
+///
+pub async fn socket_listener_subprogram_with_peer_address<TFutureStream, TReadStream, TWriteStream, TInputStream, TOutputMessage>(
+    input:                  impl 'static + Send + Stream<Item=SocketListenerMessage>,
+    context:                SceneContext, 
+    accept_connection:      impl 'static + Send + Fn() -> TFutureStream,
+    create_input_messages:  impl 'static + Send + Sync + Fn(BoxStream<'static, Vec<u8>>) -> TInputStream,
+    create_output_messages: impl 'static + Send + Sync + Fn(BoxStream<'static, TOutputMessage>) -> BoxStream<'static, Vec<u8>>)
+where
+    TFutureStream:  Send + Future<Output=Result<(TReadStream, TWriteStream, Option<PeerAddress>), ConnectionError>>,
+    TReadStream:    'static + Send + AsyncRead,
+    TWriteStream:   'static + Send + AsyncWrite,
+    TInputStream:   'static + Send + Stream,
+    TOutputMessage: 'static + Send ,
+{
+    // Convert the reader and writer for each connection into a stream of bytes and a function to write the output bytes
+    let accept_byte_stream = move || {
+        accept_connection().map(|connection| connection.map(|(async_reader, async_writer, peer_address)| {
+            let reader_stream                   = create_reader_stream(async_reader).boxed();
+            let write_bytes: ConnectionWriter   = Box::new(move |output_byte_stream| write_byte_stream(async_writer, output_byte_stream).boxed());
+
+            (reader_stream, write_bytes, peer_address)
+        }))
+    };
+
+    byte_stream_listener_subprogram(input, context, accept_byte_stream, create_input_messages, create_output_messages).await;
+}
+
+///
+/// Runs a socket listener subprogram for connections that are represented as a stream of incoming bytes and a function that writes the
+/// outgoing bytes
+///
+/// This is used to implement `socket_listener_subprogram()`, and can be used directly for connections that don't have an `AsyncRead`/`AsyncWrite`
+/// interface (for example, message-based connections such as websockets)
+///
+pub (crate) async fn byte_stream_listener_subprogram<TFutureConnection, TInputStream, TOutputMessage>(
+    input:                  impl 'static + Send + Stream<Item=SocketListenerMessage>,
+    context:                SceneContext, 
+    accept_connection:      impl 'static + Send + Fn() -> TFutureConnection,
+    create_input_messages:  impl 'static + Send + Sync + Fn(BoxStream<'static, Vec<u8>>) -> TInputStream,
+    create_output_messages: impl 'static + Send + Sync + Fn(BoxStream<'static, TOutputMessage>) -> BoxStream<'static, Vec<u8>>)
+where
+    TFutureConnection:  Send + Future<Output=Result<(BoxStream<'static, Vec<u8>>, ConnectionWriter, Option<PeerAddress>), ConnectionError>>,
+    TInputStream:       'static + Send + Stream,
+    TOutputMessage:     'static + Send ,
 {
     // Wrap functions that get shared in a reference
     let accept_connection       = Arc::new(accept_connection);
     let create_output_messages  = Arc::new(create_output_messages);
 
     // Combine the subscription and the acceptance streams
-    let accept_messages = stream::unfold(0, move |_| {
+    let connection_limit    = ConnectionLimit::new();
+    let accept_messages     = stream::unfold(connection_limit.clone(), move |connection_limit| {
         let accept_connection = Arc::clone(&accept_connection);
 
         async move {
+            // Wait until there's room for another connection
+            let permit = connection_limit.acquire().await;
+
             // Fetch the next connection if there is one
             let next_connection = accept_connection().await;
 
             // Continue until we get an error
             match next_connection {
-                Ok(next_connection) => Some((next_connection, 0)),
+                Ok(next_connection) => Some(((next_connection, permit), connection_limit)),
                 _                   => None,
             }
         }
     });
 
-    pin_mut!(accept_messages);
-    let mut input = accept_messages;
+    // We also read shutdown and connection limit requests from the input stream
+    enum ListenerEvent<TConnection> {
+        Connection(TConnection),
+        Message(SocketListenerMessage),
+    }
+
+    let accept_messages = accept_messages.map(ListenerEvent::Connection);
+    let input           = input.map(ListenerEvent::Message);
+    let input           = stream::select(accept_messages, input);
+
+    pin_mut!(input);
+    let connections         = SocketConnectionTracker::new();
+    let mut idle_timeout    = None;
 
     // Run the socket listener
     while let Some(next_event) = input.next().await {
         match next_event {
-            (async_reader, async_writer) => {
+            ListenerEvent::Message(SocketListenerMessage::Shutdown) => {
+                // Stop accepting new connections and wait for the existing ones to finish
+                connections.shutdown().await;
+                return;
+            }
+
+            ListenerEvent::Message(SocketListenerMessage::SetMaxConnections(max_connections)) => {
+                connection_limit.set_max_connections(max_connections);
+            }
+
+            ListenerEvent::Message(SocketListenerMessage::SetIdleTimeout(timeout)) => {
+                idle_timeout = timeout;
+            }
+
+            ListenerEvent::Connection(((reader_stream, write_bytes, peer_address), permit)) => {
+                // Close the input if the connection stays idle for too long
+                let reader_stream = if let Some(idle_timeout) = idle_timeout {
+                    with_idle_timeout(reader_stream, idle_timeout)
+                } else {
+                    reader_stream
+                };
+
                 // Create the socket connection from the reader
-                let reader_stream = create_reader_stream(async_reader);
-                let reader_stream = create_input_messages(reader_stream.boxed());
+                let reader_stream = connections.track_input(create_input_messages(reader_stream));
 
                 let create_output_messages  = Arc::clone(&create_output_messages);
-                let socket_connection       = SocketConnection::<TInputStream::Item, TOutputMessage>::new(&context, reader_stream, move |context, output_stream| {
-                    // Create a stream that converts to bytes
-                    let mut output_byte_stream = create_output_messages(output_stream);
-
-                    // Future to write the bytes
-                    let async_writer = Box::pin(async_writer);
-                    let byte_writer  = async move {
-                        // Write each block as it arrives from the output byte stream to the socket target
-                        let mut async_writer = async_writer;
-                        while let Some(bytes) = output_byte_stream.next().await {
-                            // Loop until we've written all of the bytes
-                            let mut write_pos = 0;
-
-                            while write_pos < bytes.len() {
-                                match async_writer.write(&bytes[write_pos..(bytes.len())]).await {
-                                    Ok(0)           => break,
-                                    Err(_)          => break,
-                                    Ok(num_written) => {
-                                        write_pos += num_written;
-                                        if write_pos >= bytes.len() {
-                                            break;
-                                        }
-                                    }
-                                }
-                            }
-                        }
+                let open_connection         = connections.open_connection();
+                let mut socket_connection      = SocketConnection::<TInputStream::Item, TOutputMessage>::new(&context, reader_stream, move |context, output_stream| {
+                    // Create a stream that converts to bytes, and a future to write them
+                    let output_byte_stream  = create_output_messages(output_stream);
+                    let byte_writer         = open_connection.track_output(write_bytes(output_byte_stream));
+                    let byte_writer         = async move {
+                        // The connection's slot is freed once it has finished writing
+                        byte_writer.await;
+                        drop(permit);
                     };
 
                     // Ask the scene to create a subprogram that writes the output (won't work if the main 'scene' program isn't running)
@@ -189,6 +614,7 @@ where
                     let mut control = context.send(()).unwrap();
                     control.send_immediate(output_program).ok();
                 });
+                socket_connection.peer_address = peer_address;
 
                 // Send the connection to whoever is connected to this socket listener
                 let socket_connection = SocketMessage::Connection(socket_connection);
@@ -1,14 +1,71 @@
 use crate::commands::*;
 
 use flo_scene::*;
+use flo_scene::commands::*;
 
 use futures::prelude::*;
+use serde::*;
 
+use std::collections::{HashMap};
+
+///
+/// Describes a command in the response to the 'help' command
+///
+#[derive(Clone, PartialEq, Eq, Debug, Serialize, Deserialize)]
+pub struct CommandHelp {
+    /// The name of the command
+    pub name: String,
+
+    /// A description of what the command does, if the launcher for the command has one
+    #[serde(skip_serializing_if = "Option::is_none", default)]
+    pub description: Option<String>,
+}
+
+///
+/// The 'help' command, which lists the commands that can be run in the scene, along with their descriptions
 ///
-/// The 'help' command, which generates some help text
+/// If the parameter is a string, only the commands that start with that string are listed (this is useful for implementing
+/// tab completion in a frontend)
 ///
-pub fn command_help(input: serde_json::Value, context: SceneContext) -> impl Future<Output=CommandResponse> {
+pub fn command_help(input: serde_json::Value, context: SceneContext) -> impl Future<Output=CommandResponseData<Vec<CommandHelp>>> {
     async move {
-        CommandResponse::Error("Not implemented".into())
+        let prefix = match input {
+            serde_json::Value::String(prefix)   => prefix,
+            _                                   => String::new(),
+        };
+
+        // Ask the JSON command dispatcher for the commands that it knows about
+        match context.spawn_query(ReadCommand::default(), JsonCommand::new((), LIST_COMMANDS, serde_json::Value::Null), ()) {
+            Ok(responses) => {
+                let mut commands = responses
+                    .filter_map(|response| future::ready(TryInto::<ListCommandResponse>::try_into(response).ok()))
+                    .map(|ListCommandResponse(name)| CommandHelp { name, description: None })
+                    .filter(|command| future::ready(command.name.starts_with(&prefix)))
+                    .collect::<Vec<_>>().await;
+
+                // Fill in the descriptions for any commands that have them
+                if let Ok(descriptions) = context.spawn_query(ReadCommand::default(), JsonCommand::new((), DESCRIBE_COMMANDS, serde_json::Value::Null), ()) {
+                    let descriptions = descriptions
+                        .filter_map(|response| future::ready(TryInto::<CommandDescription>::try_into(response).ok()))
+                        .map(|description| (description.name, description.description))
+                        .collect::<HashMap<_, _>>().await;
+
+                    for command in commands.iter_mut() {
+                        command.description = descriptions.get(&command.name).cloned();
+                    }
+                }
+
+                // Return the commands in a consistent order
+                commands.sort_by(|a, b| a.name.cmp(&b.name));
+                commands.dedup_by(|a, b| a.name == b.name);
+
+                CommandResponseData::Data(commands)
+            }
+
+            Err(error) => {
+                // Could not contact the dispatcher
+                CommandResponseData::Error(format!("Could not list commands: {:?}", error))
+            }
+        }
     }
 }
@@ -6,6 +6,7 @@ use super::list_subprograms::*;
 use super::query::*;
 use super::send::*;
 use super::subscribe::*;
+use super::unsubscribe::*;
 use crate::commands::*;
 
 use flo_scene::commands::*;
@@ -23,13 +24,14 @@ pub trait StandardCommandsLauncherExt {
 impl StandardCommandsLauncherExt for CommandLauncher<serde_json::Value, CommandResponse> {
     fn with_standard_commands(self) -> Self {
         self
-            .with_command("echo", command_echo)
-            .with_json_command("connect", command_connect)
-            .with_json_command("help", command_help)
-            .with_json_command("list_connections", command_list_connections)
-            .with_json_command("list_subprograms", command_list_subprograms)
-            .with_json_command("query", command_query)
-            .with_json_command("send", command_send)
-            .with_json_command("subscribe", command_subscribe)
+            .with_command_described("echo", "Displays its argument as a message", command_echo)
+            .with_json_command_described("connect", "Connects two subprograms in the scene", command_connect)
+            .with_json_command_described("help", "Lists the commands that can be run, optionally only those that start with a prefix", command_help)
+            .with_json_command_described("list_connections", "Lists the connections that are active between subprograms", command_list_connections)
+            .with_json_command_described("list_subprograms", "Lists the subprograms in the scene", command_list_subprograms)
+            .with_json_command_described("query", "Runs a query and returns the results", command_query)
+            .with_json_command_described("send", "Sends messages to a subprogram", command_send)
+            .with_json_command_described("subscribe", "Opens a background stream of events from a source subprogram", command_subscribe)
+            .with_json_command_described("unsubscribe", "Stops a background stream using the number it was announced with", command_unsubscribe)
     }
 }
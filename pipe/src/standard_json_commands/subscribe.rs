@@ -1,23 +1,73 @@
 use crate::commands::*;
 
 use flo_scene::*;
+use flo_scene::programs::*;
 
 use futures::prelude::*;
+use futures::channel::{mpsc, oneshot};
 use serde::*;
 
 ///
-/// The arguments to the connect command
+/// The arguments to the subscribe command
+///
+/// This is the serialization type name of the events to subscribe to (eg, `subscribe "my_crate::MyEvent"`). The type must have been
+/// installed as a serializable type for the `serde_json::value::Serializer` serializer.
 ///
 #[derive(Clone, Serialize, Deserialize)]
-pub struct SubscribeArguments {
-
-}
+pub struct SubscribeArguments(pub String);
 
 ///
 /// The `subscribe` command, which opens a background stream to events from a source subprogram
 ///
+/// A `Subscribe` message is sent for the event type (so it will go to whichever program the `Subscribe` stream is connected to), and
+/// the events that are received are returned as JSON values in a background stream. The subscription is ended when the background
+/// stream is dropped, which happens when the connection is closed or when the `unsubscribe` command is used.
+///
 pub fn command_subscribe(input: SubscribeArguments, context: SceneContext) -> impl Future<Output=CommandResponse> {
     async move {
-        CommandResponse::Error("Not implemented".into())
+        let SubscribeArguments(stream_type_name) = input;
+
+        // Stream ID must use a serialization name
+        let stream_id = StreamId::with_serialization_type(&stream_type_name);
+        let stream_id = if let Some(stream_id) = stream_id { stream_id } else { return CommandResponse::Error(format!("'{}' is not a known stream type", stream_type_name)); };
+
+        // The events are received by a subprogram, which relays them to the background stream
+        let (send_events, receive_events)               = mpsc::channel(1);
+        let (stop_subscription, subscription_stopped)   = oneshot::channel::<()>();
+        let subscriber_program                          = SubProgramId::new();
+
+        let start_subscriber = SceneControl::start_program(subscriber_program, move |input: InputStream<SerializedMessage<serde_json::Value>>, context| async move {
+            let relay_events = async move {
+                // Send the subscription request (the subscription stops when this program ends)
+                if subscribe_serialized::<serde_json::Value>(&context, &stream_id, subscriber_program).await.is_err() {
+                    return;
+                }
+
+                let mut input       = input;
+                let mut send_events = send_events;
+
+                while let Some(SerializedMessage(event, _)) = input.next().await {
+                    if send_events.send(event).await.is_err() {
+                        break;
+                    }
+                }
+            };
+
+            // Stop as soon as the background stream is dropped, even if we're still waiting for the subscription to be accepted
+            future::select(relay_events.boxed(), subscription_stopped).await;
+        }, 1);
+
+        if let Err(err) = context.send_message(start_subscriber).await {
+            return CommandResponse::Error(format!("Could not start subscription: {:?}", err));
+        }
+
+        // The stream owns the stop_subscription sender, so the subscriber program is stopped when the stream is dropped
+        let events = stream::unfold((receive_events, stop_subscription), |(mut receive_events, stop_subscription)| async move {
+            let event = receive_events.next().await?;
+
+            Some((event, (receive_events, stop_subscription)))
+        });
+
+        CommandResponse::BackgroundStream(events.boxed())
     }
 }
@@ -0,0 +1,14 @@
+use crate::commands::*;
+
+use flo_scene::*;
+
+use futures::prelude::*;
+
+///
+/// The `unsubscribe` command, which stops a background stream using the number it was announced with (eg, `unsubscribe 2`)
+///
+/// This works for any background stream, not just the ones created by `subscribe`. The stream is closed with an `<EOS n>` message.
+///
+pub fn command_unsubscribe(input: usize, _context: SceneContext) -> impl Future<Output=CommandResponse> {
+    future::ready(CommandResponse::CloseBackgroundStream(input))
+}
@@ -7,7 +7,7 @@ use futures::stream::{BoxStream};
 
 use tokio::net::{TcpListener, ToSocketAddrs};
 
-use ::desync::*;
+use std::sync::*;
 
 ///
 /// Starts a sub-program that accepts unencrypted connections on a TCP socket.
@@ -16,6 +16,9 @@ use ::desync::*;
 /// message. Typically, there's only one subscriber but in the event multiple are connected, they are informed of connections in
 /// a round-robin fashion.
 ///
+/// Sending `SocketListenerMessage::Shutdown` to the program will stop it from accepting new connections, and stop it once the
+/// existing connections have finished writing their responses.
+///
 pub fn start_unencrpted_tcp_socket<TInputStream, TOutputMessage>(
         scene:                  &Scene, 
         program_id:             SubProgramId, 
@@ -27,24 +30,29 @@ where
     TInputStream:   'static + Send + Stream,
     TOutputMessage: 'static + Send,
 {
-    scene.add_subprogram(program_id, move |_input: InputStream<()>, context| async move {
+    scene.add_subprogram(program_id, move |input: InputStream<SocketListenerMessage>, context| async move {
         // The listener requires an await to start, so we create it as part of the program
         let listener = TcpListener::bind(address).await
             .map_err(|tokio_err| ConnectionError::IoError(format!("{}", tokio_err)))
             .unwrap();
 
-        // Add a socket runner subprogram. We don't use the address for anything, ie we accept all connections here
-        let listener = Desync::new(listener);
-
-        socket_listener_subprogram(context, move || 
-            listener.future_desync(|listener| async {
-                listener.accept().await
-                    .map(|(socket, _addr)| {
-                        socket.set_nodelay(true).ok();
-                        socket.into_split()
-                    })
-                    .map_err(|tokio_err| tokio_err.into())
-            }).map_ok_or_else(|_cancelled| Err(ConnectionError::Cancelled), |ok| ok),
+        // Add a socket runner subprogram. We accept all connections here, and pass on the address of the peer with each connection
+        // (the listener is shared with each call to accept a connection, so that connections are accepted by this program's task)
+        let listener = Arc::new(listener);
+
+        socket_listener_subprogram_with_peer_address(input, context, move || {
+                let listener = Arc::clone(&listener);
+
+                async move {
+                    listener.accept().await
+                        .map(|(socket, addr)| {
+                            socket.set_nodelay(true).ok();
+                            let (reader, writer) = socket.into_split();
+                            (reader, writer, Some(PeerAddress::Ip(addr)))
+                        })
+                        .map_err(|tokio_err| tokio_err.into())
+                }
+            },
             create_input_messages,
             create_output_messages).await;
         }, 0);
@@ -1,3 +1,5 @@
+use crate::parse_json::{JsonLimits};
+
 use futures::prelude::*;
 
 use std::collections::{VecDeque};
@@ -45,6 +47,9 @@ pub struct TokenMatch<TToken> {
 
     /// The input fragment that was matched against the token
     pub fragment: String,
+
+    /// The byte offset into the source stream where the fragment starts (as returned by `Tokenizer::position()` before it was matched)
+    pub start: usize,
 }
 
 ///
@@ -65,6 +70,12 @@ pub struct Tokenizer<TToken, TStream> {
 
     /// Bytes that do not yet match a fully-formed UTF-8 character
     lookahead_bytes: VecDeque<u8>,
+
+    /// The number of bytes that have been returned as tokens or skipped so far
+    position: usize,
+
+    /// The limits on the JSON values that can be read from this tokenizer (no token can be longer than the maximum size of a JSON value)
+    pub (crate) json_limits: JsonLimits,
 }
 
 impl<TToken> TokenMatchResult<TToken> {
@@ -105,6 +116,7 @@ impl<TToken> TokenMatch<TToken> {
         TokenMatch {
             token:      new_token,
             fragment:   self.fragment,
+            start:      self.start,
         }
     }
 }
@@ -119,6 +131,8 @@ impl<TToken, TStream> Tokenizer<TToken, TStream> {
             matchers:           vec![],
             lookahead_bytes:    VecDeque::new(),
             lookahead_chars:    String::new(),
+            position:           0,
+            json_limits:        JsonLimits::default(),
         }
     }
 
@@ -137,6 +151,17 @@ impl<TToken, TStream> Tokenizer<TToken, TStream> {
         self.matchers.clear();
         self
     }
+
+    ///
+    /// The byte offset into the source stream of the next character that will be matched as part of a token
+    ///
+    /// This counts the bytes of the UTF-8 encoding of the characters that have been matched or skipped, so characters that
+    /// are returned using `return_characters()` are counted again when they are matched for a second time.
+    ///
+    #[inline]
+    pub fn position(&self) -> usize {
+        self.position
+    }
 }
 
 impl<TToken, TStream> Tokenizer<TToken, TStream> 
@@ -190,6 +215,12 @@ where
                 break;
             }
 
+            // Any matcher that's still active here is matching a token that's longer than a JSON value is allowed to be, so stop
+            // before a runaway token can use up all of the available memory
+            if self.lookahead_chars.len() > self.json_limits.max_bytes {
+                break;
+            }
+
             // Try to read more characters if possible
             loop {
                 let last_pos = self.lookahead_chars.len();
@@ -228,11 +259,45 @@ where
                 .unwrap();
 
             // Remove the matched characters
-            let matched_fragment = self.lookahead_chars.chars().take(num_chars).collect();
-            self.lookahead_chars = self.lookahead_chars.chars().skip(num_chars).collect();
+            let start                       = self.position;
+            let matched_fragment: String    = self.lookahead_chars.chars().take(num_chars).collect();
+            self.lookahead_chars            = self.lookahead_chars.chars().skip(num_chars).collect();
+            self.position                   += matched_fragment.len();
 
             // Return a matched token
-            return Some(TokenMatch { token: Some(token), fragment: matched_fragment });
+            return Some(TokenMatch { token: Some(token), fragment: matched_fragment, start });
+        }
+    }
+
+    ///
+    /// Returns true if there are no more characters to be read by this tokenizer
+    ///
+    pub async fn at_end_of_stream(&mut self) -> bool {
+        self.lookahead_chars.is_empty() && !self.read_more_characters().await
+    }
+
+    ///
+    /// Discards characters up to and including the next newline character
+    ///
+    /// This can be used to recover from a parse error by skipping the rest of the current line. Returns false if the end of
+    /// the stream was reached before a newline was found.
+    ///
+    pub async fn skip_to_newline(&mut self) -> bool {
+        loop {
+            if let Some(newline_pos) = self.lookahead_chars.find(['\n', '\r']) {
+                // Keep the characters after the newline
+                self.lookahead_chars    = self.lookahead_chars.split_off(newline_pos + 1);
+                self.position           += newline_pos + 1;
+                return true;
+            }
+
+            // Discard everything in the lookahead and read more characters
+            self.position += self.lookahead_chars.len();
+            self.lookahead_chars.clear();
+
+            if !self.read_more_characters().await {
+                return false;
+            }
         }
     }
 
@@ -241,7 +306,8 @@ where
     ///
     #[inline]
     pub fn return_characters(&mut self, new_lookahead: String) {
-        self.lookahead_chars = new_lookahead + &self.lookahead_chars;
+        self.position           = self.position.saturating_sub(new_lookahead.len());
+        self.lookahead_chars    = new_lookahead + &self.lookahead_chars;
     }
 
     ///
@@ -356,6 +422,8 @@ mod test {
             matchers:           vec![],
             lookahead_chars:    String::new(),
             lookahead_bytes:    vec![0x24].into_iter().collect(),
+            position:           0,
+            json_limits:        JsonLimits::default(),
         };
 
         assert!(state.read_lookahead_character());
@@ -369,6 +437,8 @@ mod test {
             matchers:           vec![],
             lookahead_chars:    String::new(),
             lookahead_bytes:    vec![0xc2, 0xa3].into_iter().collect(),
+            position:           0,
+            json_limits:        JsonLimits::default(),
         };
 
         assert!(state.read_lookahead_character());
@@ -383,6 +453,8 @@ mod test {
             matchers:           vec![],
             lookahead_chars:    String::new(),
             lookahead_bytes:    vec![0xe0, 0xa4, 0xb9].into_iter().collect(),
+            position:           0,
+            json_limits:        JsonLimits::default(),
         };
 
         assert!(state.read_lookahead_character());
@@ -397,10 +469,37 @@ mod test {
             matchers:           vec![],
             lookahead_chars:    String::new(),
             lookahead_bytes:    vec![0xf0, 0x90, 0x8d, 0x88].into_iter().collect(),
+            position:           0,
+            json_limits:        JsonLimits::default(),
         };
 
         assert!(state.read_lookahead_character());
         assert!(state.lookahead_chars == "𐍈", "{:?} {:x}", state.lookahead_chars, state.lookahead_chars.chars().next().unwrap() as u32);
         assert!(state.lookahead_bytes.is_empty());
     }
+    #[test]
+    fn token_start_offsets() {
+        use crate::parse_json::*;
+        use futures::executor;
+
+        let mut tokenizer = Tokenizer::<JsonToken, _>::new(stream::iter("\"£\" 12\n  true".bytes()).ready_chunks(2));
+        tokenizer.with_json_matchers();
+
+        executor::block_on(async {
+            let mut starts = vec![];
+            while let Some(token) = tokenizer.match_token().await {
+                starts.push((token.fragment, token.start));
+            }
+
+            // Offsets are in bytes, so the '£' counts as 2
+            assert!(starts == vec![
+                ("\"£\"".to_string(), 0),
+                (" ".to_string(), 4),
+                ("12".to_string(), 5),
+                ("\n".to_string(), 7),
+                ("  ".to_string(), 8),
+                ("true".to_string(), 10),
+            ], "{:?}", starts);
+        });
+    }
 }
\ No newline at end of file
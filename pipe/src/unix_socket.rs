@@ -20,6 +20,9 @@ use std::sync::*;
 /// message. Typically, there's only one subscriber but in the event multiple are connected, they are informed of connections in
 /// a round-robin fashion.
 ///
+/// Sending `SocketListenerMessage::Shutdown` to the program will stop it from accepting new connections, and stop it once the
+/// existing connections have finished writing their responses.
+///
 pub fn start_unix_socket_program<TInputStream, TOutputMessage>(
         scene:                  &Scene, 
         program_id:             SubProgramId, 
@@ -38,14 +41,17 @@ where
             .map_err(|tokio_err| ConnectionError::IoError(format!("{}", tokio_err)))?;
         let listener = Arc::new(Mutex::new(Some(listener)));
 
-        // Add a socket runner subprogram. We don't use the address for anything, ie we accept all connections here
-        scene.add_subprogram(program_id, move |_input: InputStream<()>, context| socket_listener_subprogram(context, move || {
+        // Add a socket runner subprogram. We accept all connections here, and pass on the address of the peer with each connection
+        scene.add_subprogram(program_id, move |input: InputStream<SocketListenerMessage>, context| socket_listener_subprogram_with_peer_address(input, context, move || {
                 let listener        = Arc::clone(&listener);
                 let our_listener    = listener.lock().unwrap().take().unwrap();
 
                 async move {
                     let connection = our_listener.accept().await
-                        .map(|(socket, _addr)| socket.into_split())
+                        .map(|(socket, addr)| {
+                            let (reader, writer) = socket.into_split();
+                            (reader, writer, Some(PeerAddress::Unix(addr.as_pathname().map(|path| path.to_path_buf()))))
+                        })
                         .map_err(|tokio_err| tokio_err.into());
 
                     *listener.lock().unwrap() = Some(our_listener);
@@ -63,7 +69,7 @@ where
     #[cfg(not(unix))]
     {
         // If we're not on Unix, this creates a program that ignores its messages (we can't create any UNIX sockets)
-        scene.add_subprogram(program_id, move |input: InputStream<Subscribe>, _context| async move {
+        scene.add_subprogram(program_id, move |input: InputStream<SocketListenerMessage>, _context| async move {
             let mut input = input;
             while let Some(_) = input.next().await {
             }
@@ -0,0 +1,115 @@
+use super::socket::*;
+
+use flo_scene::*;
+
+use futures::prelude::*;
+use futures::future;
+use futures::channel::{oneshot};
+use futures::stream::{BoxStream};
+
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
+use tokio_tungstenite::{accept_async};
+use tokio_tungstenite::tungstenite::{Message};
+
+use std::sync::*;
+
+///
+/// Starts a sub-program that accepts websocket connections on a TCP socket.
+///
+/// This works in the same way as `start_unencrpted_tcp_socket()`, except that clients must perform a websocket handshake when they
+/// connect. The payloads of the text and binary frames sent by the client are concatenated to form the input byte stream (so a frame
+/// does not need to contain exactly one command), and each block of bytes generated by `create_output_messages` is sent back as a single
+/// frame: a text frame if the bytes are valid UTF-8, or a binary frame if they are not.
+///
+/// Pings are answered automatically, and the connection's input stream is closed when the client sends a close frame. A close frame
+/// is sent to the client once the output stream for the connection has finished.
+///
+pub fn start_websocket_socket_program<TInputStream, TOutputMessage>(
+        scene:                  &Scene, 
+        program_id:             SubProgramId, 
+        address:                impl 'static + Send + ToSocketAddrs, 
+        create_input_messages:  impl 'static + Send + Sync + Fn(BoxStream<'static, Vec<u8>>) -> TInputStream,
+        create_output_messages: impl 'static + Send + Sync + Fn(BoxStream<'static, TOutputMessage>) -> BoxStream<'static, Vec<u8>>
+    ) -> Result<(), ConnectionError> 
+where
+    TInputStream:   'static + Send + Stream,
+    TOutputMessage: 'static + Send,
+{
+    scene.add_subprogram(program_id, move |input: InputStream<SocketListenerMessage>, context| async move {
+        // The listener requires an await to start, so we create it as part of the program
+        let listener = TcpListener::bind(address).await
+            .map_err(|tokio_err| ConnectionError::IoError(format!("{}", tokio_err)))
+            .unwrap();
+
+        // The listener is shared with each call to accept a connection, so that connections are accepted by this program's task
+        let listener = Arc::new(listener);
+
+        byte_stream_listener_subprogram(input, context, move || {
+                let listener = Arc::clone(&listener);
+
+                async move {
+                    let (socket, addr) = listener.accept().await?;
+                    socket.set_nodelay(true).ok();
+
+                    let (input_bytes, write_bytes) = websocket_byte_streams(socket);
+                    Ok((input_bytes, write_bytes, Some(PeerAddress::Ip(addr))))
+                }
+            },
+            create_input_messages,
+            create_output_messages).await;
+        }, 0);
+
+    // Success
+    Ok(())
+}
+
+///
+/// Converts a socket into a stream of the bytes received from the client and a function that sends bytes back to the client
+///
+/// The websocket handshake is performed when the input stream is first read, so a client that never completes the handshake only
+/// holds up its own connection. The input stream is empty and the output is discarded for clients that fail the handshake.
+///
+fn websocket_byte_streams(socket: TcpStream) -> (BoxStream<'static, Vec<u8>>, ConnectionWriter) {
+    let (send_sink, receive_sink) = oneshot::channel();
+
+    // The input stream finishes when the client closes the connection or an error occurs. Tungstenite responds to pings while we're reading.
+    let input_bytes = stream::once(async move {
+            let websocket = if let Ok(websocket) = accept_async(socket).await { websocket } else { return stream::empty().boxed(); };
+            let (websocket_sink, websocket_stream) = websocket.split();
+
+            // The sink is passed on to the writer (which waits for the handshake to finish)
+            send_sink.send(websocket_sink).ok();
+
+            websocket_stream
+                .take_while(|frame| future::ready(matches!(frame, Ok(frame) if !frame.is_close())))
+                .filter_map(|frame| future::ready(match frame {
+                    Ok(Message::Text(text))     => Some(text.as_bytes().to_vec()),
+                    Ok(Message::Binary(bytes))  => Some(bytes.to_vec()),
+                    _                           => None,
+                }))
+                .boxed()
+        })
+        .flatten()
+        .boxed();
+
+    // Each block of output bytes is sent as a frame, and the websocket is closed once the output has finished
+    let write_bytes: ConnectionWriter = Box::new(move |output_byte_stream| async move {
+        let mut websocket_sink      = if let Ok(websocket_sink) = receive_sink.await { websocket_sink } else { return; };
+        let mut output_byte_stream  = output_byte_stream;
+
+        while let Some(bytes) = output_byte_stream.next().await {
+            let frame = match String::from_utf8(bytes) {
+                Ok(text)    => Message::text(text),
+                Err(err)    => Message::binary(err.into_bytes()),
+            };
+
+            if websocket_sink.send(frame).await.is_err() {
+                break;
+            }
+        }
+
+        websocket_sink.close().await.ok();
+    }.boxed());
+
+    (input_bytes, write_bytes)
+}
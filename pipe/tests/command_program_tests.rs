@@ -1,13 +1,19 @@
+mod common;
+
+use common::*;
 use flo_scene::*;
 use flo_scene::commands::*;
 use flo_scene::programs::*;
 use flo_scene_pipe::*;
 use flo_scene_pipe::commands::*;
+use flo_scene_pipe::standard_json_commands::{CommandHelp};
 
 use futures::prelude::*;
 use futures::channel::mpsc;
 use futures::channel::oneshot;
 
+use serde::{Deserialize, Serialize};
+
 #[test]
 pub fn send_error_command() {
     let scene = Scene::default();
@@ -109,3 +115,502 @@ pub fn declare_and_run_json_command() {
         })
         .run_in_scene(&scene, test_subprogram);
 }
+
+#[test]
+pub fn run_command_from_connection() {
+    let scene = Scene::default();
+
+    struct TestSucceeded;
+    impl SceneMessage for TestSucceeded { }
+
+    // Create a command program, and a launcher with a command that parrots strings back to us
+    let test_program        = SubProgramId::new();
+    let command_program     = SubProgramId::new();
+    let launcher_program    = SubProgramId::new();
+    scene.add_subprogram(command_program, |input, context| command_connection_program(input, context, ()), 0);
+
+    let json_launcher = CommandLauncher::json()
+        .with_json_command("::test", |param: String, _context| async move {
+            CommandResponse::Json(serde_json::Value::String(param))
+        });
+    scene.add_subprogram(launcher_program, json_launcher.to_subprogram(), 1);
+
+    scene.add_subprogram(SubProgramId::called("Test"), move |_: InputStream<()>, context| async move {
+        let (send_commands, recv_commands)      = mpsc::channel(1);
+        let (send_responses, recv_responses)    = oneshot::channel();
+
+        // The launcher needs to be running before the dispatcher can find its commands
+        wait_for_program(&context, launcher_program).await;
+
+        // Request a connection
+        let connection = SocketConnection::new(&context, recv_commands, move |_context, output| { send_responses.send(output).ok(); });
+        context.send(command_program).unwrap().send(CommandProgramSocketMessage::Connection(connection)).await.ok().unwrap();
+
+        let mut send_commands   = send_commands;
+        let mut response_stream = recv_responses.await.unwrap();
+
+        // Run the command via the default dispatcher
+        send_commands.send(CommandRequest::parse("::test \"Hello\"").await).await.unwrap();
+
+        let response = response_stream.next().await.unwrap();
+        assert!(matches!(&response, CommandResponse::Json(serde_json::Value::String(val)) if val == "Hello"), "{:?}", response);
+
+        // Run the command by sending it directly to the launcher program
+        let command = CommandRequest::parse("::test \"Direct\"").await.unwrap();
+        let command = CommandRequest::ForTarget { target: launcher_program.into(), request: Box::new(command) };
+        send_commands.send(Ok(command)).await.unwrap();
+
+        let response = response_stream.next().await.unwrap();
+        assert!(matches!(&response, CommandResponse::Json(serde_json::Value::String(val)) if val == "Direct"), "{:?}", response);
+
+        // Commands that didn't parse produce an error
+        send_commands.send(Err(())).await.unwrap();
+
+        let response = response_stream.next().await.unwrap();
+        assert!(matches!(&response, CommandResponse::Error(_)), "{:?}", response);
+
+        context.send_message(TestSucceeded).await.unwrap();
+    }, 0);
+
+    TestBuilder::new()
+        .redirect_input(StreamId::with_message_type::<TestSucceeded>())
+        .expect_message(|_: TestSucceeded| Ok(()))
+        .run_in_scene_with_threads(&scene, test_program, 5);
+}
+
+#[test]
+pub fn responses_are_tagged_with_request_id() {
+    let scene = Scene::default();
+
+    struct TestSucceeded;
+    impl SceneMessage for TestSucceeded { }
+
+    // Create a command program, and a launcher with a command that parrots strings back to us
+    let test_program        = SubProgramId::new();
+    let command_program     = SubProgramId::new();
+    let launcher_program    = SubProgramId::new();
+    scene.add_subprogram(command_program, |input, context| command_connection_program(input, context, ()), 0);
+
+    let json_launcher = CommandLauncher::json()
+        .with_json_command("::test", |param: String, _context| async move {
+            CommandResponse::Json(serde_json::Value::String(param))
+        });
+    scene.add_subprogram(launcher_program, json_launcher.to_subprogram(), 1);
+
+    scene.add_subprogram(SubProgramId::called("Test"), move |_: InputStream<()>, context| async move {
+        let (send_commands, recv_commands)      = mpsc::channel(1);
+        let (send_responses, recv_responses)    = oneshot::channel();
+
+        wait_for_program(&context, launcher_program).await;
+
+        let connection = SocketConnection::new(&context, recv_commands, move |_context, output| { send_responses.send(output).ok(); });
+        context.send(command_program).unwrap().send(CommandProgramSocketMessage::Connection(connection)).await.ok().unwrap();
+
+        let mut send_commands   = send_commands;
+        let mut response_stream = recv_responses.await.unwrap();
+
+        // The response to a request with an ID is tagged with the same ID
+        send_commands.send(CommandRequest::parse("@req-1 ::test \"Hello\"").await).await.unwrap();
+
+        let response = response_stream.next().await.unwrap();
+        assert!(matches!(&response, CommandResponse::WithRequestId(RequestId(id), response) 
+            if id == "req-1" && matches!(&**response, CommandResponse::Json(serde_json::Value::String(val)) if val == "Hello")), "{:?}", response);
+
+        // Requests without an ID are not tagged
+        send_commands.send(CommandRequest::parse("::test \"Untagged\"").await).await.unwrap();
+
+        let response = response_stream.next().await.unwrap();
+        assert!(matches!(&response, CommandResponse::Json(serde_json::Value::String(val)) if val == "Untagged"), "{:?}", response);
+
+        context.send_message(TestSucceeded).await.unwrap();
+    }, 0);
+
+    TestBuilder::new()
+        .redirect_input(StreamId::with_message_type::<TestSucceeded>())
+        .expect_message(|_: TestSucceeded| Ok(()))
+        .run_in_scene_with_threads(&scene, test_program, 5);
+}
+
+#[test]
+pub fn assign_and_reuse_variable() {
+    let scene = Scene::default();
+
+    struct TestSucceeded;
+    impl SceneMessage for TestSucceeded { }
+
+    // Create a command program, and a launcher with some commands to generate and read values
+    let test_program        = SubProgramId::new();
+    let command_program     = SubProgramId::new();
+    let launcher_program    = SubProgramId::new();
+    scene.add_subprogram(command_program, |input, context| command_connection_program(input, context, ()), 0);
+
+    let json_launcher = CommandLauncher::json()
+        .with_json_command("::test", |param: String, _context| async move {
+            CommandResponse::Json(serde_json::Value::String(param))
+        })
+        .with_json_command("::add", |param: Vec<i64>, _context| async move {
+            CommandResponse::Json(serde_json::Value::from(param.into_iter().sum::<i64>()))
+        });
+    scene.add_subprogram(launcher_program, json_launcher.to_subprogram(), 1);
+
+    scene.add_subprogram(SubProgramId::called("Test"), move |_: InputStream<()>, context| async move {
+        let (send_commands, recv_commands)      = mpsc::channel(1);
+        let (send_responses, recv_responses)    = oneshot::channel();
+
+        // The launcher needs to be running before the dispatcher can find its commands
+        wait_for_program(&context, launcher_program).await;
+
+        // Request a connection
+        let connection = SocketConnection::new(&context, recv_commands, move |_context, output| { send_responses.send(output).ok(); });
+        context.send(command_program).unwrap().send(CommandProgramSocketMessage::Connection(connection)).await.ok().unwrap();
+
+        let mut send_commands   = send_commands;
+        let mut response_stream = recv_responses.await.unwrap();
+
+        // Assign some values to variables (these generate no output)
+        send_commands.send(CommandRequest::parse("x = ::test \"Hello\"").await).await.unwrap();
+        send_commands.send(CommandRequest::parse("y = ::add [ 1, 2 ]").await).await.unwrap();
+
+        // Use the variables in later commands
+        send_commands.send(CommandRequest::parse("::test $x").await).await.unwrap();
+
+        let response = response_stream.next().await.unwrap();
+        assert!(matches!(&response, CommandResponse::Json(serde_json::Value::String(val)) if val == "Hello"), "{:?}", response);
+
+        send_commands.send(CommandRequest::parse("::add [ $y, 4 ]").await).await.unwrap();
+
+        let response = response_stream.next().await.unwrap();
+        assert!(matches!(&response, CommandResponse::Json(val) if val == &serde_json::Value::from(7)), "{:?}", response);
+
+        // Strings are never variable references
+        send_commands.send(CommandRequest::parse("::test \"$x\"").await).await.unwrap();
+
+        let response = response_stream.next().await.unwrap();
+        assert!(matches!(&response, CommandResponse::Json(serde_json::Value::String(val)) if val == "$x"), "{:?}", response);
+
+        // Referring to a variable that has not been assigned is an error
+        send_commands.send(CommandRequest::parse("::test $z").await).await.unwrap();
+
+        let response = response_stream.next().await.unwrap();
+        assert!(matches!(&response, CommandResponse::Error(_)), "{:?}", response);
+
+        context.send_message(TestSucceeded).await.unwrap();
+    }, 0);
+
+    TestBuilder::new()
+        .redirect_input(StreamId::with_message_type::<TestSucceeded>())
+        .expect_message(|_: TestSucceeded| Ok(()))
+        .run_in_scene_with_threads(&scene, test_program, 5);
+}
+
+#[test]
+pub fn extract_path_from_variable() {
+    let scene = Scene::default();
+
+    struct TestSucceeded;
+    impl SceneMessage for TestSucceeded { }
+
+    // Create a command program, and a launcher with some commands to generate and read values
+    let test_program        = SubProgramId::new();
+    let command_program     = SubProgramId::new();
+    let launcher_program    = SubProgramId::new();
+    scene.add_subprogram(command_program, |input, context| command_connection_program(input, context, ()), 0);
+
+    let json_launcher = CommandLauncher::json()
+        .with_json_command("::test", |param: String, _context| async move {
+            CommandResponse::Json(serde_json::Value::String(param))
+        })
+        .with_json_command("::add", |param: Vec<i64>, _context| async move {
+            CommandResponse::Json(serde_json::Value::from(param.into_iter().sum::<i64>()))
+        })
+        .with_json_command("::object", |_param: (), _context| async move {
+            CommandResponse::Json(serde_json::json!({ "items": [ { "name": "first" }, { "name": "second" } ], "count": 2 }))
+        });
+    scene.add_subprogram(launcher_program, json_launcher.to_subprogram(), 1);
+
+    scene.add_subprogram(SubProgramId::called("Test"), move |_: InputStream<()>, context| async move {
+        let (send_commands, recv_commands)      = mpsc::channel(1);
+        let (send_responses, recv_responses)    = oneshot::channel();
+
+        // The launcher needs to be running before the dispatcher can find its commands
+        wait_for_program(&context, launcher_program).await;
+
+        // Request a connection
+        let connection = SocketConnection::new(&context, recv_commands, move |_context, output| { send_responses.send(output).ok(); });
+        context.send(command_program).unwrap().send(CommandProgramSocketMessage::Connection(connection)).await.ok().unwrap();
+
+        let mut send_commands   = send_commands;
+        let mut response_stream = recv_responses.await.unwrap();
+
+        // Store an object in a variable
+        send_commands.send(CommandRequest::parse("x = ::object").await).await.unwrap();
+
+        // Nested values can be extracted from the variable as a command argument...
+        send_commands.send(CommandRequest::parse("::test $x.items[1].name").await).await.unwrap();
+
+        let response = response_stream.next().await.unwrap();
+        assert!(matches!(&response, CommandResponse::Json(serde_json::Value::String(val)) if val == "second"), "{:?}", response);
+
+        // ... or within a JSON argument
+        send_commands.send(CommandRequest::parse("::add [ $x.count, 4 ]").await).await.unwrap();
+
+        let response = response_stream.next().await.unwrap();
+        assert!(matches!(&response, CommandResponse::Json(val) if val == &serde_json::Value::from(6)), "{:?}", response);
+
+        // Paths that aren't in the variable produce an error
+        send_commands.send(CommandRequest::parse("::test $x.items[5].name").await).await.unwrap();
+
+        let response = response_stream.next().await.unwrap();
+        assert!(matches!(&response, CommandResponse::Error(_)), "{:?}", response);
+
+        context.send_message(TestSucceeded).await.unwrap();
+    }, 0);
+
+    TestBuilder::new()
+        .redirect_input(StreamId::with_message_type::<TestSucceeded>())
+        .expect_message(|_: TestSucceeded| Ok(()))
+        .run_in_scene_with_threads(&scene, test_program, 5);
+}
+
+#[test]
+pub fn pipe_command_output() {
+    let scene = Scene::default();
+
+    struct TestSucceeded;
+    impl SceneMessage for TestSucceeded { }
+
+    // Create a command program, and a launcher with some commands that can be piped together
+    let test_program        = SubProgramId::new();
+    let command_program     = SubProgramId::new();
+    let launcher_program    = SubProgramId::new();
+    scene.add_subprogram(command_program, |input, context| command_connection_program(input, context, ()), 0);
+
+    let json_launcher = CommandLauncher::json()
+        .with_json_command("::add", |param: Vec<i64>, _context| async move {
+            CommandResponse::Json(serde_json::Value::from(param.into_iter().sum::<i64>()))
+        })
+        .with_json_command("::double", |param: i64, _context| async move {
+            CommandResponse::Json(serde_json::Value::from(param * 2))
+        })
+        .with_json_command("::count", |_param: (), _context| async move {
+            CommandResponse::BackgroundStream(stream::iter(vec![serde_json::Value::from(1), serde_json::Value::from(2), serde_json::Value::from(3)]).boxed())
+        })
+        .with_json_command("::odd", |param: i64, _context| async move {
+            if param % 2 == 1 {
+                CommandResponse::Json(serde_json::Value::from(param))
+            } else {
+                CommandResponse::Error(format!("{} is even", param))
+            }
+        })
+        .with_json_command("::fail", |_param: (), _context| async move {
+            CommandResponse::Error("Failed".to_string())
+        });
+    scene.add_subprogram(launcher_program, json_launcher.to_subprogram(), 1);
+
+    scene.add_subprogram(SubProgramId::called("Test"), move |_: InputStream<()>, context| async move {
+        let (send_commands, recv_commands)      = mpsc::channel(1);
+        let (send_responses, recv_responses)    = oneshot::channel();
+
+        // The launcher needs to be running before the dispatcher can find its commands
+        wait_for_program(&context, launcher_program).await;
+
+        // Request a connection
+        let connection = SocketConnection::new(&context, recv_commands, move |_context, output| { send_responses.send(output).ok(); });
+        context.send(command_program).unwrap().send(CommandProgramSocketMessage::Connection(connection)).await.ok().unwrap();
+
+        let mut send_commands   = send_commands;
+        let mut response_stream = recv_responses.await.unwrap();
+
+        // JSON output becomes the argument of the next command
+        send_commands.send(CommandRequest::parse("::add [ 1, 2 ] | ::double").await).await.unwrap();
+
+        let response = response_stream.next().await.unwrap();
+        assert!(matches!(&response, CommandResponse::Json(val) if val == &serde_json::Value::from(6)), "{:?}", response);
+
+        // ... or can be referred to as $input if the command already has an argument
+        send_commands.send(CommandRequest::parse("::add [ 1, 2 ] | ::add [ $input, 10 ]").await).await.unwrap();
+
+        let response = response_stream.next().await.unwrap();
+        assert!(matches!(&response, CommandResponse::Json(val) if val == &serde_json::Value::from(13)), "{:?}", response);
+
+        // Background streams are collected and passed to the next command as an array
+        send_commands.send(CommandRequest::parse("::count | ::add").await).await.unwrap();
+
+        let response = response_stream.next().await.unwrap();
+        assert!(matches!(&response, CommandResponse::Json(val) if val == &serde_json::Value::from(6)), "{:?}", response);
+
+        // The next command is only run once, so it produces an error if it can't accept an array
+        send_commands.send(CommandRequest::parse("::count | ::double").await).await.unwrap();
+
+        let response = response_stream.next().await.unwrap();
+        assert!(matches!(&response, CommandResponse::Error(_)), "{:?}", response);
+
+        // Errors stop the pipe
+        send_commands.send(CommandRequest::parse("::fail | ::double").await).await.unwrap();
+
+        let response = response_stream.next().await.unwrap();
+        assert!(matches!(&response, CommandResponse::Error(msg) if msg == "Failed"), "{:?}", response);
+
+        // $input always refers to the piped value, so it can't be assigned
+        send_commands.send(CommandRequest::parse("input = ::add [ 1, 2 ]").await).await.unwrap();
+
+        let response = response_stream.next().await.unwrap();
+        assert!(matches!(&response, CommandResponse::Error(_)), "{:?}", response);
+
+        context.send_message(TestSucceeded).await.unwrap();
+    }, 0);
+
+    TestBuilder::new()
+        .redirect_input(StreamId::with_message_type::<TestSucceeded>())
+        .expect_message(|_: TestSucceeded| Ok(()))
+        .run_in_scene_with_threads(&scene, test_program, 5);
+}
+
+#[test]
+pub fn list_commands_with_help() {
+    let scene = Scene::default();
+
+    struct TestSucceeded;
+    impl SceneMessage for TestSucceeded { }
+
+    // Create a command program, and a launcher with the standard commands and some extra test commands
+    let test_program        = SubProgramId::new();
+    let command_program     = SubProgramId::new();
+    let launcher_program    = SubProgramId::new();
+    scene.add_subprogram(command_program, |input, context| command_connection_program(input, context, ()), 0);
+
+    let json_launcher = CommandLauncher::json()
+        .with_standard_commands()
+        .with_json_command("::test", |param: String, _context| async move {
+            CommandResponse::Json(serde_json::Value::String(param))
+        })
+        .with_json_command_described("::add", "Adds numbers together", |param: Vec<i64>, _context| async move {
+            CommandResponse::Json(serde_json::Value::from(param.into_iter().sum::<i64>()))
+        });
+    scene.add_subprogram(launcher_program, json_launcher.to_subprogram(), 1);
+
+    scene.add_subprogram(SubProgramId::called("Test"), move |_: InputStream<()>, context| async move {
+        let (send_commands, recv_commands)      = mpsc::channel(1);
+        let (send_responses, recv_responses)    = oneshot::channel();
+
+        // The launcher needs to be running before the dispatcher can find its commands
+        wait_for_program(&context, launcher_program).await;
+
+        // Request a connection
+        let connection = SocketConnection::new(&context, recv_commands, move |_context, output| { send_responses.send(output).ok(); });
+        context.send(command_program).unwrap().send(CommandProgramSocketMessage::Connection(connection)).await.ok().unwrap();
+
+        let mut send_commands   = send_commands;
+        let mut response_stream = recv_responses.await.unwrap();
+
+        // 'help' lists all of the commands
+        send_commands.send(CommandRequest::parse("help").await).await.unwrap();
+
+        let response = response_stream.next().await.unwrap();
+        let commands = if let CommandResponse::Json(commands) = &response { serde_json::from_value::<Vec<CommandHelp>>(commands.clone()).unwrap() } else { panic!("{:?}", response) };
+        let names    = commands.iter().map(|command| command.name.clone()).collect::<Vec<_>>();
+        assert!(names.contains(&"help".to_string()), "{:?}", names);
+        assert!(names.contains(&"echo".to_string()), "{:?}", names);
+        assert!(names.contains(&"::test".to_string()), "{:?}", names);
+        assert!(names.contains(&"::add".to_string()), "{:?}", names);
+
+        // Commands can have descriptions
+        let help = commands.iter().find(|command| command.name == "help").unwrap();
+        assert!(help.description.is_some(), "{:?}", help);
+
+        // A string parameter lists only the commands with that prefix
+        send_commands.send(CommandRequest::parse("help \"::\"").await).await.unwrap();
+
+        let response = response_stream.next().await.unwrap();
+        assert!(matches!(&response, CommandResponse::Json(val) if val == &serde_json::json!([ { "name": "::add", "description": "Adds numbers together" }, { "name": "::test" } ])), "{:?}", response);
+
+        context.send_message(TestSucceeded).await.unwrap();
+    }, 0);
+
+    TestBuilder::new()
+        .redirect_input(StreamId::with_message_type::<TestSucceeded>())
+        .expect_message(|_: TestSucceeded| Ok(()))
+        .run_in_scene_with_threads(&scene, test_program, 5);
+}
+
+#[test]
+pub fn subscribe_to_events() {
+    let scene = Scene::default();
+
+    struct TestSucceeded;
+    impl SceneMessage for TestSucceeded { }
+
+    #[derive(Clone, Serialize, Deserialize)]
+    struct TestEvent(usize);
+    impl SceneMessage for TestEvent { }
+
+    scene.with_serializer(|| serde_json::value::Serializer)
+        .with_serializable_type::<TestEvent>("command_program_tests::TestEvent");
+
+    // Create a command program, and a launcher with the standard commands
+    let test_program        = SubProgramId::new();
+    let command_program     = SubProgramId::new();
+    let launcher_program    = SubProgramId::new();
+    let event_program       = SubProgramId::new();
+    scene.add_subprogram(command_program, |input, context| command_connection_program(input, context, ()), 0);
+    scene.add_subprogram(launcher_program, CommandLauncher::json().with_standard_commands().to_subprogram(), 1);
+
+    // The event program sends a couple of events to anything that subscribes to it
+    scene.add_subprogram(event_program, |input: InputStream<Subscribe<TestEvent>>, context| async move {
+        let mut input       = input;
+        let mut subscribers = EventSubscribers::new();
+
+        while let Some(subscription) = input.next().await {
+            subscribers.subscribe(&context, subscription.target());
+
+            subscribers.send(TestEvent(1)).await;
+            subscribers.send(TestEvent(2)).await;
+        }
+    }, 0);
+    scene.connect_programs((), event_program, StreamId::with_message_type::<Subscribe<TestEvent>>()).unwrap();
+
+    scene.add_subprogram(SubProgramId::called("Test"), move |_: InputStream<()>, context| async move {
+        let (send_commands, recv_commands)      = mpsc::channel(1);
+        let (send_responses, recv_responses)    = oneshot::channel();
+
+        // The launcher needs to be running before the dispatcher can find its commands
+        wait_for_program(&context, launcher_program).await;
+
+        // Request a connection
+        let connection = SocketConnection::new(&context, recv_commands, move |_context, output| { send_responses.send(output).ok(); });
+        context.send(command_program).unwrap().send(CommandProgramSocketMessage::Connection(connection)).await.ok().unwrap();
+
+        let mut send_commands   = send_commands;
+        let mut response_stream = recv_responses.await.unwrap();
+
+        // Subscribing generates a background stream of the serialized events
+        send_commands.send(CommandRequest::parse("subscribe \"command_program_tests::TestEvent\"").await).await.unwrap();
+
+        let response = response_stream.next().await.unwrap();
+        if let CommandResponse::BackgroundStream(events) = response {
+            let events = events.take(2).collect::<Vec<_>>().await;
+            assert!(events == vec![serde_json::json!(1), serde_json::json!(2)], "{:?}", events);
+        } else {
+            panic!("{:?}", response);
+        }
+
+        // Types that aren't serializable can't be subscribed to
+        send_commands.send(CommandRequest::parse("subscribe \"command_program_tests::NotAType\"").await).await.unwrap();
+
+        let response = response_stream.next().await.unwrap();
+        assert!(matches!(&response, CommandResponse::Error(_)), "{:?}", response);
+
+        // 'unsubscribe' asks the display to close a background stream
+        send_commands.send(CommandRequest::parse("unsubscribe 0").await).await.unwrap();
+
+        let response = response_stream.next().await.unwrap();
+        assert!(matches!(&response, CommandResponse::CloseBackgroundStream(0)), "{:?}", response);
+
+        context.send_message(TestSucceeded).await.unwrap();
+    }, 0);
+
+    TestBuilder::new()
+        .redirect_input(StreamId::with_message_type::<TestSucceeded>())
+        .expect_message(|_: TestSucceeded| Ok(()))
+        .run_in_scene_with_threads(&scene, test_program, 5);
+}
@@ -0,0 +1,219 @@
+use flo_scene_pipe::commands::*;
+
+use futures::prelude::*;
+use futures::executor;
+use futures::channel::mpsc;
+
+use serde_json::{json};
+
+///
+/// Displays some responses using `display_command_responses_json()`, and returns the JSON objects that were generated
+///
+fn display_json(responses: Vec<CommandResponse>) -> Vec<serde_json::Value> {
+    executor::block_on(async move {
+        let output = display_command_responses_json(stream::iter(responses)).collect::<Vec<_>>().await;
+        let output = String::from_utf8(output.concat()).unwrap();
+
+        output.lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect()
+    })
+}
+
+#[test]
+fn display_json_response() {
+    let output = display_json(vec![CommandResponse::Json(json!([1, 2, 3]))]);
+
+    assert!(output == vec![json!({ "type": "json", "value": [1, 2, 3] })], "{:?}", output);
+}
+
+#[test]
+fn display_json_message() {
+    let output = display_json(vec![CommandResponse::Message("Hello".into())]);
+
+    assert!(output == vec![json!({ "type": "message", "message": "Hello" })], "{:?}", output);
+}
+
+#[test]
+fn display_json_error() {
+    let output = display_json(vec![CommandResponse::Error("Failed".into())]);
+
+    assert!(output == vec![json!({ "type": "error", "message": "Failed" })], "{:?}", output);
+}
+
+#[test]
+fn display_json_responses_in_order() {
+    let output = display_json(vec![
+        CommandResponse::Message("First".into()),
+        CommandResponse::Json(json!(2)),
+        CommandResponse::Error("Third".into()),
+    ]);
+
+    assert!(output == vec![
+        json!({ "type": "message", "message": "First" }),
+        json!({ "type": "json", "value": 2 }),
+        json!({ "type": "error", "message": "Third" }),
+    ], "{:?}", output);
+}
+
+#[test]
+fn display_json_background_stream() {
+    executor::block_on(async {
+        // Keep the input open until the background stream has finished
+        let (mut send_responses, recv_responses) = mpsc::channel(1);
+        let mut output = display_command_responses_json(recv_responses);
+
+        send_responses.send(CommandResponse::BackgroundStream(stream::iter(vec![json!(1), json!(2)]).boxed())).await.unwrap();
+
+        // Read lines until the stream finishes
+        let mut lines = vec![];
+        while let Some(bytes) = output.next().await {
+            let line = serde_json::from_slice::<serde_json::Value>(&bytes).unwrap();
+            let end  = line["type"] == json!("stream_end");
+
+            lines.push(line);
+            if end { break; }
+        }
+
+        assert!(lines == vec![
+            json!({ "type": "stream_start", "id": 0 }),
+            json!({ "type": "stream_event", "id": 0, "value": 1 }),
+            json!({ "type": "stream_event", "id": 0, "value": 2 }),
+            json!({ "type": "stream_end", "id": 0 }),
+        ], "{:?}", lines);
+
+        // Closing the input finishes the output
+        drop(send_responses);
+        assert!(output.next().await.is_none());
+    });
+}
+
+#[test]
+fn display_json_close_background_stream() {
+    executor::block_on(async {
+        // Start a stream that never finishes, then close it (and a stream that doesn't exist), keeping the input open afterwards
+        let responses = stream::iter(vec![
+            CommandResponse::BackgroundStream(stream::pending().boxed()),
+            CommandResponse::CloseBackgroundStream(0),
+            with_id("missing", CommandResponse::CloseBackgroundStream(5)),
+        ]).chain(stream::pending());
+        let mut output = display_command_responses_json(responses);
+
+        let mut lines = vec![];
+        while lines.len() < 3 {
+            let bytes = output.next().await.unwrap();
+            lines.push(serde_json::from_slice::<serde_json::Value>(&bytes).unwrap());
+        }
+
+        assert!(lines == vec![
+            json!({ "type": "stream_start", "id": 0 }),
+            json!({ "type": "stream_end", "id": 0 }),
+            json!({ "type": "error", "message": "Background stream 5 is not running", "request_id": "missing" }),
+        ], "{:?}", lines);
+    });
+}
+
+///
+/// Displays some responses using `display_command_responses()`, and returns the text that was generated
+///
+fn display_text(responses: Vec<CommandResponse>) -> String {
+    executor::block_on(async move {
+        let output = display_command_responses(stream::iter(responses)).collect::<Vec<_>>().await;
+        String::from_utf8(output.concat()).unwrap()
+    })
+}
+
+///
+/// Tags a response with a request ID
+///
+fn with_id(id: &str, response: CommandResponse) -> CommandResponse {
+    CommandResponse::WithRequestId(RequestId(id.to_string()), Box::new(response))
+}
+
+#[test]
+fn display_text_request_id() {
+    let output = display_text(vec![
+        with_id("1", CommandResponse::Json(json!(42))),
+        with_id("2", CommandResponse::Error("Failed".into())),
+        with_id("3", CommandResponse::Message("Hello".into())),
+    ]);
+
+    assert!(output.contains("@1 42\n"), "{:?}", output);
+    assert!(output.contains("@2 !!! Failed\n"), "{:?}", output);
+    assert!(output.contains("@3   Hello\n"), "{:?}", output);
+}
+
+#[test]
+fn display_json_request_id() {
+    let output = display_json(vec![
+        with_id("1", CommandResponse::Json(json!(42))),
+        with_id("2", CommandResponse::Error("Failed".into())),
+        CommandResponse::Message("Untagged".into()),
+    ]);
+
+    assert!(output == vec![
+        json!({ "type": "json", "value": 42, "request_id": "1" }),
+        json!({ "type": "error", "message": "Failed", "request_id": "2" }),
+        json!({ "type": "message", "message": "Untagged" }),
+    ], "{:?}", output);
+}
+
+#[test]
+fn display_background_stream_request_id() {
+    let background_stream = || with_id("bg", CommandResponse::BackgroundStream(stream::pending().boxed()));
+
+    // The announcement for the stream is tagged with the request that created it
+    let output = display_text(vec![background_stream()]);
+    assert!(output.contains("@bg <<< 0\n"), "{:?}", output);
+
+    let output = display_json(vec![background_stream()]);
+    assert!(output[0] == json!({ "type": "stream_start", "id": 0, "request_id": "bg" }), "{:?}", output);
+}
+
+#[test]
+fn request_id_round_trip() {
+    // Parse a tagged command
+    let request = executor::block_on(CommandRequest::parse("@round-trip some::command [ 1 ]")).unwrap();
+    let id      = match request {
+        CommandRequest::WithRequestId { id, .. }    => id,
+        other                                       => panic!("{:?}", other),
+    };
+
+    // Responding with the same ID displays it
+    let output = display_text(vec![CommandResponse::WithRequestId(id, Box::new(CommandResponse::Json(json!(1))))]);
+    assert!(output.contains("@round-trip 1\n"), "{:?}", output);
+}
+
+#[test]
+fn parse_with_errors_succeeds_for_valid_command() {
+    let request = executor::block_on(CommandRequest::parse_with_errors("some::command [ 1 ]"));
+    let expected = executor::block_on(CommandRequest::parse("some::command [ 1 ]")).unwrap();
+
+    assert!(request == Ok(expected), "{:?}", request);
+}
+
+#[test]
+fn parse_error_at_end_of_pipe() {
+    let error = executor::block_on(CommandRequest::parse_with_errors("some::command [ 1 ] |")).unwrap_err();
+
+    assert!(error.expected == "a command after '|'", "{:?}", error);
+    assert!(error.location == CommandLocation { offset: 21, line: 1, column: 22 }, "{:?}", error);
+}
+
+#[test]
+fn parse_error_in_json_argument() {
+    // The key in the object is missing its ':'
+    let error = executor::block_on(CommandRequest::parse_with_errors("some::command { \"a\" 1 }")).unwrap_err();
+
+    assert!(error.expected == "a JSON value", "{:?}", error);
+    assert!(error.location == CommandLocation { offset: 20, line: 1, column: 21 }, "{:?}", error);
+}
+
+#[test]
+fn parse_error_on_later_line() {
+    let error = executor::block_on(CommandRequest::parse_with_errors("\n\n@id [ 1 ]")).unwrap_err();
+
+    assert!(error.expected == "a command after the request ID", "{:?}", error);
+    assert!(error.location == CommandLocation { offset: 6, line: 3, column: 5 }, "{:?}", error);
+    assert!(error.to_string() == "Expected a command after the request ID at line 3, column 5", "{}", error);
+}
@@ -0,0 +1,29 @@
+//!
+//! Helper functions shared between the integration tests
+//!
+
+use flo_scene::*;
+use flo_scene::commands::*;
+use flo_scene::programs::*;
+
+use futures::prelude::*;
+use futures_timer::{Delay};
+
+use std::time::{Duration};
+
+///
+/// Waits until the scene control program reports that a subprogram has started (which is when the command dispatcher can find its commands)
+///
+pub async fn wait_for_program(context: &SceneContext, program_id: SubProgramId) {
+    loop {
+        let scene_status = context.spawn_query(ReadCommand::default(), Query::<SceneUpdate>::with_no_target(), ()).unwrap();
+        let scene_status = scene_status.collect::<Vec<_>>().await;
+
+        if scene_status.iter().any(|update| matches!(update, SceneUpdate::Started(started_program, _) if *started_program == program_id)) {
+            break;
+        }
+
+        // Give the program a chance to start before checking again
+        Delay::new(Duration::from_millis(1)).await;
+    }
+}
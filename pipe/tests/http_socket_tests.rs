@@ -0,0 +1,159 @@
+#![cfg(feature = "http")]
+
+mod common;
+
+use common::*;
+use flo_scene::*;
+use flo_scene::commands::*;
+use flo_scene_pipe::*;
+use flo_scene_pipe::commands::*;
+
+use futures::prelude::*;
+use futures::channel::oneshot;
+use tokio::io::*;
+use tokio::net::{TcpStream};
+
+use std::time::{Duration};
+
+///
+/// Sends a HTTP request to a port on the local machine, returning the response as a string
+///
+async fn send_http_request(port: u16, method: &str, body: &str) -> String {
+    send_http_request_with_connection(port, method, body, "close").await
+}
+
+///
+/// Sends a HTTP request with a particular value for the `Connection` header, returning the response once the server closes the connection
+///
+async fn send_http_request_with_connection(port: u16, method: &str, body: &str, connection: &str) -> String {
+    // Connect to the socket (retrying until the listener has started)
+    let mut socket = loop {
+        if let Ok(socket) = TcpStream::connect(("127.0.0.1", port)).await {
+            break socket;
+        }
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    };
+
+    let request = format!("{} / HTTP/1.1\r\nHost: localhost\r\nConnection: {}\r\nContent-Length: {}\r\n\r\n{}", method, connection, body.len(), body);
+    socket.write_all(request.as_bytes()).await.unwrap();
+
+    let mut response = String::new();
+    socket.read_to_string(&mut response).await.unwrap();
+
+    response
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn http_command_and_parse_error() {
+    // Find a free port to listen on
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+
+    let scene = Scene::default();
+
+    // Create a launcher with a command that parrots strings back to us
+    let launcher_program    = SubProgramId::new();
+    let json_launcher       = CommandLauncher::json()
+        .with_json_command("::test", |param: String, _context| async move {
+            CommandResponse::Json(serde_json::Value::String(param))
+        });
+    scene.add_subprogram(launcher_program, json_launcher.to_subprogram(), 1);
+
+    // The HTTP program sends commands to the default dispatcher
+    let http_program = SubProgramId::new();
+    start_http_command_socket(&scene, http_program, ("127.0.0.1", port), ()).unwrap();
+
+    // The launcher needs to be running before the dispatcher can find its commands
+    let (launcher_started, wait_for_launcher) = oneshot::channel();
+    scene.add_subprogram(SubProgramId::new(), move |_: InputStream<()>, context| async move {
+        wait_for_program(&context, launcher_program).await;
+        launcher_started.send(()).ok();
+    }, 0);
+
+    // Run the scene in the background
+    tokio::spawn(scene.run_scene());
+
+    let client = async move {
+        wait_for_launcher.await.unwrap();
+
+        let success     = send_http_request(port, "POST", "::test \"Hello\"").await;
+        let parse_error = send_http_request(port, "POST", "::test [ \"Hello\"").await;
+        let cmd_error   = send_http_request(port, "POST", "::doesnotexist \"Hello\"").await;
+        let wrong_verb  = send_http_request(port, "GET", "").await;
+        let too_large   = send_http_request(port, "POST", &format!("::test \"{}\"", "x".repeat(2 * 1024 * 1024))).await;
+
+        (success, parse_error, cmd_error, wrong_verb, too_large)
+    };
+
+    let (success, parse_error, cmd_error, wrong_verb, too_large) = tokio::time::timeout(Duration::from_secs(10), client).await.unwrap();
+    println!("{}\n\n{}\n\n{}\n\n{}\n\n{}", success, parse_error, cmd_error, wrong_verb, too_large);
+
+    assert!(success.starts_with("HTTP/1.1 200"), "{}", success);
+    assert!(success.ends_with("\"Hello\""), "{}", success);
+
+    assert!(parse_error.starts_with("HTTP/1.1 400"), "{}", parse_error);
+    assert!(parse_error.contains("{\"error\":"), "{}", parse_error);
+
+    assert!(cmd_error.starts_with("HTTP/1.1 500"), "{}", cmd_error);
+    assert!(cmd_error.contains("{\"error\":"), "{}", cmd_error);
+
+    assert!(wrong_verb.starts_with("HTTP/1.1 405"), "{}", wrong_verb);
+
+    assert!(too_large.starts_with("HTTP/1.1 413"), "{}", too_large);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn http_clients_do_not_hold_connection_slots() {
+    // Find a free port to listen on
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+
+    let scene = Scene::default();
+
+    let launcher_program    = SubProgramId::new();
+    let json_launcher       = CommandLauncher::json()
+        .with_json_command("::test", |param: String, _context| async move {
+            CommandResponse::Json(serde_json::Value::String(param))
+        });
+    scene.add_subprogram(launcher_program, json_launcher.to_subprogram(), 1);
+
+    let http_program = SubProgramId::new();
+    start_http_command_socket(&scene, http_program, ("127.0.0.1", port), ()).unwrap();
+
+    // Only serve one connection at a time, and disconnect clients that don't send a request quickly
+    let (launcher_started, wait_for_launcher) = oneshot::channel();
+    scene.add_subprogram(SubProgramId::new(), move |_: InputStream<()>, context| async move {
+        let mut http_program = context.send(http_program).unwrap();
+        http_program.send(SocketListenerMessage::SetMaxConnections(Some(1))).await.ok().unwrap();
+        http_program.send(SocketListenerMessage::SetIdleTimeout(Some(Duration::from_millis(200)))).await.ok().unwrap();
+
+        wait_for_program(&context, launcher_program).await;
+        launcher_started.send(()).ok();
+    }, 0);
+
+    tokio::spawn(scene.run_scene());
+
+    let client = async move {
+        wait_for_launcher.await.unwrap();
+
+        // A client that connects and never sends a request is disconnected after the idle timeout
+        let mut stalled_client  = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        let mut stalled_output  = vec![];
+        stalled_client.read_to_end(&mut stalled_output).await.ok();
+
+        // A client that asks to keep the connection alive has the connection closed after its request
+        let keep_alive = send_http_request_with_connection(port, "POST", "::test \"Keep alive\"", "keep-alive").await;
+
+        // The connection slot is free for the next request
+        let next_request = send_http_request(port, "POST", "::test \"Hello\"").await;
+
+        (keep_alive, next_request)
+    };
+
+    let (keep_alive, next_request) = tokio::time::timeout(Duration::from_secs(10), client).await.unwrap();
+
+    assert!(keep_alive.starts_with("HTTP/1.1 200"), "{}", keep_alive);
+    assert!(keep_alive.ends_with("\"Keep alive\""), "{}", keep_alive);
+
+    assert!(next_request.starts_with("HTTP/1.1 200"), "{}", next_request);
+    assert!(next_request.ends_with("\"Hello\""), "{}", next_request);
+}
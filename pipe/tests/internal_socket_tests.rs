@@ -1,3 +1,6 @@
+mod common;
+
+use common::*;
 use flo_scene::*;
 use flo_scene::commands::*;
 use flo_scene::programs::*;
@@ -233,3 +236,170 @@ fn read_from_background_stream() {
         .run_in_scene_with_threads(&scene, test_program, 5);
 }
 
+
+#[test]
+fn responses_do_not_cross_between_connections() {
+    let scene           = Scene::default();
+    let test_program    = SubProgramId::new();
+ 
+    struct TestSucceeded;
+    impl SceneMessage for TestSucceeded { }
+
+    // The command program accepts connections from the socket and interprets the commands
+    let command_program = SubProgramId::new();
+    scene.add_subprogram(command_program, |input, context| command_connection_program(input, context, ()), 0);
+
+    // The internal socket program lets us stream commands and responses via a socket connection
+    let socket_program = SubProgramId::new();
+    start_internal_socket_program(&scene, socket_program, parse_command_stream, display_command_responses).unwrap();
+
+    // Create a test command that echoes its parameter back to the connection
+    let launcher_program = SubProgramId::new();
+    scene.add_subprogram(launcher_program, 
+        CommandLauncher::json()
+            .with_json_command("test", |param: String, _context| async move {
+                CommandResponse::Json(serde_json::Value::String(param))
+            })
+            .to_subprogram(), 
+        0);
+
+    // Socket program is connected to the command program using the command program socket message (which generates connections)
+    scene.connect_programs(socket_program, command_program, StreamId::with_message_type::<CommandProgramSocketMessage>()).unwrap();
+
+    // Add another program that opens two connections at once and checks that each only receives its own responses
+    scene.add_subprogram(SubProgramId::new(), move |_input: InputStream<()>, context| async move {
+        // The launcher needs to be running before the dispatcher can find its commands
+        wait_for_program(&context, launcher_program).await;
+
+        // Initialise the JsonCommand message type before the connections race to use it
+        context.send::<JsonCommand>(()).unwrap();
+
+        let mut socket_program  = context.send(socket_program).unwrap();
+        let mut connections     = vec![];
+
+        for name in ["first", "second"] {
+            // Create an internal buffer for each connection
+            let (our_side, their_side)          = duplex(1024);
+            let (command_input, command_output) = split(their_side);
+            let (read_result, write_command)    = split(our_side);
+
+            socket_program.send(InternalSocketMessage::CreateInternalSocket(Box::new(command_input), Box::new(command_output))).await.ok().unwrap();
+            connections.push((name, read_result, write_command));
+        }
+
+        // Send the commands for both connections before reading any of the results
+        for (name, _, write_command) in connections.iter_mut() {
+            let test_commands = format!("test \"{}\"\ntest \"{}\"\ntest \"{}\"\n", name, name, name);
+            println!("> {:?}", test_commands);
+            write_command.write_all(&test_commands.bytes().collect::<Vec<u8>>()).await.unwrap();
+        }
+
+        // Each connection should see its own three responses and nothing from the other connection
+        for (name, read_result, write_command) in connections.iter_mut() {
+            let mut characters = String::new();
+            while let Ok(msg) = read_result.read_u8().await {
+                characters.push(msg as char);
+
+                if characters.matches(*name).count() >= 3 {
+                    break;
+                }
+            }
+
+            write_command.shutdown().await.unwrap();
+            println!("{}: {:?}", name, characters);
+
+            let other_name = if *name == "first" { "second" } else { "first" };
+            assert!(characters.matches(*name).count() == 3, "{:?}", characters);
+            assert!(!characters.contains(other_name), "{:?}", characters);
+        }
+
+        // Indicate successs
+        context.send_message(TestSucceeded).await.ok();
+    }, 0);
+
+    // Wait for the test program to indicate that it succeeded
+    TestBuilder::new()
+        .redirect_input(StreamId::with_message_type::<TestSucceeded>())
+        .expect_message(|_: TestSucceeded| Ok(()))
+        .run_in_scene_with_threads(&scene, test_program, 5);
+}
+
+#[test]
+fn shutdown_closes_background_streams() {
+    let scene           = Scene::default();
+    let test_program    = SubProgramId::new();
+ 
+    struct TestSucceeded;
+    impl SceneMessage for TestSucceeded { }
+
+    // The command program accepts connections from the socket and interprets the commands
+    let command_program = SubProgramId::new();
+    scene.add_subprogram(command_program, |input, context| command_connection_program(input, context, ()), 0);
+
+    // The internal socket program lets us stream commands and responses via a socket connection
+    let socket_program = SubProgramId::new();
+    start_internal_socket_program(&scene, socket_program, parse_command_stream, display_command_responses).unwrap();
+
+    // Create a test command that starts a background stream that never finishes on its own
+    let launcher_program = SubProgramId::new();
+    scene.add_subprogram(launcher_program, 
+        CommandLauncher::json()
+            .with_json_command("test", |_param: (), _context| async move {
+                CommandResponse::BackgroundStream(stream::iter(vec![serde_json::Value::String("one".to_string())]).chain(stream::pending()).boxed())
+            })
+            .to_subprogram(), 
+        0);
+
+    // Socket program is connected to the command program using the command program socket message (which generates connections)
+    scene.connect_programs(socket_program, command_program, StreamId::with_message_type::<CommandProgramSocketMessage>()).unwrap();
+
+    // Add another program that starts a background stream and then shuts down the socket program
+    scene.add_subprogram(SubProgramId::new(), move |_input: InputStream<()>, context| async move {
+        // The launcher needs to be running before the dispatcher can find its commands
+        wait_for_program(&context, launcher_program).await;
+
+        // Also create an internal buffer to write to
+        let (our_side, their_side)          = duplex(1024);
+        let (command_input, command_output) = split(their_side);
+        let (read_result, write_command)    = split(our_side);
+
+        // Request that the socket program read from the test commands and writes to the internal buffer
+        let mut socket_program = context.send(socket_program).unwrap();
+        socket_program.send(InternalSocketMessage::CreateInternalSocket(Box::new(command_input), Box::new(command_output))).await.ok().unwrap();
+
+        // Start the background stream (the write side of the connection is left open)
+        let mut write_command = write_command;
+        write_command.write_all(&"test\n".bytes().collect::<Vec<u8>>()).await.unwrap();
+
+        // Wait for the first value from the background stream
+        let mut read_result = read_result;
+        let mut characters  = String::new();
+        while let Ok(msg) = read_result.read_u8().await {
+            characters.push(msg as char);
+
+            if characters.contains("<0 \"one\"") {
+                break;
+            }
+        }
+
+        // Shut down the socket program: the connection should close the background stream and then sign out
+        socket_program.send(InternalSocketMessage::Shutdown).await.ok().unwrap();
+
+        while let Ok(msg) = read_result.read_u8().await {
+            characters.push(msg as char);
+        }
+
+        println!("{:?}", characters);
+        assert!(characters.contains("<EOS 0"), "{:?}", characters);
+        assert!(characters.ends_with("\n\n.\n"), "{:?}", characters);
+
+        // Indicate successs
+        context.send_message(TestSucceeded).await.ok();
+    }, 0);
+
+    // Wait for the test program to indicate that it succeeded
+    TestBuilder::new()
+        .redirect_input(StreamId::with_message_type::<TestSucceeded>())
+        .expect_message(|_: TestSucceeded| Ok(()))
+        .run_in_scene_with_threads(&scene, test_program, 5);
+}
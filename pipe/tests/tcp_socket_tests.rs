@@ -0,0 +1,170 @@
+use flo_scene::*;
+use flo_scene_pipe::*;
+use flo_scene_pipe::commands::*;
+
+use futures::prelude::*;
+use futures::channel::oneshot;
+use tokio::io::*;
+use tokio::net::{TcpStream};
+
+use std::time::{Duration, SystemTime};
+
+///
+/// Connects to a TCP port on the local machine, retrying until the listener has started
+///
+async fn connect_to_port(port: u16) -> TcpStream {
+    loop {
+        if let Ok(socket) = TcpStream::connect(("127.0.0.1", port)).await {
+            break socket;
+        }
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+///
+/// Reads from a socket until the command prompt is received
+///
+async fn read_prompt(socket: &mut TcpStream) {
+    let mut received = String::new();
+
+    while !received.contains("> ") {
+        let mut buf = [0u8; 256];
+        let len     = socket.read(&mut buf).await.unwrap();
+        assert!(len > 0, "Socket closed before prompt (received {:?})", received);
+
+        received.push_str(&String::from_utf8_lossy(&buf[0..len]));
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn tcp_connection_has_peer_address() {
+    // Find a free port to listen on
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+
+    let scene = Scene::default();
+
+    // The socket program accepts connections and sends them to the connection program
+    let socket_program      = SubProgramId::new();
+    let connection_program  = SubProgramId::new();
+    start_unencrpted_tcp_socket(&scene, socket_program, ("127.0.0.1", port), |input| input, |output| output.boxed()).unwrap();
+
+    // The connection program reports the metadata for the first connection it receives
+    let (send_connection_info, recv_connection_info) = oneshot::channel();
+    scene.add_subprogram(connection_program, move |input: InputStream<SocketMessage<Vec<u8>, Vec<u8>>>, _| async move {
+        let mut input = input;
+
+        if let Some(SocketMessage::Connection(connection)) = input.next().await {
+            send_connection_info.send((connection.peer_address().cloned(), connection.connected_at())).ok();
+        }
+    }, 0);
+
+    scene.connect_programs(socket_program, connection_program, StreamId::with_message_type::<SocketMessage<Vec<u8>, Vec<u8>>>()).unwrap();
+
+    // Run the scene in the background
+    tokio::spawn(scene.run_scene());
+
+    let before_connect  = SystemTime::now();
+    let client          = async move {
+        // Connect to the socket (retrying until the listener has started)
+        let socket = connect_to_port(port).await;
+
+        let (peer_address, connected_at) = recv_connection_info.await.unwrap();
+
+        (socket.local_addr().unwrap(), peer_address, connected_at)
+    };
+
+    let (client_address, peer_address, connected_at) = tokio::time::timeout(Duration::from_secs(10), client).await.unwrap();
+
+    // The peer address of the connection should be the address of our client
+    assert!(peer_address == Some(PeerAddress::Ip(client_address)), "{:?} != {:?}", peer_address, client_address);
+    assert!(connected_at >= before_connect);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn tcp_connection_limit_queues_connections() {
+    // Find a free port to listen on
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+
+    let scene = Scene::default();
+
+    // Command program that greets each connection with a prompt
+    let command_program = SubProgramId::new();
+    scene.add_subprogram(command_program, |input, context| command_connection_program(input, context, ()), 0);
+
+    let socket_program = SubProgramId::new();
+    start_unencrpted_tcp_socket(&scene, socket_program, ("127.0.0.1", port), parse_command_stream, display_command_responses).unwrap();
+    scene.connect_programs(socket_program, command_program, StreamId::with_message_type::<CommandProgramSocketMessage>()).unwrap();
+
+    // Only allow one connection at a time
+    let (limit_set, wait_for_limit) = oneshot::channel();
+    scene.add_subprogram(SubProgramId::new(), move |_: InputStream<()>, context| async move {
+        context.send(socket_program).unwrap().send(SocketListenerMessage::SetMaxConnections(Some(1))).await.ok().unwrap();
+        limit_set.send(()).ok();
+    }, 0);
+
+    // Run the scene in the background
+    tokio::spawn(scene.run_scene());
+
+    let client = async move {
+        wait_for_limit.await.unwrap();
+
+        // The first connection is accepted and receives a prompt
+        let mut first_connection = connect_to_port(port).await;
+        read_prompt(&mut first_connection).await;
+
+        // The second connection is queued, so it receives nothing while the first connection is open
+        let mut second_connection   = connect_to_port(port).await;
+        let mut buf                 = [0u8; 256];
+        let second_read             = tokio::time::timeout(Duration::from_millis(250), second_connection.read(&mut buf)).await;
+        assert!(second_read.is_err(), "Second connection was not queued ({:?})", second_read);
+
+        // Once the first connection is closed, the second connection is accepted
+        first_connection.shutdown().await.unwrap();
+        let mut remaining = vec![];
+        first_connection.read_to_end(&mut remaining).await.unwrap();
+
+        read_prompt(&mut second_connection).await;
+    };
+
+    tokio::time::timeout(Duration::from_secs(10), client).await.unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn tcp_idle_connection_is_closed() {
+    // Find a free port to listen on
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+
+    let scene = Scene::default();
+
+    let command_program = SubProgramId::new();
+    scene.add_subprogram(command_program, |input, context| command_connection_program(input, context, ()), 0);
+
+    let socket_program = SubProgramId::new();
+    start_unencrpted_tcp_socket(&scene, socket_program, ("127.0.0.1", port), parse_command_stream, display_command_responses).unwrap();
+    scene.connect_programs(socket_program, command_program, StreamId::with_message_type::<CommandProgramSocketMessage>()).unwrap();
+
+    // Close connections that don't send anything for 100ms
+    let (timeout_set, wait_for_timeout) = oneshot::channel();
+    scene.add_subprogram(SubProgramId::new(), move |_: InputStream<()>, context| async move {
+        context.send(socket_program).unwrap().send(SocketListenerMessage::SetIdleTimeout(Some(Duration::from_millis(100)))).await.ok().unwrap();
+        timeout_set.send(()).ok();
+    }, 0);
+
+    // Run the scene in the background
+    tokio::spawn(scene.run_scene());
+
+    let client = async move {
+        wait_for_timeout.await.unwrap();
+
+        // Connect and send nothing: the server should close the connection after signing out
+        let mut connection  = connect_to_port(port).await;
+        let mut received    = vec![];
+        connection.read_to_end(&mut received).await.unwrap();
+
+        String::from_utf8_lossy(&received).to_string()
+    };
+
+    let received = tokio::time::timeout(Duration::from_secs(5), client).await.unwrap();
+    assert!(received.ends_with("\n.\n"), "Received {:?}", received);
+}
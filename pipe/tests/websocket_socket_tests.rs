@@ -0,0 +1,132 @@
+#![cfg(feature = "websocket")]
+
+use flo_scene::*;
+use flo_scene_pipe::*;
+use flo_scene_pipe::commands::*;
+
+use futures::prelude::*;
+use tokio_tungstenite::{connect_async};
+use tokio_tungstenite::tungstenite::{Message};
+
+use std::time::{Duration};
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn error_from_websocket() {
+    // Find a free port to listen on
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+
+    let scene = Scene::default();
+
+    // The command program accepts connections from the socket and interprets the commands
+    let command_program = SubProgramId::new();
+    scene.add_subprogram(command_program, |input, context| command_connection_program(input, context, ()), 0);
+
+    // The websocket program accepts connections from websocket clients
+    let socket_program = SubProgramId::new();
+    start_websocket_socket_program(&scene, socket_program, ("127.0.0.1", port), parse_command_stream, display_command_responses).unwrap();
+
+    // Socket program is connected to the command program using the command program socket message (which generates connections)
+    scene.connect_programs(socket_program, command_program, StreamId::with_message_type::<CommandProgramSocketMessage>()).unwrap();
+
+    // Run the scene in the background
+    tokio::spawn(scene.run_scene());
+
+    let client = async move {
+        // Connect to the websocket (retrying until the listener has started)
+        let url = format!("ws://127.0.0.1:{}", port);
+        let mut websocket = loop {
+            if let Ok((websocket, _)) = connect_async(&url).await {
+                break websocket;
+            }
+
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        };
+
+        // Send a command that generates an error
+        websocket.send(Message::text("error::message \"json\"\n")).await.unwrap();
+
+        // Read frames until we see the error response
+        let mut response = String::new();
+        while let Some(Ok(frame)) = websocket.next().await {
+            if let Message::Text(text) = frame {
+                println!("{}", text.as_str());
+                response.push_str(text.as_str());
+            }
+
+            if response.contains("!!!") {
+                break;
+            }
+        }
+
+        // Pings are answered by the server
+        websocket.send(Message::Ping(vec![1, 2, 3].into())).await.unwrap();
+        let pong = loop {
+            match websocket.next().await {
+                Some(Ok(Message::Pong(payload)))    => break Some(payload.to_vec()),
+                Some(Ok(_))                         => { }
+                _                                   => break None,
+            }
+        };
+
+        // Close the connection
+        websocket.close(None).await.unwrap();
+
+        (response, pong)
+    };
+
+    let (response, pong) = tokio::time::timeout(Duration::from_secs(10), client).await.unwrap();
+
+    assert!(response.contains("!!!"), "Response was {:?}", response);
+    assert!(pong == Some(vec![1, 2, 3]), "Pong was {:?}", pong);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn stalled_handshake_does_not_block_other_clients() {
+    // Find a free port to listen on
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+
+    let scene = Scene::default();
+
+    let command_program = SubProgramId::new();
+    scene.add_subprogram(command_program, |input, context| command_connection_program(input, context, ()), 0);
+
+    let socket_program = SubProgramId::new();
+    start_websocket_socket_program(&scene, socket_program, ("127.0.0.1", port), parse_command_stream, display_command_responses).unwrap();
+    scene.connect_programs(socket_program, command_program, StreamId::with_message_type::<CommandProgramSocketMessage>()).unwrap();
+
+    tokio::spawn(scene.run_scene());
+
+    let client = async move {
+        // Open a TCP connection that never sends the websocket handshake
+        let _stalled_client = loop {
+            if let Ok(stalled_client) = tokio::net::TcpStream::connect(("127.0.0.1", port)).await {
+                break stalled_client;
+            }
+
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        };
+
+        // Another client should still be able to connect and run commands
+        let (mut websocket, _) = connect_async(format!("ws://127.0.0.1:{}", port)).await.unwrap();
+        websocket.send(Message::text("error::message \"json\"\n")).await.unwrap();
+
+        let mut response = String::new();
+        while let Some(Ok(frame)) = websocket.next().await {
+            if let Message::Text(text) = frame {
+                response.push_str(text.as_str());
+            }
+
+            if response.contains("!!!") {
+                break;
+            }
+        }
+
+        websocket.close(None).await.ok();
+
+        response
+    };
+
+    let response = tokio::time::timeout(Duration::from_secs(10), client).await.unwrap();
+
+    assert!(response.contains("!!!"), "Response was {:?}", response);
+}
@@ -16,6 +16,9 @@ use std::iter;
 /// The name of the command sent to request the list command response
 pub const LIST_COMMANDS: &str = "list_commands";
 
+/// The name of the command sent to request the descriptions of the commands supported by a program
+pub const DESCRIBE_COMMANDS: &str = "describe_commands";
+
 ///
 /// Runs the command dispatcher subprogram for a particular type of command
 ///
@@ -28,7 +31,8 @@ pub const LIST_COMMANDS: &str = "list_commands";
 /// is run again (in which case the commands are re-scanned)
 ///
 /// This dispatcher itself also supports the list commands request, to list all of the commands found in all of the subprograms
-/// in the scene.
+/// in the scene. The describe commands request is passed on to every subprogram that owns a command, and the responses are
+/// sent back unchanged.
 ///
 pub async fn command_dispatcher_subprogram<TParameter, TResponse>(input: InputStream<RunCommand<TParameter, TResponse>>, context: SceneContext)
 where
@@ -51,8 +55,8 @@ where
             });
 
         // We might need to update our list of commands before evaluating this one
-        // We update if the LIST_COMMANDS command is sent, or if the command is not known, or if there's no way to contact the existing target
-        if next_command.name() == LIST_COMMANDS || command_owner_stream.is_none() {
+        // We update if the LIST_COMMANDS or DESCRIBE_COMMANDS command is sent, or if the command is not known, or if there's no way to contact the existing target
+        if next_command.name() == LIST_COMMANDS || next_command.name() == DESCRIBE_COMMANDS || command_owner_stream.is_none() {
             // Request the current scene status
             let scene_status = context.spawn_query(ReadCommand::default(), Query::<SceneUpdate>::with_no_target(), ());
             let scene_status = if let Ok(scene_status) = scene_status {
@@ -132,6 +136,20 @@ where
 
                 response_stream.send(QueryResponse::with_iterator(command_names.into_iter().map(|name| ListCommandResponse(name).into()).collect::<Vec<_>>())).await.ok();
             }
+        } else if next_command.name() == DESCRIBE_COMMANDS {
+            // Ask each subprogram that owns a command for its descriptions, and send them all back
+            let command_owners      = commands.values().copied().collect::<HashSet<_>>();
+            let mut descriptions    = vec![];
+
+            for command_owner in command_owners {
+                if let Ok(owner_descriptions) = context.spawn_query(ReadCommand::default(), RunCommand::<TParameter, TResponse>::new((), DESCRIBE_COMMANDS, ()), command_owner) {
+                    descriptions.extend(owner_descriptions.collect::<Vec<_>>().await);
+                }
+            }
+
+            if let Ok(mut response_stream) = context.send::<QueryResponse<TResponse>>(next_command.target()) {
+                response_stream.send(QueryResponse::with_iterator(descriptions)).await.ok();
+            }
         } else if let Some(command_owner_stream) = command_owner_stream {
             // Forward the command to the target stream
             let mut command_owner_stream = command_owner_stream;
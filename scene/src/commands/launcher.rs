@@ -20,12 +20,19 @@ use std::sync::*;
 /// spawning a task using a function. This is the typical way that a group of command queries is declared.
 ///
 /// The launcher will also respond to the `::list_commands` command with a list of responses converted from
-/// the `ListCommandResponse` struture.
+/// the `ListCommandResponse` struture, and to the `describe_commands` command with the descriptions of any commands
+/// added with `with_command_described()`.
 ///
 pub struct CommandLauncher<TParameter, TResponse> {
     /// The commands are invoked as a subtask when a `RunCommand<TParameter, Result<TResponse, CommandError>>` request is made
     commands: HashMap<String, Arc<dyn Send + Sync + Fn(&TParameter, SceneContext) -> BoxFuture<'static, ()>>>,
 
+    /// Descriptions of the commands, which are sent in response to the `describe_commands` command
+    descriptions: HashMap<String, String>,
+
+    /// Converts the descriptions to responses (set when the first described command is added, as only some response types support descriptions)
+    describe: Option<fn(CommandDescription) -> TResponse>,
+
     response: PhantomData<TResponse>,
 }
 
@@ -39,8 +46,10 @@ where
     ///
     pub fn empty() -> Self {
         CommandLauncher {
-            commands: HashMap::new(),
-            response: PhantomData
+            commands:       HashMap::new(),
+            descriptions:   HashMap::new(),
+            describe:       None,
+            response:       PhantomData
         }
     }
 
@@ -81,6 +90,22 @@ where
                     if let Ok(mut response) = response {
                         response.send(list_commands_response).await.ok();
                     }
+                } else if run_request.name() == DESCRIBE_COMMANDS {
+                    // Describe the commands in the launcher that have descriptions
+                    let command_target  = run_request.target();
+                    let descriptions    = if let Some(describe) = self.describe {
+                        self.descriptions.iter()
+                            .map(|(name, description)| describe(CommandDescription { name: name.clone(), description: description.clone() }))
+                            .collect::<Vec<_>>()
+                    } else {
+                        vec![]
+                    };
+
+                    let response = context.send::<QueryResponse<TResponse>>(command_target);
+
+                    if let Ok(mut response) = response {
+                        response.send(QueryResponse::with_iterator(descriptions)).await.ok();
+                    }
                 } else if let Some(command) = self.commands.get(run_request.name()).cloned() {
                     // Run the command
                     let command_target = run_request.target();
@@ -115,3 +140,24 @@ where
         }.boxed()
     }
 }
+
+impl<TParameter, TResponse> CommandLauncher<TParameter, TResponse>
+where
+    TParameter: 'static + Unpin + Send + Sync,
+    TResponse:  'static + Unpin + Send + SceneMessage + From<ListCommandResponse> + From<CommandError> + From<CommandDescription>,
+{
+    ///
+    /// Returns this launcher modified with a new command, along with a description of what it does that is sent in response to the `describe_commands` command
+    ///
+    pub fn with_command_described<TFuture>(mut self, command_name: impl Into<String>, description: impl Into<String>, command: impl 'static + Send + Sync + Fn(&TParameter, SceneContext) -> TFuture) -> Self
+    where
+        TFuture: 'static + Send + Future<Output=()>,
+    {
+        let command_name = command_name.into();
+
+        self.descriptions.insert(command_name.clone(), description.into());
+        self.describe = Some(TResponse::from);
+
+        self.with_command(command_name, command)
+    }
+}
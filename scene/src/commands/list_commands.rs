@@ -11,3 +11,19 @@ use crate::scene_message::*;
 pub struct ListCommandResponse(pub String);
 
 impl SceneMessage for ListCommandResponse { }
+
+///
+/// As part of a response to a describe commands request, this is a description of what one of the commands supported by the
+/// sender does. Commands without a description are left out of the response.
+///
+#[derive(Clone, PartialEq, Eq, Debug)]
+#[cfg_attr(feature="serde_support", derive(Serialize, Deserialize))]
+pub struct CommandDescription {
+    /// The name of the command
+    pub name: String,
+
+    /// What the command does
+    pub description: String,
+}
+
+impl SceneMessage for CommandDescription { }
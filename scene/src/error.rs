@@ -94,6 +94,50 @@ pub enum SceneSendError<TMessage> {
 
     /// The target program supports thread stealing, but it is already running on the current thread's callstack and can't re-enter
     CannotReEnterTargetProgram,
+
+    /// The target did not accept the message before the timeout passed to `send_timeout()` elapsed
+    Timeout(TMessage),
+}
+
+///
+/// Errors that can occur when parsing a subprogram ID from a string
+///
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+#[cfg_attr(feature = "serde_support", derive(Serialize, Deserialize))]
+pub enum SubProgramIdParseError {
+    /// The string is not in one of the formats `named:<name>`, `guid:<uuid>`, `task(<name>,<serial>)` or `guidtask(<uuid>,<serial>)`
+    UnrecognisedFormat(String),
+
+    /// A name was expected but the string was empty
+    MissingName,
+
+    /// The GUID could not be parsed
+    InvalidGuid(String),
+
+    /// The serial number of a task could not be parsed
+    InvalidSerial(String),
+}
+
+///
+/// Errors that can occur while checking that a message type can be serialized and deserialized
+///
+#[cfg(feature = "serde_support")]
+#[derive(Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+pub enum SerializationError {
+    /// The serializer has not been installed with `install_serializer()`
+    SerializerNotInstalled,
+
+    /// The message type has not been installed with `install_serializable_type()` for the serializer
+    TypeNotInstalled,
+
+    /// The serializer returned an error when serializing the message
+    CannotSerialize(String),
+
+    /// The serialized message could not be deserialized again
+    CannotDeserialize,
+
+    /// The message was deserialized, but the result was not the same as the original message
+    DeserializedValueDoesNotMatch,
 }
 
 impl<TMessage> SceneSendError<TMessage> {
@@ -110,6 +154,7 @@ impl<TMessage> SceneSendError<TMessage> {
             SceneSendError::TargetProgramEnded(msg)         => Some(msg),
             SceneSendError::StreamDisconnected(msg)         => Some(msg),
             SceneSendError::CannotReEnterTargetProgram      => None,
+            SceneSendError::Timeout(msg)                    => Some(msg),
         }
     }
 
@@ -127,6 +172,7 @@ impl<TMessage> SceneSendError<TMessage> {
             SceneSendError::TargetProgramEnded(msg)         => Some(msg),
             SceneSendError::StreamDisconnected(msg)         => Some(msg),
             SceneSendError::CannotReEnterTargetProgram      => None,
+            SceneSendError::Timeout(msg)                    => Some(msg),
         }
     }
 }
@@ -138,6 +184,7 @@ impl<TMessage> From<SceneSendError<TMessage>> for ConnectionError {
             SceneSendError::TargetProgramEnded(_)           => ConnectionError::TargetNotInScene,
             SceneSendError::StreamDisconnected(_)           => ConnectionError::TargetNotAvailable,
             SceneSendError::CannotReEnterTargetProgram      => ConnectionError::CannotStealThread,
+            SceneSendError::Timeout(_)                      => ConnectionError::TargetNotAvailable,
         }
     }
 }
@@ -154,6 +154,43 @@ impl FilterHandle {
         handle
     }
 
+    ///
+    /// Creates a filter that converts each message of one type to a message of a different type using a mapping function
+    ///
+    /// This is a simpler way to create a filter than `for_filter()` for the case where each message converts to exactly one
+    /// message of the target type.
+    ///
+    pub fn map<TSourceMessage, TTargetMessage>(map_fn: impl 'static + Send + Sync + Fn(TSourceMessage) -> TTargetMessage) -> FilterHandle
+    where
+        TSourceMessage: 'static + SceneMessage,
+        TTargetMessage: 'static + SceneMessage,
+    {
+        let map_fn = Arc::new(map_fn);
+
+        Self::for_filter(move |input: InputStream<TSourceMessage>| {
+            let map_fn = Arc::clone(&map_fn);
+
+            input.map(move |message| (*map_fn)(message))
+        })
+    }
+
+    ///
+    /// Creates a filter that converts messages of one type to a different type, discarding any message where the mapping function returns `None`
+    ///
+    pub fn filter_map<TSourceMessage, TTargetMessage>(filter_map_fn: impl 'static + Send + Sync + Fn(TSourceMessage) -> Option<TTargetMessage>) -> FilterHandle
+    where
+        TSourceMessage: 'static + SceneMessage,
+        TTargetMessage: 'static + SceneMessage,
+    {
+        let filter_map_fn = Arc::new(filter_map_fn);
+
+        Self::for_filter(move |input: InputStream<TSourceMessage>| {
+            let filter_map_fn = Arc::clone(&filter_map_fn);
+
+            input.filter_map(move |message| future::ready((*filter_map_fn)(message)))
+        })
+    }
+
     ///
     /// Creates a filter that converts between two message types that implements `From`
     ///
@@ -0,0 +1,45 @@
+use crate::filter::*;
+use crate::input_stream::*;
+use crate::scene_message::*;
+
+use futures::prelude::*;
+use futures::future::{Either};
+use futures_timer::{Delay};
+
+use std::time::{Duration};
+
+///
+/// Creates a filter that groups messages into batches
+///
+/// A batch is started when a message arrives, and is sent on once it contains `max_size` messages or when `max_delay` has passed
+/// since its first message arrived, whichever comes first. Any partial batch is sent when the input stream is closed.
+///
+pub fn batch_filter<TMessage>(max_size: usize, max_delay: Duration) -> FilterHandle
+where
+    TMessage: 'static + SceneMessage,
+{
+    let max_size = max_size.max(1);
+
+    FilterHandle::for_filter(move |messages: InputStream<TMessage>| {
+        stream::unfold((messages, false), move |(mut messages, finished): (InputStream<TMessage>, bool)| async move {
+            if finished { return None; }
+
+            // Wait for the first message of the batch (the timer starts once it arrives)
+            let first_message   = messages.next().await?;
+            let mut batch       = vec![first_message];
+            let mut timeout     = Delay::new(max_delay);
+            let mut finished    = false;
+
+            // Fill the batch until it's full, the timer runs out or the input closes
+            while batch.len() < max_size {
+                match future::select(messages.next(), &mut timeout).await {
+                    Either::Left((Some(message), _))    => { batch.push(message); }
+                    Either::Left((None, _))             => { finished = true; break; }
+                    Either::Right(_)                    => { break; }
+                }
+            }
+
+            Some((batch, (messages, finished)))
+        })
+    })
+}
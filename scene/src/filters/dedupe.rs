@@ -0,0 +1,57 @@
+use crate::filter::*;
+use crate::input_stream::*;
+use crate::scene_message::*;
+
+use futures::prelude::*;
+
+use std::collections::{VecDeque};
+use std::sync::*;
+
+///
+/// Creates a filter that discards a message if it has the same key as the message before it
+///
+/// The key function is used to decide which messages are duplicates: for example, it can return the whole message to remove
+/// consecutive identical messages, or a version number to avoid re-processing an unchanged state snapshot.
+///
+pub fn dedupe_filter<TMessage, TKey>(key_fn: impl 'static + Send + Sync + Fn(&TMessage) -> TKey) -> FilterHandle
+where
+    TMessage:   'static + SceneMessage,
+    TKey:       'static + Send + PartialEq,
+{
+    dedupe_window_filter(1, key_fn)
+}
+
+///
+/// Creates a filter that discards a message if it has the same key as any of the last `window` messages that were delivered
+///
+pub fn dedupe_window_filter<TMessage, TKey>(window: usize, key_fn: impl 'static + Send + Sync + Fn(&TMessage) -> TKey) -> FilterHandle
+where
+    TMessage:   'static + SceneMessage,
+    TKey:       'static + Send + PartialEq,
+{
+    let key_fn = Arc::new(key_fn);
+    let window = window.max(1);
+
+    FilterHandle::for_filter(move |messages: InputStream<TMessage>| {
+        let key_fn          = Arc::clone(&key_fn);
+        let mut recent_keys = VecDeque::with_capacity(window);
+
+        messages.filter(move |message| {
+            let key = key_fn(message);
+
+            let is_duplicate = if recent_keys.contains(&key) {
+                true
+            } else {
+                // Remember this key, forgetting the oldest one if the window is full
+                if recent_keys.len() >= window {
+                    recent_keys.pop_front();
+                }
+                recent_keys.push_back(key);
+
+                false
+            };
+
+            future::ready(!is_duplicate)
+        })
+    })
+}
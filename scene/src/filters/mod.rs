@@ -0,0 +1,14 @@
+//!
+//! Standard filters that can be used to adjust how a stream of messages is delivered to a program
+//!
+//! These return `FilterHandle`s, so they can be used as a `StreamSource::Filtered` or `StreamTarget::Filtered` when connecting
+//! programs, in the same way as the serializer filters.
+//!
+
+mod rate_limit;
+mod dedupe;
+mod batch;
+
+pub use rate_limit::*;
+pub use dedupe::*;
+pub use batch::*;
@@ -0,0 +1,72 @@
+use crate::filter::*;
+use crate::input_stream::*;
+use crate::scene_message::*;
+
+use futures::prelude::*;
+use futures_timer::{Delay};
+
+use std::time::{Duration, Instant};
+
+///
+/// What a rate limiting filter does with messages that arrive faster than the rate limit
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum RateLimitMode {
+    /// Messages that arrive before the next message is allowed are discarded
+    DropExcess,
+
+    /// Messages that arrive before the next message is allowed are held until the limit allows them to be sent
+    DelayExcess,
+}
+
+///
+/// Creates a filter that limits a stream of messages to a maximum number per second
+///
+/// Messages are spaced out so that no more than one message is delivered every `1/per_second` seconds, with the `mode` deciding
+/// whether or not messages that arrive too early are discarded or delayed. When delaying messages, the filter stops reading its
+/// input while it waits, so the sender will eventually be blocked if it keeps sending faster than the limit.
+///
+/// Each call creates a new filter, so this should generally be called once and the resulting handle re-used for all the connections
+/// that need it.
+///
+pub fn rate_limit_filter<TMessage>(per_second: u32, mode: RateLimitMode) -> FilterHandle
+where
+    TMessage: 'static + SceneMessage,
+{
+    let interval = Duration::from_secs(1) / per_second.max(1);
+
+    FilterHandle::for_filter(move |messages: InputStream<TMessage>| {
+        stream::unfold((messages, None), move |(mut messages, mut next_allowed): (InputStream<TMessage>, Option<Instant>)| async move {
+            loop {
+                let message = messages.next().await?;
+                let now     = Instant::now();
+
+                match (mode, next_allowed) {
+                    (_, None) => {
+                        // The first message is always sent immediately
+                    }
+
+                    (_, Some(next_time)) if next_time <= now => {
+                        // The rate limit has not been reached
+                    }
+
+                    (RateLimitMode::DropExcess, Some(_)) => {
+                        // Discard the message and wait for the next one
+                        continue;
+                    }
+
+                    (RateLimitMode::DelayExcess, Some(next_time)) => {
+                        // Wait until the message is allowed
+                        Delay::new(next_time - now).await;
+                    }
+                }
+
+                // Send this message and work out when the next message can be sent
+                let send_time   = next_allowed.map(|next_time| next_time.max(now)).unwrap_or(now);
+                next_allowed    = Some(send_time + interval);
+
+                return Some((message, (messages, next_allowed)));
+            }
+        })
+    })
+}
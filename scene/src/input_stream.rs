@@ -1,6 +1,7 @@
 use crate::error::*;
 use crate::scene_message::*;
 use crate::scene_core::*;
+use crate::stream_stats::*;
 use crate::subprogram_id::*;
 
 use futures::prelude::*;
@@ -9,6 +10,26 @@ use futures::task::{Waker, Poll, Context};
 use std::collections::*;
 use std::sync::*;
 
+///
+/// What an input stream does when a message is sent to it while its buffer is full
+///
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum InputStreamMode {
+    /// The sender waits until there is space in the buffer (the default)
+    Backpressure,
+
+    /// The oldest waiting message is discarded to make space for the new one
+    DropOldest,
+
+    /// The new message is discarded
+    DropNewest,
+}
+
+///
+/// Function that assigns a priority to a message waiting in an input stream
+///
+type MessagePriorityFn<TMessage> = Box<dyn Send + Fn(&TMessage) -> u8>;
+
 ///
 /// The input stream core is a shareable part of an input stream for a program
 ///
@@ -19,11 +40,17 @@ pub (crate) struct InputStreamCore<TMessage> {
     /// The maximum number of waiting messages for this input stream
     max_waiting: usize,
 
+    /// What happens when a message is sent while there are already `max_waiting` messages waiting
+    mode: InputStreamMode,
+
     /// The scene that this input is a part of
     scene_core: Weak<Mutex<SceneCore>>,
 
-    /// Messages waiting to be delivered
-    waiting_messages: VecDeque<(SubProgramId, TMessage)>,
+    /// Messages waiting to be delivered, along with the priority they were assigned when they were queued (0 if there's no priority function)
+    waiting_messages: VecDeque<(SubProgramId, u8, TMessage)>,
+
+    /// If set, waiting messages are delivered in order of priority (highest first) instead of in the order they were sent
+    priority: Option<MessagePriorityFn<TMessage>>,
 
     /// A waker for the future that is waiting for the next message in this stream
     when_message_sent: Option<Waker>,
@@ -42,6 +69,9 @@ pub (crate) struct InputStreamCore<TMessage> {
 
     /// True if this stream has been polled while empty, false if this stream has recently returned a value
     idle: bool,
+
+    /// Counts the messages that pass through this stream
+    counters: Arc<StreamCounters>,
 }
 
 /// A struct that unblocks an input stream when dropped
@@ -74,6 +104,9 @@ pub struct InputStream<TMessage> {
 ///
 struct InputStreamWithSources<TMessage> {
     pub (crate) core: Arc<Mutex<InputStreamCore<TMessage>>>,
+
+    /// Set to false if the core should not be closed when this is dropped
+    active: bool,
 }
 
 impl<TMessage> Drop for BlockedStream<TMessage> {
@@ -126,14 +159,17 @@ where
         let core = InputStreamCore {
             program_id:             program_id,
             max_waiting:            max_waiting,
+            mode:                   InputStreamMode::Backpressure,
             scene_core:             Arc::downgrade(scene_core),
             waiting_messages:       VecDeque::new(),
+            priority:               None,
             when_message_sent:      None,
             when_slots_available:   VecDeque::new(),
             blocked:                0,
             allow_thread_stealing:  TMessage::allow_thread_stealing_by_default(),
             closed:                 false,
             idle:                   false,
+            counters:               Arc::new(StreamCounters::default()),
         };
 
         InputStream {
@@ -153,13 +189,62 @@ where
     /// Upgrades this stream to return the messages with the source subprogram IDs
     ///
     pub fn messages_with_sources(mut self) -> impl Stream<Item=(SubProgramId, TMessage)> {
+        let active  = self.active;
         self.active = false;
 
         InputStreamWithSources {
-            core: self.core.clone(),
+            core:   self.core.clone(),
+            active: active,
         }
     }
 
+    ///
+    /// Creates another input stream that reads from the same core as this one, but which does not close it when dropped
+    ///
+    /// This is used when the messages in the stream need to be passed on to something that might stop early, such as a
+    /// supervised program that can be restarted.
+    ///
+    pub (crate) fn reattach(&self) -> InputStream<TMessage> {
+        InputStream {
+            core:   Arc::clone(&self.core),
+            active: false,
+        }
+    }
+
+    ///
+    /// Returns true if this input stream has been closed
+    ///
+    pub (crate) fn is_closed(&self) -> bool {
+        self.core.lock().unwrap().is_closed()
+    }
+
+    ///
+    /// Reads a single message from this stream, then closes it
+    ///
+    /// This is useful for subprograms or tasks that handle a single request: the program can finish as soon as the message has
+    /// been processed, and any further messages sent to it will be refused. Returns `None` if the stream was closed before a
+    /// message arrived.
+    ///
+    pub async fn recv_one(self) -> Option<TMessage> {
+        use std::mem;
+
+        let mut input   = self;
+        let message     = input.next().await;
+
+        // Close the input so nothing else can be queued for this program
+        let (waker, when_slots_available) = {
+            let mut core = input.core.lock().unwrap();
+
+            (core.close(), core.when_slots_available.drain(..).collect::<Vec<_>>())
+        };
+
+        // Wake anything that was waiting for the stream (they'll find it's closed)
+        mem::drop(input);
+        waker.into_iter().chain(when_slots_available).for_each(|waker| waker.wake());
+
+        message
+    }
+
     ///
     /// Returns an object that can be used to block this stream
     ///
@@ -200,9 +285,108 @@ where
     pub fn allow_thread_stealing(&self, enable: bool) {
         self.core.lock().unwrap().allow_thread_stealing = enable;
     }
+
+    ///
+    /// Changes the number of messages that can be waiting in this stream before anything sending to it has to wait
+    ///
+    /// This is initially the `max_input_waiting` value that was passed in when the subprogram was added to the scene. A larger
+    /// buffer lets a bursty sender carry on without waiting for this program to process its messages, and a value of 0 means
+    /// that a sender will wait whenever a message is already waiting to be processed.
+    ///
+    pub fn set_max_waiting(&self, max_waiting: usize) {
+        use std::mem;
+
+        let mut core = self.core.lock().unwrap();
+        core.max_waiting = max_waiting;
+
+        // Wake anything that was waiting for a slot, as there may be space now
+        if core.blocked == 0 && !core.is_queue_full() {
+            let when_slots_available = core.when_slots_available.drain(..).collect::<Vec<_>>();
+            mem::drop(core);
+
+            when_slots_available.into_iter()
+                .for_each(|waker| waker.wake());
+        }
+    }
+
+    ///
+    /// Sets what happens when a message is sent to this stream while its buffer is full
+    ///
+    /// By default, senders wait for space (`InputStreamMode::Backpressure`). The lossy modes discard messages instead, so
+    /// senders never wait for this program to catch up: this is useful for things like event monitors where only the
+    /// most recent messages are interesting. Call this before the program's future starts (ie, in the function passed to
+    /// `add_subprogram()`) to make sure that it applies to every message.
+    ///
+    pub fn set_input_mode(&self, mode: InputStreamMode) {
+        use std::mem;
+
+        let mut core = self.core.lock().unwrap();
+        core.mode = mode;
+
+        // Anything waiting for a slot can send immediately if the stream has become lossy
+        if core.blocked == 0 && !core.is_queue_full() {
+            let when_slots_available = core.when_slots_available.drain(..).collect::<Vec<_>>();
+            mem::drop(core);
+
+            when_slots_available.into_iter()
+                .for_each(|waker| waker.wake());
+        }
+    }
+
+    ///
+    /// Sets a function that assigns a priority to each message, so that messages with a higher priority are delivered first
+    ///
+    /// This has an effect when several messages are waiting in this stream: the waiting message with the highest priority is
+    /// returned next, and messages with the same priority are returned in the order they were sent. This can be used to let
+    /// control messages skip ahead of a queue of bulk data. Messages that are already waiting are reordered when this is called.
+    ///
+    pub fn set_priority(&self, priority: impl 'static + Send + Fn(&TMessage) -> u8) {
+        let mut core = self.core.lock().unwrap();
+
+        // Assign priorities to the existing messages and sort them (sort_by_key is stable, so messages with the same priority stay in order)
+        core.waiting_messages.iter_mut().for_each(|(_, message_priority, message)| *message_priority = priority(message));
+        core.waiting_messages.make_contiguous().sort_by_key(|(_, message_priority, _)| std::cmp::Reverse(*message_priority));
+        core.priority = Some(Box::new(priority));
+    }
 }
 
 impl<TMessage> InputStreamCore<TMessage> {
+    ///
+    /// Adds a message to the waiting queue, after any messages with the same or higher priority
+    ///
+    fn enqueue(&mut self, source: SubProgramId, message: TMessage) {
+        if let Some(priority) = &self.priority {
+            let message_priority    = priority(&message);
+            let insert_pos          = self.waiting_messages.iter()
+                .position(|(_, waiting_priority, _)| *waiting_priority < message_priority)
+                .unwrap_or(self.waiting_messages.len());
+
+            self.waiting_messages.insert(insert_pos, (source, message_priority, message));
+        } else {
+            self.waiting_messages.push_back((source, 0, message));
+        }
+
+        self.counters.message_queued();
+    }
+
+    ///
+    /// Discards the oldest waiting message (with a priority function, this is the oldest of the messages with the lowest priority)
+    ///
+    fn discard_oldest(&mut self) {
+        if self.priority.is_some() {
+            // The lowest priority messages are at the end of the queue
+            let lowest_priority = self.waiting_messages.back().map(|(_, message_priority, _)| *message_priority);
+            let discard_pos     = self.waiting_messages.iter().position(|(_, message_priority, _)| Some(*message_priority) == lowest_priority);
+
+            if let Some(discard_pos) = discard_pos {
+                self.waiting_messages.remove(discard_pos);
+                self.counters.message_dropped(true);
+            }
+        } else if self.waiting_messages.pop_front().is_some() {
+            self.counters.message_dropped(true);
+        }
+    }
+
     ///
     /// Retrieves the scene core for this input stream if there is one
     ///
@@ -214,14 +398,35 @@ impl<TMessage> InputStreamCore<TMessage> {
     /// Adds a message to this core if there's space for it, returning the waker to be called if successful (the waker must be called with the core unlocked)
     ///
     pub (crate) fn send(&mut self, source: SubProgramId, message: TMessage) -> Result<Option<Waker>, TMessage> {
-        if !self.closed && self.blocked == 0 && self.waiting_messages.len() <= self.max_waiting {
+        if self.closed || self.blocked != 0 {
+            // The input stream is blocked: return the message to sender
+            Err(message)
+        } else if self.waiting_messages.len() <= self.max_waiting {
             // The input stream is not blocked and has space in the waiting_messages queue for this event: queue it up and return the waker
-            self.waiting_messages.push_back((source, message));
+            self.enqueue(source, message);
             self.idle = false;
             Ok(self.when_message_sent.take())
         } else {
-            // The input stream is blocked or the queue is full: return the message to sender
-            Err(message)
+            match self.mode {
+                InputStreamMode::Backpressure => {
+                    // The queue is full: return the message to sender
+                    Err(message)
+                }
+
+                InputStreamMode::DropOldest => {
+                    // Make space by discarding the oldest message
+                    self.discard_oldest();
+                    self.enqueue(source, message);
+                    self.idle = false;
+                    Ok(self.when_message_sent.take())
+                }
+
+                InputStreamMode::DropNewest => {
+                    // Discard the message as if it had been delivered
+                    self.counters.message_dropped(false);
+                    Ok(None)
+                }
+            }
         }
     }
 
@@ -234,7 +439,7 @@ impl<TMessage> InputStreamCore<TMessage> {
         if self.closed {
             Err(SceneSendError::StreamDisconnected(message))
         } else {
-            self.waiting_messages.push_back((source, message));
+            self.enqueue(source, message);
             self.idle = false;
             Ok(self.when_message_sent.take())
         }
@@ -283,7 +488,8 @@ impl<TMessage> InputStreamCore<TMessage> {
     /// True if the queue for this input stream is full (the next `send()` call will fail)
     ///
     pub (crate) fn is_queue_full(&self) -> bool {
-        (self.max_waiting + 1) <= self.waiting_messages.len()
+        // Lossy streams can always accept a message
+        self.mode == InputStreamMode::Backpressure && (self.max_waiting + 1) <= self.waiting_messages.len()
     }
 
     ///
@@ -300,6 +506,13 @@ impl<TMessage> InputStreamCore<TMessage> {
         self.blocked > 0
     }
 
+    ///
+    /// Retrieves the counters that track the messages passing through this stream
+    ///
+    pub (crate) fn counters(&self) -> Arc<StreamCounters> {
+        Arc::clone(&self.counters)
+    }
+
     ///
     /// True if this input stream is idle (has no waiting messages and is being waiting upon)
     ///
@@ -341,10 +554,13 @@ impl<TMessage> Stream for InputStream<TMessage> {
 
         let mut core = self.core.lock().unwrap();
 
-        if let Some((source, message)) = core.waiting_messages.pop_front() {
+        if let Some((source, _, message)) = core.waiting_messages.pop_front() {
             // If any of the output sinks are waiting to write a value, wake them up as the queue has reduced
             let next_available = core.when_slots_available.pop_front();
 
+            // Count the message as delivered
+            core.counters.message_delivered();
+
             // The core is no longer idle
             core.idle = false;
 
@@ -391,10 +607,13 @@ impl<TMessage> Stream for InputStreamWithSources<TMessage> {
 
         let mut core = self.core.lock().unwrap();
 
-        if let Some((source, message)) = core.waiting_messages.pop_front() {
+        if let Some((source, _, message)) = core.waiting_messages.pop_front() {
             // If any of the output sinks are waiting to write a value, wake them up as the queue has reduced
             let next_available = core.when_slots_available.pop_front();
 
+            // Count the message as delivered
+            core.counters.message_delivered();
+
             // The core is no longer idle
             core.idle = false;
 
@@ -447,12 +666,14 @@ impl<TMessage> Drop for InputStream<TMessage> {
 
 impl<TMessage> Drop for InputStreamWithSources<TMessage> {
     fn drop(&mut self) {
-        let mut core = self.core.lock().unwrap();
+        if self.active {
+            let mut core = self.core.lock().unwrap();
 
-        // Core becomes idle if the input stream is dropped (it will never process any messages again)
-        core.idle   = true;
+            // Core becomes idle if the input stream is dropped (it will never process any messages again)
+            core.idle   = true;
 
-        // Stream is closed at this point, shouldn't handle any more messages
-        core.closed = true;
+            // Stream is closed at this point, shouldn't handle any more messages
+            core.closed = true;
+        }
     }
 }
@@ -184,14 +184,18 @@ mod stream_source;
 mod stream_target;
 mod input_stream;
 mod output_sink;
+mod stream_stats;
 mod filter;
 mod scene_message;
 mod thread_stealer;
 mod command_trait;
+mod supervision;
+mod retry;
 
 pub mod error;
 pub mod programs;
 pub mod commands;
+pub mod filters;
 
 pub use scene::*;
 pub use scene_context::*;
@@ -201,13 +205,18 @@ pub use stream_source::*;
 pub use stream_target::*;
 pub use input_stream::*;
 pub use output_sink::*;
+pub use stream_stats::{StreamStats};
 pub use filter::*;
 pub use scene_message::*;
 pub use command_trait::*;
-pub use error::{ConnectionError, SceneSendError};
+pub use supervision::{RestartPolicy};
+pub use retry::{RetryPolicy};
+pub use error::{ConnectionError, SceneSendError, SubProgramIdParseError};
 
 #[cfg(feature = "serde_support")]
 mod serialization;
 
 #[cfg(feature = "serde_support")]
 pub use serialization::*;
+#[cfg(feature = "serde_support")]
+pub use error::{SerializationError};
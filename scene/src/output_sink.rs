@@ -1,13 +1,17 @@
 use crate::error::*;
 use crate::input_stream::*;
 use crate::scene_core::*;
+use crate::stream_target::*;
 use crate::subprogram_id::*;
 
 use futures::prelude::*;
+use futures::future::{Either};
 use futures::task::{Poll, Waker};
+use futures_timer::{Delay};
 
 use std::pin::*;
 use std::sync::*;
+use std::time::{Duration};
 
 // TODO: close the sink when the target program finishes
 
@@ -26,8 +30,23 @@ pub (crate) enum OutputSinkTarget<TMessage> {
 
     /// Same as 'Input', except the stream is closed when this output sink target is dropped
     CloseWhenDropped(Weak<Mutex<InputStreamCore<TMessage>>>),
+
+    /// Indicates an output that sends a copy of its data to the inputs of several subprograms (the function is used to copy the messages)
+    ///
+    /// Each target receives a message as soon as it has space for it, but the next message will not be sent until every target has
+    /// accepted the current one. A target that stops reading its input will eventually block the others. The last list contains
+    /// the inputs that are closed when this output sink target is dropped (the inputs for any filters applied to the targets).
+    FanOut(Vec<Weak<Mutex<InputStreamCore<TMessage>>>>, fn(&TMessage) -> TMessage, Vec<Weak<Mutex<InputStreamCore<TMessage>>>>),
+
+    /// Indicates an output whose target program is not in the scene: messages are forwarded to the scene's dead-letter program instead
+    ///
+    /// The function is used to forward the messages (it's `SceneCore::send_dead_letter()` for the message type)
+    DeadLetter(SubProgramId, DeadLetterFn<TMessage>),
 }
 
+/// Function that forwards a message to the dead-letter program for a scene, returning the message if it can't be forwarded
+pub (crate) type DeadLetterFn<TMessage> = fn(&Arc<Mutex<SceneCore>>, SubProgramId, StreamTarget, TMessage) -> Result<(), TMessage>;
+
 ///
 /// The shared core of an output sink
 ///
@@ -55,6 +74,9 @@ pub struct OutputSink<TMessage> {
     /// The message that is being sent
     waiting_message: Option<TMessage>,
 
+    /// The copies of a message sent to a fan-out target that are waiting for space in their target inputs
+    waiting_fan_out: Vec<(Weak<Mutex<InputStreamCore<TMessage>>>, TMessage)>,
+
     /// True if the message was sent by waking the target (we'll return Poll::Pending to yield to the target)
     yield_after_sending: bool,
 
@@ -68,16 +90,17 @@ impl<TMessage> Clone for OutputSinkTarget<TMessage> {
         use OutputSinkTarget::*;
 
         match self {
-            Disconnected                => Disconnected,
-            Discard                     => Discard,
-            Input(input)                => Input(Weak::clone(input)),
-            CloseWhenDropped(input)     => Input(Weak::clone(input)),           // Only the original output sink target will close when dropped
+            Disconnected                    => Disconnected,
+            Discard                         => Discard,
+            Input(input)                    => Input(Weak::clone(input)),
+            CloseWhenDropped(input)         => Input(Weak::clone(input)),               // Only the original output sink target will close when dropped
+            FanOut(inputs, clone_msg, _)    => FanOut(inputs.clone(), *clone_msg, vec![]),
+            DeadLetter(target, send)        => DeadLetter(*target, *send),
         }
     }
 }
 
 impl<TMessage> Drop for OutputSinkTarget<TMessage> {
-    #[allow(clippy::single_match)]      // May be more cases in the future, current singleton is not inherent
     fn drop(&mut self) {
         match self {
             OutputSinkTarget::CloseWhenDropped(core) => {
@@ -90,6 +113,16 @@ impl<TMessage> Drop for OutputSinkTarget<TMessage> {
                 }
             }
 
+            OutputSinkTarget::FanOut(_, _, close_when_dropped) => {
+                for core in close_when_dropped.iter().flat_map(|core| core.upgrade()) {
+                    let waker = core.lock().unwrap().close();
+
+                    if let Some(waker) = waker {
+                        waker.wake();
+                    }
+                }
+            }
+
             _ => { }
         }
     }
@@ -107,16 +140,19 @@ impl<TMessage> OutputSinkCore<TMessage> {
     }
 
     ///
-    /// Returns the ID of the target of this core
+    /// Returns the IDs of the targets of this core (there can be several targets if this is a fan-out core)
     ///
-    pub fn target_program_id(core: &Arc<Mutex<Self>>) -> Option<SubProgramId> {
-        let input_core = match &core.lock().unwrap().target {
-            OutputSinkTarget::Disconnected      | OutputSinkTarget::Discard                         => None,
-            OutputSinkTarget::Input(input_core) | OutputSinkTarget::CloseWhenDropped(input_core)    => input_core.upgrade(),
-        }?;
+    pub fn target_program_ids(core: &Arc<Mutex<Self>>) -> Vec<SubProgramId> {
+        let input_cores = match &core.lock().unwrap().target {
+            OutputSinkTarget::Disconnected      | OutputSinkTarget::Discard                         => vec![],
+            OutputSinkTarget::DeadLetter(_, _)                                                      => vec![],
+            OutputSinkTarget::Input(input_core) | OutputSinkTarget::CloseWhenDropped(input_core)    => input_core.upgrade().into_iter().collect(),
+            OutputSinkTarget::FanOut(input_cores, _, _)                                             => input_cores.iter().flat_map(|core| core.upgrade()).collect(),
+        };
 
-        let program_id = input_core.lock().unwrap().target_program_id();
-        Some(program_id)
+        input_cores.into_iter()
+            .map(|input_core| input_core.lock().unwrap().target_program_id())
+            .collect()
     }
 }
 
@@ -130,6 +166,7 @@ impl<TMessage> OutputSink<TMessage> {
             core:                   core,
             scene_core:             Arc::downgrade(scene_core),
             waiting_message:        None,
+            waiting_fan_out:        vec![],
             yield_after_sending:    false,
             when_message_sent:      None,
         }
@@ -157,8 +194,16 @@ impl<TMessage> OutputSink<TMessage> {
         let maybe_input_core = match &self.core.lock().unwrap().target {
             OutputSinkTarget::Discard                   => { return true; },
             OutputSinkTarget::Disconnected              => { return true; },
+            OutputSinkTarget::DeadLetter(_, _)          => { return false; },
             OutputSinkTarget::Input(input)              |
-            OutputSinkTarget::CloseWhenDropped(input)   => input.upgrade()
+            OutputSinkTarget::CloseWhenDropped(input)   => input.upgrade(),
+
+            OutputSinkTarget::FanOut(inputs, _, _)      => {
+                // A fan-out target is attached if any of its targets are attached
+                return inputs.iter()
+                    .flat_map(|input| input.upgrade())
+                    .any(|input| !input.lock().unwrap().is_closed());
+            }
         };
 
         if let Some(input_core) = maybe_input_core {
@@ -172,6 +217,43 @@ impl<TMessage> OutputSink<TMessage> {
         }
     }
 
+    ///
+    /// Sends a message, returning `SceneSendError::Timeout` if the target has not accepted it within the specified time
+    ///
+    /// This is the same as calling `send()` except that a target that is stuck or permanently busy will eventually produce
+    /// an error instead of blocking the sender forever. When the send times out, the message is taken back from this sink
+    /// and returned in the error, so the target will not receive it later on.
+    ///
+    /// For a fan-out connection, the message is delivered to each target as soon as it has space, so a timeout can happen after
+    /// some of the targets have already received the message. Those targets keep their copy: the error only indicates that at
+    /// least one target did not receive it.
+    ///
+    pub async fn send_timeout(&mut self, message: TMessage, timeout: Duration) -> Result<(), SceneSendError<TMessage>>
+    where
+        TMessage: Unpin,
+    {
+        {
+            // Race sending the message against the timeout
+            let send_message    = self.send(message);
+            let timeout         = Delay::new(timeout);
+
+            if let Either::Left((send_result, _)) = future::select(send_message, timeout).await {
+                return send_result;
+            }
+        }
+
+        // The message will still be waiting in this sink if it was not accepted by the target (for fan-out targets, any target that hasn't accepted the message won't receive it)
+        let waiting_fan_out = self.waiting_fan_out.drain(..).map(|(_, message)| message).next();
+
+        match self.waiting_message.take().or(waiting_fan_out) {
+            Some(message)   => {
+                self.record_dropped_message();
+                Err(SceneSendError::Timeout(message))
+            }
+            None            => Ok(()),
+        }
+    }
+
     ///
     /// Sends a message in immediate mode
     ///
@@ -196,23 +278,62 @@ impl<TMessage> OutputSink<TMessage> {
                 let source = self.program_id;
                 let target = self.core.lock().unwrap().target.clone();
 
-                match &target {
+                let send_result = match &target {
                     OutputSinkTarget::Discard                   => Ok(()),
                     OutputSinkTarget::Disconnected              => Err(SceneSendError::StreamDisconnected(message)),
+                    OutputSinkTarget::DeadLetter(target, send)  => self.send_dead_letter(*target, *send, message).map_err(SceneSendError::StreamDisconnected),
                     OutputSinkTarget::Input(input)              |
                     OutputSinkTarget::CloseWhenDropped(input)   => {
                         if let Some(input) = input.upgrade() {
-                            let waker = input.lock().unwrap().send_with_overfill(source, message)?;
-                            if let Some(waker) = waker {
-                                waker.wake();
+                            // Fails if the target input stream has been closed
+                            let waker = input.lock().unwrap().send_with_overfill(source, message);
+
+                            waker.map(|waker| if let Some(waker) = waker { waker.wake(); })
+                        } else {
+                            Err(SceneSendError::StreamDisconnected(message))
+                        }
+                    }
+
+                    OutputSinkTarget::FanOut(inputs, clone_message, _) => {
+                        // Overfill any of the targets that are full
+                        let inputs = inputs.iter().flat_map(|input| input.upgrade()).collect::<Vec<_>>();
+                        if inputs.is_empty() { 
+                            self.record_dropped_message();
+                            return Err(SceneSendError::StreamDisconnected(message));
+                        }
+
+                        // Targets that have closed their input are skipped, so the message is only returned if no target could receive it
+                        let mut delivered = false;
+
+                        for input in inputs {
+                            let mut input   = input.lock().unwrap();
+                            let waker       = match input.send(source, clone_message(&message)) {
+                                Ok(waker)       => Ok(waker),
+                                Err(message)    => input.send_with_overfill(source, message),
+                            };
+
+                            if let Ok(waker) = waker {
+                                delivered = true;
+
+                                if let Some(waker) = waker {
+                                    waker.wake();
+                                }
                             }
+                        }
 
+                        if delivered {
                             Ok(())
                         } else {
                             Err(SceneSendError::StreamDisconnected(message))
                         }
                     }
+                };
+
+                if let Err(SceneSendError::StreamDisconnected(_)) = &send_result {
+                    self.record_dropped_message();
                 }
+
+                send_result
             } else {
                 // Sent on the second attempt
                 Ok(())
@@ -240,7 +361,16 @@ impl<TMessage> OutputSink<TMessage> {
             OutputSinkTarget::Discard                   => { return Ok(()); },
             OutputSinkTarget::Disconnected              => None,
             OutputSinkTarget::Input(input)              |
-            OutputSinkTarget::CloseWhenDropped(input)   => input.upgrade()
+            OutputSinkTarget::CloseWhenDropped(input)   => input.upgrade(),
+
+            OutputSinkTarget::FanOut(inputs, clone_message, _) => {
+                return Self::try_send_fan_out_immediate(program_id, inputs, *clone_message, message);
+            }
+
+            OutputSinkTarget::DeadLetter(target, send) => {
+                let (target, send) = (*target, *send);
+                return self.send_dead_letter(target, send, message);
+            }
         };
 
         // We're disconnected if the core is 'None'
@@ -266,6 +396,30 @@ impl<TMessage> OutputSink<TMessage> {
         }
     }
 
+    ///
+    /// Sends a message to all of the targets of a fan-out target, provided that none of them are full
+    ///
+    fn try_send_fan_out_immediate(program_id: SubProgramId, inputs: &[Weak<Mutex<InputStreamCore<TMessage>>>], clone_message: fn(&TMessage) -> TMessage, message: TMessage) -> Result<(), TMessage> {
+        let inputs = inputs.iter()
+            .flat_map(|input| input.upgrade())
+            .filter(|input| !input.lock().unwrap().is_closed())
+            .collect::<Vec<_>>();
+
+        // The message is only sent if every target that is still running has space for it
+        if inputs.is_empty() || inputs.iter().any(|input| input.lock().unwrap().is_queue_full()) {
+            return Err(message);
+        }
+
+        for input in inputs {
+            let waker = input.lock().unwrap().send(program_id, clone_message(&message)).ok().flatten();
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+
+        Ok(())
+    }
+
     ///
     /// If the target stream allows thread stealing, steal the current thread until the input buffer is empty
     ///
@@ -285,6 +439,8 @@ impl<TMessage> OutputSink<TMessage> {
         let maybe_input_core = match &self.core.lock().unwrap().target {
             OutputSinkTarget::Discard                   => None,
             OutputSinkTarget::Disconnected              => None,
+            OutputSinkTarget::FanOut(_, _, _)           => None,                // Fan-out targets don't support thread stealing
+            OutputSinkTarget::DeadLetter(_, _)          => None,
             OutputSinkTarget::Input(input)              |
             OutputSinkTarget::CloseWhenDropped(input)   => {
                 input.upgrade()
@@ -312,6 +468,72 @@ impl<TMessage> OutputSink<TMessage> {
     }
 }
 
+impl<TMessage> OutputSink<TMessage> {
+    ///
+    /// Records that a message sent to this sink could not be delivered
+    ///
+    fn record_dropped_message(&self) {
+        if let Some(scene_core) = self.scene_core.upgrade() {
+            scene_core.lock().unwrap().message_dropped();
+        }
+    }
+
+    ///
+    /// Forwards a message for a target that is not in the scene to the dead-letter program, returning the message if it could not be forwarded
+    ///
+    fn send_dead_letter(&self, target: SubProgramId, send: DeadLetterFn<TMessage>, message: TMessage) -> Result<(), TMessage> {
+        if let Some(scene_core) = self.scene_core.upgrade() {
+            send(&scene_core, self.program_id, StreamTarget::Program(target), message)
+        } else {
+            Err(message)
+        }
+    }
+
+    ///
+    /// Tries to send the message copies that are waiting for space in the targets of a fan-out output
+    ///
+    fn poll_flush_fan_out(&mut self, context: &mut std::task::Context<'_>) -> Poll<Result<(), SceneSendError<TMessage>>> {
+        use std::mem;
+
+        let program_id  = self.program_id;
+        let waiting     = mem::take(&mut self.waiting_fan_out);
+        let mut wakers  = vec![];
+
+        for (input_core, message) in waiting {
+            // Targets that have finished no longer need the message
+            if let Some(input_core_arc) = input_core.upgrade() {
+                let mut locked_core = input_core_arc.lock().unwrap();
+                if locked_core.is_closed() { continue; }
+
+                match locked_core.send(program_id, message) {
+                    Ok(waker)       => wakers.extend(waker),
+                    Err(message)    => {
+                        // Still waiting for this target to have space
+                        locked_core.wake_when_slots_available(context);
+                        mem::drop(locked_core);
+
+                        self.waiting_fan_out.push((input_core, message));
+                    }
+                }
+            }
+        }
+
+        // Wake up the targets that received the message
+        wakers.into_iter().for_each(|waker| waker.wake());
+
+        if self.waiting_fan_out.is_empty() {
+            // Every target has received the message
+            if let Some(when_message_sent) = self.when_message_sent.take() { 
+                when_message_sent.wake();
+            }
+
+            Poll::Ready(Ok(()))
+        } else {
+            Poll::Pending
+        }
+    }
+}
+
 impl<TMessage> Sink<TMessage> for OutputSink<TMessage> 
 where
     TMessage: Unpin,
@@ -319,8 +541,10 @@ where
     type Error = SceneSendError<TMessage>;
 
     fn poll_ready(mut self: Pin<&mut Self>, context: &mut std::task::Context<'_>) -> Poll<Result<(), Self::Error>> {
+        use std::mem;
+
         // Say we're waiting if there's an input value waiting
-        if self.waiting_message.is_some() {
+        if self.waiting_message.is_some() || !self.waiting_fan_out.is_empty() {
             // Wait for the message to finish sending
             self.when_message_sent = Some(context.waker().clone());
             Poll::Pending
@@ -333,21 +557,37 @@ where
                     core.when_target_changed = Some(context.waker().clone());
                     Poll::Pending
                 },
-                OutputSinkTarget::Discard => Poll::Ready(Ok(())),
+                OutputSinkTarget::Discard           => Poll::Ready(Ok(())),
+                OutputSinkTarget::DeadLetter(_, _)  => Poll::Ready(Ok(())),
 
                 OutputSinkTarget::Input(input_core)               |
                 OutputSinkTarget::CloseWhenDropped(input_core)    => {
                     if input_core.upgrade().is_none() {
                         // Downgrade to a disconnected core so the sending can be retried
                         core.target = OutputSinkTarget::Disconnected;
+                        mem::drop(core);
 
                         // Error if the target program is not running any more
+                        self.record_dropped_message();
                         Poll::Ready(Err(SceneSendError::TargetProgramEndedBeforeReady))
                     } else {
                         // Can send the message
                         Poll::Ready(Ok(()))
                     }
                 }
+
+                OutputSinkTarget::FanOut(input_cores, _, _) => {
+                    if input_cores.iter().all(|input_core| input_core.upgrade().is_none()) {
+                        // Every target has finished, so downgrade to a disconnected core
+                        core.target = OutputSinkTarget::Disconnected;
+                        mem::drop(core);
+
+                        self.record_dropped_message();
+                        Poll::Ready(Err(SceneSendError::TargetProgramEndedBeforeReady))
+                    } else {
+                        Poll::Ready(Ok(()))
+                    }
+                }
             }
         }
     }
@@ -372,6 +612,20 @@ where
                 Ok(())
             },
 
+            OutputSinkTarget::DeadLetter(target, send)      => {
+                let (target, send) = (*target, *send);
+                mem::drop(core);
+
+                // The message is sent to the dead-letter program instead of the target
+                self.waiting_message = None;
+                if let Some(when_message_sent) = self.when_message_sent.take() { when_message_sent.wake(); }
+
+                self.send_dead_letter(target, send, item).map_err(|item| {
+                    self.record_dropped_message();
+                    SceneSendError::TargetProgramEnded(item)
+                })
+            },
+
             OutputSinkTarget::Input(input_core)             |
             OutputSinkTarget::CloseWhenDropped(input_core)  => {
                 if let Some(input_core) = input_core.upgrade() {
@@ -426,11 +680,64 @@ where
                 } else {
                     // Downgrade to a disconnected core so the sending can be retried
                     core.target = OutputSinkTarget::Disconnected;
+                    mem::drop(core);
 
                     // Target program is not available
+                    self.record_dropped_message();
                     Err(SceneSendError::TargetProgramEnded(item))
                 }
             }
+
+            OutputSinkTarget::FanOut(input_cores, clone_message, _) => {
+                let input_cores     = input_cores.iter().flat_map(|input_core| input_core.upgrade()).collect::<Vec<_>>();
+                let clone_message   = *clone_message;
+
+                if input_cores.is_empty() {
+                    // Downgrade to a disconnected core so the sending can be retried
+                    core.target = OutputSinkTarget::Disconnected;
+                    mem::drop(core);
+
+                    // None of the target programs are available
+                    self.record_dropped_message();
+                    return Err(SceneSendError::TargetProgramEnded(item));
+                }
+
+                mem::drop(core);
+
+                // Send a copy of the message to every target, keeping the copies for any target that's full
+                let program_id      = self.program_id;
+                let mut wakers      = vec![];
+                let mut waiting     = vec![];
+                let mut queue_full  = false;
+
+                for input_core in input_cores {
+                    let mut locked_core = input_core.lock().unwrap();
+
+                    // Closed targets are skipped (they'll never accept the message)
+                    if locked_core.is_closed() { continue; }
+
+                    match locked_core.send(program_id, clone_message(&item)) {
+                        Ok(waker) => {
+                            queue_full = queue_full || locked_core.is_queue_full() || locked_core.is_blocked();
+                            wakers.extend(waker);
+                        }
+
+                        Err(message) => {
+                            mem::drop(locked_core);
+                            waiting.push((Arc::downgrade(&input_core), message));
+                        }
+                    }
+                }
+
+                self.waiting_message        = None;
+                self.waiting_fan_out        = waiting;
+                self.yield_after_sending    = !wakers.is_empty() && queue_full;
+
+                // Wake up the targets that received the message
+                wakers.into_iter().for_each(|waker| waker.wake());
+
+                Ok(())
+            }
         }
     }
 
@@ -449,6 +756,11 @@ where
             return Poll::Pending;
         }
 
+        // Finish sending any message copies to a fan-out target
+        if !self.waiting_fan_out.is_empty() {
+            return self.poll_flush_fan_out(context);
+        }
+
         // If there's no waiting message, then it has been sent and there's no work to do
         if self.waiting_message.is_none() {
             return Poll::Ready(Ok(()));
@@ -517,9 +829,23 @@ where
 
                     // When the core is released during a send, the target program has terminated, so we generate an error
                     core.when_target_changed    = Some(context.waker().clone());
+                    mem::drop(core);
+
+                    self.record_dropped_message();
                     Poll::Ready(Err(SceneSendError::TargetProgramEndedBeforeReady))
                 }
             }
+
+            OutputSinkTarget::FanOut(_, _, _) | OutputSinkTarget::DeadLetter(_, _) => {
+                // The target was changed while the message was waiting: send it as a new message
+                mem::drop(core);
+
+                if let Some(message) = self.waiting_message.take() {
+                    self.as_mut().start_send(message)?;
+                }
+
+                self.poll_flush(context)
+            }
         }
     }
 
@@ -552,6 +878,7 @@ mod test {
                 core:                   Arc::new(Mutex::new(core)),
                 scene_core:             Arc::downgrade(scene_core),
                 waiting_message:        None,
+                waiting_fan_out:        vec![],
                 yield_after_sending:    false,
                 when_message_sent:      None,
             }
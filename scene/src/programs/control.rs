@@ -15,6 +15,7 @@ use super::subscription::*;
 use super::query::*;
 
 use futures::prelude::*;
+use futures::future;
 use futures::future::{poll_fn};
 use futures::channel::oneshot;
 use futures::stream;
@@ -116,6 +117,9 @@ pub enum SceneUpdate {
     /// A requested connection failed to be made for some reason
     FailedConnection(ConnectionError, StreamSource, StreamTarget, StreamId),
 
+    /// A subprogram panicked (the string is the panic message). The program is stopped, but the rest of the scene keeps running.
+    Panicked(SubProgramId, String),
+
     /// A subprogram has finished running
     Stopped(SubProgramId),
 }
@@ -243,13 +247,17 @@ impl SceneControl {
         enum ControlInput {
             Control(SceneControl),
             Update(SceneUpdate),
+            InputClosed,
         }
 
-        let input   = input.map(|input| ControlInput::Control(input));
+        let input   = input.map(|input| ControlInput::Control(input)).chain(stream::once(future::ready(ControlInput::InputClosed)));
         let updates = updates.map(|update| ControlInput::Update(update));
 
-        // The program runs until the input is exhausted
-        let mut input = stream::select(input, updates);
+        // The program runs until the input is exhausted (the update stream is never closed)
+        let own_program_id      = context.current_program_id();
+        let mut input           = stream::select(input, updates);
+        let mut input_closed    = false;
+
         while let Some(request) = input.next().await {
             use SceneControl::*;
             use ControlInput::*;
@@ -386,10 +394,25 @@ impl SceneControl {
                         SceneUpdate::Stopped(program_id)                    => { started_subprograms.remove(program_id); },
 
                         SceneUpdate::FailedConnection(_, _, _, _)           => { },
+                        SceneUpdate::Panicked(_, _)                         => { },
                     }
 
                     // Send the update to the subscribers
                     update_subscribers.send(update).await;
+
+                    // Stop once the input is closed and all of the other programs have stopped
+                    if input_closed && started_subprograms.iter().all(|program_id| Some(*program_id) == own_program_id) {
+                        break;
+                    }
+                }
+
+                InputClosed => {
+                    // Programs send an update when they stop, which will block if this program is not running to receive it, so wait for the other programs to stop before finishing
+                    input_closed = true;
+
+                    if started_subprograms.iter().all(|program_id| Some(*program_id) == own_program_id) {
+                        break;
+                    }
                 }
             }
         }
@@ -64,7 +64,8 @@ impl SceneMessage for IdleNotification {
 ///
 enum IdleProgramMsg {
     Request(SubProgramId, IdleRequest),
-    CoreIsIdle
+    CoreIsIdle,
+    InputClosed,
 }
 
 ///
@@ -84,8 +85,10 @@ pub (crate) async fn idle_subprogram(input_stream: InputStream<IdleRequest>, con
         SceneCore::send_idle_notifications_to(&core, send_idle);
     }
 
-    // Merge the notifications (idle notifications and requests)
-    let mut input_stream = stream::select(input_stream.map(|(subprogram_id, msg)| IdleProgramMsg::Request(subprogram_id, msg)), recv_idle.map(|_| IdleProgramMsg::CoreIsIdle));
+    // Merge the notifications (idle notifications and requests). The program stops once the input stream is closed.
+    let input_stream        = input_stream.map(|(subprogram_id, msg)| IdleProgramMsg::Request(subprogram_id, msg))
+        .chain(stream::once(future::ready(IdleProgramMsg::InputClosed)));
+    let mut input_stream    = stream::select(input_stream, recv_idle.map(|_| IdleProgramMsg::CoreIsIdle));
 
     while let Some(request) = input_stream.next().await {
         use IdleProgramMsg::*;
@@ -135,6 +138,10 @@ pub (crate) async fn idle_subprogram(input_stream: InputStream<IdleRequest>, con
                         .await;
                 }
             }
+
+            InputClosed => {
+                break;
+            }
         }
     }
 }
\ No newline at end of file
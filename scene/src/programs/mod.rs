@@ -7,6 +7,7 @@ mod timer;
 mod test;
 mod subscription;
 mod query;
+mod recording;
 
 pub use control::*;
 pub use outside::*;
@@ -17,3 +18,4 @@ pub use timer::*;
 pub use test::*;
 pub use subscription::*;
 pub use query::*;
+pub use recording::*;
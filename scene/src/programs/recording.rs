@@ -0,0 +1,61 @@
+use crate::*;
+
+use futures::prelude::*;
+use futures::future::{BoxFuture};
+
+use std::sync::*;
+
+///
+/// The buffer where a recording program stores its messages
+///
+type RecordedMessages<TMessage> = Arc<Mutex<Vec<TMessage>>>;
+
+///
+/// A subprogram that stores every message that it receives, so they can be inspected later on
+///
+/// This is mainly useful for tests: instead of writing a subprogram that checks the messages it receives, a test can connect
+/// a recording program to the stream it's interested in, run the scene until it's idle, and then check the messages that were
+/// recorded in the shared buffer.
+///
+/// ```
+/// # use flo_scene::*;
+/// # use flo_scene::programs::*;
+/// let scene                   = Scene::default();
+/// let (recorder, messages)    = RecordingProgram::<usize>::new();
+///
+/// scene.add_subprogram(SubProgramId::new(), recorder, 20);
+/// # let _ = messages;
+/// ```
+///
+pub struct RecordingProgram<TMessage> {
+    /// The messages that have been received by this program
+    recorded: RecordedMessages<TMessage>,
+}
+
+impl<TMessage> RecordingProgram<TMessage>
+where
+    TMessage: 'static + SceneMessage,
+{
+    ///
+    /// Creates a recording program, returning the function to pass to `add_subprogram()` and the buffer that the messages it receives
+    /// are stored in
+    ///
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new() -> (impl 'static + Send + FnOnce(InputStream<TMessage>, SceneContext) -> BoxFuture<'static, ()>, RecordedMessages<TMessage>) {
+        let program     = RecordingProgram { recorded: Arc::new(Mutex::new(vec![])) };
+        let recorded    = Arc::clone(&program.recorded);
+
+        (move |input, context| program.run(input, context).boxed(), recorded)
+    }
+
+    ///
+    /// Runs the recording program, adding the messages from the input stream to the buffer until the stream is closed
+    ///
+    async fn run(self, input: InputStream<TMessage>, _context: SceneContext) {
+        let mut input = input;
+
+        while let Some(message) = input.next().await {
+            self.recorded.lock().unwrap().push(message);
+        }
+    }
+}
@@ -3,6 +3,7 @@ use crate::output_sink::*;
 use crate::scene_context::*;
 use crate::scene_message::*;
 use crate::stream_target::*;
+use crate::subprogram_id::*;
 
 use futures::prelude::*;
 
@@ -43,6 +44,33 @@ impl<TMessageType: SceneMessage> Subscribe<TMessageType> {
     }
 }
 
+///
+/// Sub-programs that support the `Subscribe` message can also support this message, which is a request to stop sending
+/// events to a subprogram that previously subscribed to them.
+///
+#[derive(Clone)]
+pub struct Unsubscribe<TMessageType: SceneMessage>(SubProgramId, PhantomData<TMessageType>);
+
+impl<TMessageType: SceneMessage> SceneMessage for Unsubscribe<TMessageType> { }
+
+impl<TMessageType: SceneMessage> Unsubscribe<TMessageType> { 
+    ///
+    /// Creates an 'unsubscribe' message that will stop sending events to the specified program
+    ///
+    #[inline]
+    pub fn with_program(program: SubProgramId) -> Self {
+        Unsubscribe(program, PhantomData)
+    }
+
+    ///
+    /// Retrieves the program that should stop receiving events
+    ///
+    #[inline]
+    pub fn program(&self) -> SubProgramId {
+        self.0
+    }
+}
+
 ///
 /// Creates a 'Subscribe' message that will return a particular type
 ///
@@ -51,6 +79,47 @@ pub fn subscribe<TMessageType: SceneMessage>(target: impl Into<StreamTarget>) ->
     Subscribe::with_target(target.into())
 }
 
+///
+/// Creates an 'Unsubscribe' message for a particular type
+///
+#[inline]
+pub fn unsubscribe<TMessageType: SceneMessage>(program: SubProgramId) -> Unsubscribe<TMessageType> {
+    Unsubscribe::with_program(program)
+}
+
+///
+/// A function that decides whether or not an event should be sent to a subscriber
+///
+type EventFilter<TEventMessage> = Box<dyn Send + Fn(&TEventMessage) -> bool>;
+
+///
+/// A receiver of the events sent by an `EventSubscribers` object
+///
+struct EventReceiver<TEventMessage> {
+    /// The subprogram that subscribed to the events (None if the receiver was added with `add_target()`)
+    program: Option<SubProgramId>,
+
+    /// Where the events should be sent
+    sink: OutputSink<TEventMessage>,
+
+    /// If set, only the events that match this filter are sent to this receiver
+    filter: Option<EventFilter<TEventMessage>>,
+}
+
+impl<TEventMessage> EventReceiver<TEventMessage> {
+    ///
+    /// True if this receiver wants to receive the specified message
+    ///
+    #[inline]
+    fn accepts(&self, message: &TEventMessage) -> bool {
+        if let Some(filter) = &self.filter {
+            (filter)(message)
+        } else {
+            true
+        }
+    }
+}
+
 ///
 /// Stores the subscribers for an event stream, and forwards events as needed
 ///
@@ -59,7 +128,7 @@ where
     TEventMessage: SceneMessage,
 {
     /// The output sinks that will receive the events from this subprogram
-    receivers: Vec<OutputSink<TEventMessage>>,
+    receivers: Vec<EventReceiver<TEventMessage>>,
 
     /// The next receiver to use when sending a round-robin message
     next_receiver: usize,
@@ -83,16 +152,38 @@ where
     /// Subscribes a subprogram to the events sent by this object
     ///
     pub fn subscribe(&mut self, context: &SceneContext, target: impl Into<StreamTarget>) {
-        let target = target.into();
+        self.add_subscriber(context, target.into(), None);
+    }
 
+    ///
+    /// Subscribes a subprogram to only the events sent by this object that match a filter function
+    ///
+    /// Events that the filter rejects are skipped for this subscriber by both `send()` and `send_round_robin()`.
+    ///
+    pub fn subscribe_filtered(&mut self, context: &SceneContext, target: impl Into<StreamTarget>, filter: impl 'static + Send + Fn(&TEventMessage) -> bool) {
+        self.add_subscriber(context, target.into(), Some(Box::new(filter)));
+    }
+
+    ///
+    /// Adds a new subscriber with an optional filter
+    ///
+    fn add_subscriber(&mut self, context: &SceneContext, target: StreamTarget, filter: Option<EventFilter<TEventMessage>>) {
         // Remove any subscriber that's no longer attached to a target
-        self.receivers.retain(|sink| sink.is_attached());
+        self.receivers.retain(|receiver| receiver.sink.is_attached());
 
         // If we can successfully connect to the target, then send events there
+        let program     = target.target_sub_program();
         let output_sink = context.send(target);
         let output_sink = if let Ok(output_sink) = output_sink { output_sink } else { return; };
 
-        self.receivers.push(output_sink);
+        self.receivers.push(EventReceiver { program, sink: output_sink, filter });
+    }
+
+    ///
+    /// Stops sending events to a subprogram that previously subscribed to them
+    ///
+    pub fn unsubscribe(&mut self, program: SubProgramId) {
+        self.receivers.retain(|receiver| receiver.program != Some(program));
     }
 
     ///
@@ -101,16 +192,26 @@ where
     /// This sink cannot be unsubscribed from the events, but this can be used to send to other streams where the target is not identified by a subprogram ID
     ///
     pub fn add_target(&mut self, output_sink: OutputSink<TEventMessage>) {
-        self.receivers.push(output_sink)
+        self.receivers.push(EventReceiver { program: None, sink: output_sink, filter: None })
+    }
+
+    ///
+    /// Returns the number of subscribers that are still attached to a target
+    ///
+    pub fn subscriber_count(&self) -> usize {
+        self.receivers.iter()
+            .filter(|receiver| receiver.sink.is_attached())
+            .count()
     }
 
     ///
     /// Sends a message to a single subscriber, returning Ok(()) if the message is delivered, otherwise returning an error that preserves the original message
     ///
-    /// Subscribers are sent to in a round-robin fashion
+    /// Subscribers are sent to in a round-robin fashion. Subscribers whose filter rejects the message are skipped.
     ///
     pub async fn send_round_robin(&mut self, message: TEventMessage) -> Result<(), TEventMessage> {
         let mut message = message;
+        let mut skipped = 0;
 
         loop {
             // If there are no receivers, then there's onothing to send a message to
@@ -118,14 +219,25 @@ where
                 break Err(message);
             }
 
+            // If every receiver has rejected the message, then there's nowhere to send it
+            if skipped >= self.receivers.len() {
+                break Err(message);
+            }
+
             // Move on to the next receiver
             self.next_receiver += 1;
             if self.next_receiver >= self.receivers.len() {
                 self.next_receiver = 0;
             }
 
+            // Skip receivers that aren't interested in this message
+            if !self.receivers[self.next_receiver].accepts(&message) {
+                skipped += 1;
+                continue;
+            }
+
             // Try to send to this receiver
-            match self.receivers[self.next_receiver].send(message).await {
+            match self.receivers[self.next_receiver].sink.send(message).await {
                 Ok(()) => { break Ok(()); }
 
                 Err(SceneSendError::TargetProgramEndedBeforeReady)  |
@@ -135,7 +247,8 @@ where
                 }
 
                 Err(SceneSendError::TargetProgramEnded(returned_message)) |
-                Err(SceneSendError::StreamDisconnected(returned_message)) => {
+                Err(SceneSendError::StreamDisconnected(returned_message)) |
+                Err(SceneSendError::Timeout(returned_message))            => {
                     // Remove this subscriber as it errored out
                     self.receivers.remove(self.next_receiver);
 
@@ -162,16 +275,18 @@ where
     ///
     /// Sends a message to the subscribers to this object
     ///
-    /// Returns true if the message is sent to at least one subscriber, or false if there are no subscribers
+    /// Returns true if the message is sent to at least one subscriber, or false if there are no subscribers (or if all of the
+    /// subscribers filtered out the message)
     ///
     pub async fn send(&mut self, message: TEventMessage) -> bool {
         // Remove any subscriber that's no longer attached to a target
-        self.receivers.retain(|sink| sink.is_attached());
+        self.receivers.retain(|receiver| receiver.sink.is_attached());
 
         // Send to all of the streams at once
         let senders = self.receivers.iter_mut()
             .enumerate()
-            .map(|(idx, sender)| sender.send(message.clone()).map(move |result| (idx, result)))
+            .filter(|(_, receiver)| receiver.accepts(&message))
+            .map(|(idx, receiver)| receiver.sink.send(message.clone()).map(move |result| (idx, result)))
             .collect::<Vec<_>>();
 
         // Wait for all the messages to send
@@ -1,18 +1,22 @@
 use crate::*;
+use crate::input_stream::*;
 use super::control::*;
+use super::idle_request::*;
 use super::query::*;
 
 use futures::prelude::*;
 use futures::executor;
 use futures::future;
-use futures::future::{BoxFuture};
+use futures::future::{BoxFuture, Either};
 use futures::channel::mpsc;
 use futures_timer::{Delay};
 
 use std::any::*;
 use std::collections::{HashMap};
+use std::sync::{Mutex};
 use std::time::{Duration};
 
+type MessagePredicate<TMessage> = Box<dyn Send + Fn(&TMessage) -> bool>;
 type ActionFn = Box<dyn Send + FnOnce(InputStream<TestRequest>, &SceneContext, mpsc::Sender<String>) -> BoxFuture<'static, (InputStream<TestRequest>, mpsc::Sender<String>)>>;
 
 ///
@@ -92,6 +96,47 @@ impl TestBuilder {
         self
     }
 
+    ///
+    /// Adds a test action that pushes a message directly on to the input stream of a subprogram
+    ///
+    /// This bypasses the connections in the scene, so the target program does not need to be connected to anything to receive
+    /// the message, which makes it possible to test a single subprogram in isolation. The message appears to have come from the
+    /// test program, so a program that replies to the source of its messages will send the replies back to the test program,
+    /// where they can be checked with `expect_message()`.
+    ///
+    pub fn inject_message<TMessage: 'static + SceneMessage>(mut self, target_program: SubProgramId, message: TMessage) -> Self {
+        self.actions.push(Box::new(move |input_stream, context, failed_assertions| {
+            let source_program  = context.current_program_id().unwrap();
+            let scene_core      = context.scene_core();
+
+            async move {
+                let mut failed_assertions = failed_assertions;
+
+                // Find the input stream core for the target program
+                let input_core = scene_core.upgrade()
+                    .and_then(|scene_core| scene_core.lock().unwrap().get_input_stream_core(target_program))
+                    .and_then(|input_core| input_core.downcast::<Mutex<InputStreamCore<TMessage>>>().ok());
+
+                if let Some(input_core) = input_core {
+                    // Add the message to the input stream even if it's full
+                    let waker = input_core.lock().unwrap().send_with_overfill(source_program, message);
+
+                    match waker {
+                        Ok(Some(waker)) => { waker.wake(); }
+                        Ok(None)        => { }
+                        Err(_)          => { failed_assertions.send(format!("Could not inject a message into {}: the input stream is closed", target_program)).await.ok(); }
+                    }
+                } else {
+                    failed_assertions.send(format!("Could not inject a message into {}: the program is not running, or does not accept messages of type {}", target_program, type_name::<TMessage>())).await.ok();
+                }
+
+                (input_stream, failed_assertions)
+            }.boxed()
+        }));
+
+        self
+    }
+
     ///
     /// Runs a `Command` and then evaluates an assertion against the messages that it returns
     ///
@@ -164,25 +209,52 @@ impl TestBuilder {
     /// The test program will configure itself to be able to receive messages of this type
     /// using a filter.
     ///
-    pub fn expect_message_async<TMessage: 'static + Send + SceneMessage, TFuture>(mut self, assertion: impl 'static + Send + FnOnce(TMessage) -> TFuture) -> Self 
+    pub fn expect_message_async<TMessage: 'static + Send + SceneMessage, TFuture>(self, assertion: impl 'static + Send + FnOnce(TMessage) -> TFuture) -> Self 
+    where
+        TFuture: 'static + Send + Future<Output=Result<(), String>>,
+    {
+        self.expect_message_with_timeout(None, assertion)
+    }
+
+    ///
+    /// Expects a message of a particular type to be received by the test program within a certain time
+    ///
+    /// This is the same as `expect_message()`, except that the test fails with a timeout error if the message does not
+    /// arrive within the specified time, instead of waiting for the timeout for the test as a whole. The test carries
+    /// on with the next action after a timeout.
+    ///
+    pub fn expect_message_within<TMessage: 'static + Send + SceneMessage>(self, timeout: impl Into<Duration>, assertion: impl 'static + Send + FnOnce(TMessage) -> Result<(), String>) -> Self {
+        self.expect_message_with_timeout(Some(timeout.into()), move |value| async move { assertion(value) })
+    }
+
+    ///
+    /// Adds an action that expects a message, optionally failing if it does not arrive before a timeout
+    ///
+    fn expect_message_with_timeout<TMessage: 'static + Send + SceneMessage, TFuture>(mut self, timeout: Option<Duration>, assertion: impl 'static + Send + FnOnce(TMessage) -> TFuture) -> Self 
     where
         TFuture: 'static + Send + Future<Output=Result<(), String>>,
     {
         // Create a filter for the message type
-        self.filters.entry(StreamId::with_message_type::<TMessage>())
-            .or_insert_with(|| {
-                FilterHandle::for_filter(|source_stream: InputStream<TMessage>| source_stream.map(|msg| TestRequest::AnyMessage(Box::new(msg))))
-            });
+        self.receive_message_type::<TMessage>();
 
         // Add an action to receive the message from the target
         self.actions.push(Box::new(move |input_stream, _context, failed_assertions| {
             async move {
                 let mut input_stream        = input_stream;
                 let mut failed_assertions   = failed_assertions;
-                let next_message            = input_stream.next().await;
+
+                // next_message is 'None' if the timeout elapses before the message arrives
+                let next_message = if let Some(timeout) = timeout {
+                    match future::select(input_stream.next(), Delay::new(timeout)).await {
+                        Either::Left((next_message, _)) => Some(next_message),
+                        Either::Right(_)                => None,
+                    }
+                } else {
+                    Some(input_stream.next().await)
+                };
 
                 match next_message {
-                    Some(TestRequest::AnyMessage(any_message))  => {
+                    Some(Some(TestRequest::AnyMessage(any_message)))  => {
                         // Check that the message matches
                         if let Ok(message) = any_message.downcast::<TMessage>() {
                             match assertion(*message).await {
@@ -201,10 +273,121 @@ impl TestBuilder {
                         }
                     },
 
-                    None => {
+                    Some(None) => {
                         // The input stream was closed while we were waiting for the message
                         failed_assertions.send("Test finished prematurely".to_string()).await.ok();
                     }
+
+                    None => {
+                        // The message did not arrive in time
+                        failed_assertions.send(format!("Timed out after {:?} waiting for a message of type {}", timeout.unwrap_or_default(), type_name::<TMessage>())).await.ok();
+                    }
+                }
+
+                (input_stream, failed_assertions)
+            }.boxed()
+        }));
+
+        self
+    }
+
+    ///
+    /// Expects a set of messages of a particular type to be received by the test program, in any order
+    ///
+    /// Each message that arrives is matched against the first of the remaining predicates that accepts it, and that predicate is
+    /// then considered satisfied. This passes once every predicate has been satisfied, which is useful when several independent
+    /// programs are generating messages and the order they arrive in is not deterministic. The test fails if a message arrives
+    /// that is not accepted by any of the remaining predicates.
+    ///
+    pub fn expect_messages_unordered<TMessage: 'static + Send + SceneMessage>(mut self, predicates: Vec<MessagePredicate<TMessage>>) -> Self {
+        // Create a filter for the message type
+        self.receive_message_type::<TMessage>();
+
+        // Add an action to receive messages until all of the predicates are satisfied
+        self.actions.push(Box::new(move |input_stream, _context, failed_assertions| {
+            async move {
+                let mut input_stream        = input_stream;
+                let mut failed_assertions   = failed_assertions;
+                let mut remaining           = predicates;
+
+                while !remaining.is_empty() {
+                    match input_stream.next().await {
+                        Some(TestRequest::AnyMessage(any_message)) => {
+                            if let Ok(message) = any_message.downcast::<TMessage>() {
+                                // Satisfy the first predicate that accepts the message
+                                let matching_predicate = remaining.iter().position(|predicate| predicate(&message));
+
+                                if let Some(index) = matching_predicate {
+                                    // The predicate is satisfied, so it no longer needs to be checked
+                                    let _ = remaining.remove(index);
+                                } else {
+                                    failed_assertions.send(format!("Received a message that did not match any of the {} remaining expectations", remaining.len())).await.ok();
+                                    break;
+                                }
+                            } else {
+                                // We expect the exact message type that was specified
+                                failed_assertions.send(format!("Received a message of an unexpected type (was expecting {})", type_name::<TMessage>())).await.ok();
+                                break;
+                            }
+                        }
+
+                        None => {
+                            // The input stream was closed while we were waiting for the messages
+                            failed_assertions.send("Test finished prematurely".to_string()).await.ok();
+                            break;
+                        }
+                    }
+                }
+
+                (input_stream, failed_assertions)
+            }.boxed()
+        }));
+
+        self
+    }
+
+    ///
+    /// Expects that no messages have been dropped by the time the scene next becomes idle
+    ///
+    /// This waits for the scene to become idle (so any messages that are still being sent will have been delivered or dropped),
+    /// then fails the test if `SceneContext::dropped_message_count()` is not 0. Messages are dropped when a send fails because
+    /// the target program has finished or its stream is disconnected, or when the target uses a lossy `InputStreamMode`.
+    ///
+    /// The scene must have an idle request program (ie, it was created with `Scene::default()`) for this to work.
+    ///
+    pub fn expect_no_dropped_messages(mut self) -> Self {
+        // The idle notification is received in the same way as any other expected message
+        self.receive_message_type::<IdleNotification>();
+
+        self.actions.push(Box::new(move |input_stream, context, failed_assertions| {
+            let program_id  = context.current_program_id().unwrap();
+            let context     = context.clone();
+
+            async move {
+                let mut input_stream        = input_stream;
+                let mut failed_assertions   = failed_assertions;
+
+                // Wait for the scene to become idle
+                context.send_message(IdleRequest::WhenIdle(program_id)).await.unwrap();
+
+                match input_stream.next().await {
+                    Some(TestRequest::AnyMessage(any_message)) => {
+                        if any_message.is::<IdleNotification>() {
+                            // Check that nothing was dropped before the scene became idle
+                            let dropped_count = context.dropped_message_count();
+
+                            if dropped_count != 0 {
+                                failed_assertions.send(format!("{} message(s) were dropped before the scene became idle", dropped_count)).await.ok();
+                            }
+                        } else {
+                            failed_assertions.send("Received an unexpected message while waiting for the scene to become idle".to_string()).await.ok();
+                        }
+                    }
+
+                    None => {
+                        // The input stream was closed while we were waiting for the scene to become idle
+                        failed_assertions.send("Test finished prematurely".to_string()).await.ok();
+                    }
                 }
 
                 (input_stream, failed_assertions)
@@ -214,6 +397,16 @@ impl TestBuilder {
         self
     }
 
+    ///
+    /// Sets up the test program so that it can receive messages of a particular type
+    ///
+    fn receive_message_type<TMessage: 'static + Send + SceneMessage>(&mut self) {
+        self.filters.entry(StreamId::with_message_type::<TMessage>())
+            .or_insert_with(|| {
+                FilterHandle::for_filter(|source_stream: InputStream<TMessage>| source_stream.map(|msg| TestRequest::AnyMessage(Box::new(msg))))
+            });
+    }
+
     ///
     /// Creates a test action that redirects the input for a particular message type to the test program
     ///
@@ -0,0 +1,51 @@
+use std::time::{Duration};
+
+///
+/// Describes how a failed send should be retried
+///
+/// This is used by `SceneContext::send_with_retry()`, which is useful when sending to a program that might not have finished
+/// starting up yet (for example, while a scene is still being set up).
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RetryPolicy {
+    /// The message is only sent once
+    Never,
+
+    /// The send is attempted up to the specified number of times, waiting for the same amount of time between each attempt
+    Fixed(usize, Duration),
+
+    /// The send is attempted up to the specified number of times. The first retry waits for the first duration, and the wait
+    /// doubles after each attempt, up to a maximum of the second duration
+    Exponential(usize, Duration, Duration),
+}
+
+impl RetryPolicy {
+    ///
+    /// Returns how long to wait before making another attempt, after the specified number of attempts have failed (or None if
+    /// no more attempts should be made)
+    ///
+    pub (crate) fn delay_after_attempt(&self, failed_attempts: usize) -> Option<Duration> {
+        match self {
+            RetryPolicy::Never => None,
+
+            RetryPolicy::Fixed(max_attempts, delay) => {
+                if failed_attempts < *max_attempts {
+                    Some(*delay)
+                } else {
+                    None
+                }
+            }
+
+            RetryPolicy::Exponential(max_attempts, initial_delay, max_delay) => {
+                if failed_attempts < *max_attempts {
+                    let multiplier  = 1u32.checked_shl((failed_attempts.max(1) - 1) as u32).unwrap_or(u32::MAX);
+                    let delay       = initial_delay.checked_mul(multiplier).unwrap_or(*max_delay);
+
+                    Some(delay.min(*max_delay))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
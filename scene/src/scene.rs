@@ -1,4 +1,4 @@
-use crate::commands::ListCommandResponse;
+use crate::commands::{ListCommandResponse, CommandDescription};
 use crate::input_stream::*;
 use crate::output_sink::*;
 use crate::scene_context::*;
@@ -9,7 +9,9 @@ use crate::stream_source::*;
 use crate::stream_target::*;
 use crate::subprogram_id::*;
 use crate::error::*;
+use crate::filter::*;
 use crate::programs::*;
+use crate::supervision::*;
 
 use futures::prelude::*;
 use futures::channel::oneshot;
@@ -85,7 +87,8 @@ impl Scene {
                 .with_serializable_type::<TextOutput>("flo_scene::TextOutput")
                 .with_serializable_type::<TimerRequest>("flo_scene::TimerRequest")
                 .with_serializable_type::<IdleRequest>("flo_scene::IdleRequest")
-                .with_serializable_type::<ListCommandResponse>("flo_scene::ListCommandResponse");
+                .with_serializable_type::<ListCommandResponse>("flo_scene::ListCommandResponse")
+                .with_serializable_type::<CommandDescription>("flo_scene::CommandDescription");
         }
 
         scene
@@ -111,6 +114,11 @@ impl Scene {
     ///
     /// Adds a subprogram to run in this scene
     ///
+    /// `max_input_waiting` is the number of messages that can be buffered in the input stream of the new subprogram before
+    /// anything sending to it has to wait for it to process its messages (there's no default value: the stream can always
+    /// hold one more message than this, so 0 means that senders will wait if a message is already waiting to be processed).
+    /// This can be changed later on by calling `set_max_waiting()` on the input stream.
+    ///
     pub fn add_subprogram<'a, TProgramFn, TInputMessage, TFuture>(&'a self, program_id: SubProgramId, program: TProgramFn, max_input_waiting: usize)
     where
         TFuture:        'static + Send + Future<Output=()>,
@@ -147,6 +155,24 @@ impl Scene {
         send_context.send((program, context)).ok();
     }
 
+    ///
+    /// Adds a subprogram that is restarted according to a policy if it fails
+    ///
+    /// This works like `add_subprogram()`, except that the program function can be called several times. The program fails if
+    /// its future returns an error or panics, and the `policy` decides if it should be started again with the same input stream
+    /// (any messages that were waiting are kept for the new instance of the program). A program that finishes with `Ok(())`, or
+    /// whose input stream has been closed, is not restarted.
+    ///
+    pub fn add_supervised_subprogram<TProgramFn, TInputMessage, TFuture, TError>(&self, program_id: SubProgramId, program: TProgramFn, policy: RestartPolicy, max_input_waiting: usize)
+    where
+        TFuture:        'static + Send + Future<Output=Result<(), TError>>,
+        TError:         'static,
+        TInputMessage:  'static + SceneMessage,
+        TProgramFn:     'static + Send + Fn(InputStream<TInputMessage>, SceneContext) -> TFuture,
+    {
+        self.add_subprogram(program_id, move |input, context| run_supervised(input, context, program, policy), max_input_waiting);
+    }
+
     ///
     /// Connects the output `stream` of the `source` program to the input of `target`
     ///
@@ -167,6 +193,10 @@ impl Scene {
     /// using `StreamId::with_message_type::<SomeMessage>()` to indicate all outgoing streams of that type from `source`, or 
     /// `StreamId::with_message_type::<SomeMessage>().for_target(target)` to indicate an outgoing stream with a specific destination.
     ///
+    /// This can be called while the scene is running to rewire a program: any existing output sinks that match the source and
+    /// stream are disconnected from their old target and reconnected to the new one, so the next message sent on them will go to
+    /// the new target. Any sender that was blocked waiting for a connection is woken up.
+    ///
     /// Examples:
     ///
     /// ```
@@ -212,6 +242,54 @@ impl Scene {
         SceneCore::connect_programs(&self.core, source, target, stream)
     }
 
+    ///
+    /// Connects the output of a source to several target programs, so that every target receives a copy of each message
+    ///
+    /// This applies to the streams that don't have a specific target (eg, `context.send::<TMessage>(())`), and replaces any
+    /// existing connection for the same source and message type: calling `connect_programs()` for the same source and stream
+    /// will replace the fan-out connection in turn. The targets must accept `TMessage` directly as their input type.
+    ///
+    /// A message is delivered to each target as soon as that target has space in its input buffer, so the targets that are
+    /// keeping up are not held back by a busy target for that message. However, the sender will wait until every target has
+    /// accepted a message before it can send the next one, so a target that stops reading its input will eventually block the
+    /// sender (and thus the other targets). Targets that finish running are dropped from the fan-out.
+    ///
+    pub fn connect_programs_fan_out<TMessage>(&self, source: impl Into<StreamSource>, targets: impl IntoIterator<Item=SubProgramId>) -> Result<(), ConnectionError>
+    where
+        TMessage: 'static + SceneMessage + Clone,
+    {
+        let targets = targets.into_iter()
+            .map(StreamTarget::Program)
+            .collect();
+
+        SceneCore::connect_programs_fan_out::<TMessage>(&self.core, source.into(), targets)
+    }
+
+    ///
+    /// Connects the output of a source to several target programs, passing the messages through a different filter for each target
+    ///
+    /// This works like `connect_programs_fan_out()`, except that each target receives the output of its filter instead of the original
+    /// message. This makes it possible to send the same messages in several forms: for example, one target might receive a serialized
+    /// version of each message while another receives a summary. Each filter must accept `TMessage` as its input (the connection fails
+    /// with `FilterInputDoesNotMatch` if it doesn't), and produce the input type of its target program.
+    ///
+    /// The targets must be `StreamTarget::Program` targets: a fan-out target can only be passed through a single filter, so a target
+    /// that is already filtered will produce an `UnexpectedConnectionType` error.
+    ///
+    pub fn connect_filtered_broadcast<TMessage>(&self, source: impl Into<StreamSource>, targets: impl IntoIterator<Item=(StreamTarget, FilterHandle)>) -> Result<(), ConnectionError>
+    where
+        TMessage: 'static + SceneMessage + Clone,
+    {
+        let targets = targets.into_iter()
+            .map(|(target, filter_handle)| match target {
+                StreamTarget::Program(target_program)   => Ok(StreamTarget::Filtered(filter_handle, target_program)),
+                _                                       => Err(ConnectionError::UnexpectedConnectionType),
+            })
+            .collect::<Result<_, _>>()?;
+
+        SceneCore::connect_programs_fan_out::<TMessage>(&self.core, source.into(), targets)
+    }
+
     ///
     /// Creates a stream that can be used to send messages into this scene from elsewhere
     ///
@@ -247,14 +325,14 @@ impl Scene {
             let new_or_old_target = match new_or_old_target { Ok(new) => new, Err(old) => old };
 
             // Report the new connection
-            let target_program  = OutputSinkCore::target_program_id(&new_or_old_target);
-            let update          = if let Some(target_program) = target_program {
-                SceneUpdate::Connected(program_id, target_program, stream_id)
+            let target_programs = OutputSinkCore::target_program_ids(&new_or_old_target);
+            let updates         = if !target_programs.is_empty() {
+                target_programs.into_iter().map(|target_program| SceneUpdate::Connected(program_id, target_program, stream_id.clone())).collect()
             } else {
-                SceneUpdate::Disconnected(program_id, stream_id)
+                vec![SceneUpdate::Disconnected(program_id, stream_id)]
             };
 
-            SceneCore::send_scene_updates(&self.core, vec![update]);
+            SceneCore::send_scene_updates(&self.core, updates);
 
             // Create an output sink from the target
             let output_sink = OutputSink::attach(program_id, new_or_old_target, &self.core);
@@ -262,6 +340,23 @@ impl Scene {
         }
     }
 
+    ///
+    /// Starts shutting down the scene, returning a future that completes once every subprogram has finished
+    ///
+    /// This closes the input stream of every subprogram that is running in the scene. Each program will receive any messages that
+    /// were already waiting for it, and then its input stream will end, giving it a chance to tidy up before it finishes. This is
+    /// less abrupt than dropping the scene or sending `SceneControl::StopScene`, both of which stop the programs at the point where
+    /// they last yielded. Any programs that are started while the scene is shutting down have their input streams closed as they
+    /// start, so they finish along with the others.
+    ///
+    /// The scene must still be running (eg, via `run_scene()`) for the programs to finish. Programs that are waiting for something
+    /// other than their input stream won't finish until that wait is over, so the returned future may not complete in that case.
+    ///
+    pub fn shutdown(&self) -> impl Future<Output=()> {
+        SceneCore::close_all_inputs(&self.core);
+        SceneCore::wait_for_programs_to_finish(&self.core)
+    }
+
     ///
     /// Returns a future that will run any waiting programs on the current thread
     ///
@@ -3,16 +3,21 @@ use crate::error::*;
 use crate::input_stream::*;
 use crate::output_sink::*;
 use crate::programs::*;
+use crate::retry::*;
 use crate::scene_core::*;
 use crate::scene_message::*;
 use crate::stream_id::*;
+use crate::stream_source::*;
+use crate::stream_stats::*;
 use crate::stream_target::*;
 use crate::subprogram_core::*;
 use crate::subprogram_id::*;
 
 use futures::prelude::*;
 use futures::channel::oneshot;
+use futures_timer::{Delay};
 
+use std::any::{TypeId};
 use std::cell::*;
 use std::sync::*;
 
@@ -51,6 +56,59 @@ impl SceneContext {
         Some(program_id)
     }
 
+    ///
+    /// Returns the subprograms that are currently running in the scene, along with the type and type name of the messages they accept as input
+    ///
+    /// This will return an empty list if the scene is no longer running.
+    ///
+    pub fn list_programs(&self) -> Vec<(SubProgramId, TypeId, &'static str)> {
+        if let Some(scene_core) = self.scene_core.upgrade() {
+            SceneCore::list_programs(&scene_core).into_iter()
+                .map(|(program_id, input_stream_id)| (program_id, input_stream_id.message_type(), input_stream_id.static_message_type_name()))
+                .collect()
+        } else {
+            vec![]
+        }
+    }
+
+    ///
+    /// Returns the connections that have been made in the scene (via `Scene::connect_programs()` or the `SceneControl::Connect` message)
+    ///
+    /// Each connection is returned as the source of the stream, the target it's connected to and the ID of the stream that was connected.
+    /// Connections made implicitly (for example, by sending directly to a program with `send()`) are not included. This will return an
+    /// empty list if the scene is no longer running.
+    ///
+    pub fn list_connections(&self) -> Vec<(StreamSource, StreamTarget, StreamId)> {
+        if let Some(scene_core) = self.scene_core.upgrade() {
+            scene_core.lock().unwrap().list_connections()
+        } else {
+            vec![]
+        }
+    }
+
+    ///
+    /// Returns the number of messages that have been delivered, dropped and are pending for the input stream of a subprogram
+    ///
+    /// The counters are updated as messages pass through the stream, so this can be polled periodically to measure the throughput
+    /// of a program. This returns `None` if the program is not running (or the scene has stopped).
+    ///
+    pub fn stream_stats(&self, program_id: SubProgramId) -> Option<StreamStats> {
+        self.scene_core.upgrade()?.lock().unwrap().stream_stats(program_id)
+    }
+
+    ///
+    /// Returns the total number of messages that could not be delivered in the scene this context belongs to
+    ///
+    /// This counts the sends that failed because their target program had finished or their stream was disconnected,
+    /// sends that timed out, and the messages discarded by input streams that use one of the lossy `InputStreamMode`s.
+    /// Messages sent to a `StreamTarget::None` target are discarded deliberately, so are not counted.
+    ///
+    pub fn dropped_message_count(&self) -> usize {
+        self.scene_core.upgrade()
+            .map(|scene_core| scene_core.lock().unwrap().dropped_message_count())
+            .unwrap_or(0)
+    }
+
     ///
     /// Retrieves a stream for sending messages of the specified type
     ///
@@ -103,14 +161,14 @@ impl SceneContext {
                         mem::drop(stale_sinks);
 
                         // Report the new connection
-                        let target_program  = OutputSinkCore::target_program_id(&new_target);
-                        let update          = if let Some(target_program) = target_program {
-                            SceneUpdate::Connected(program_id, target_program, stream_id)
+                        let target_programs = OutputSinkCore::target_program_ids(&new_target);
+                        let updates         = if !target_programs.is_empty() {
+                            target_programs.into_iter().map(|target_program| SceneUpdate::Connected(program_id, target_program, stream_id.clone())).collect()
                         } else {
-                            SceneUpdate::Disconnected(program_id, stream_id)
+                            vec![SceneUpdate::Disconnected(program_id, stream_id)]
                         };
 
-                        SceneCore::send_scene_updates(&scene_core, vec![update]);
+                        SceneCore::send_scene_updates(&scene_core, updates);
 
                         // Attach the new target to an output sink
                         Ok(OutputSink::attach(program_id, new_target, &scene_core))
@@ -142,6 +200,49 @@ impl SceneContext {
         Ok(())
     }
 
+    ///
+    /// Sends a single message to a target, retrying according to a policy if the target is not ready to receive it
+    ///
+    /// A send is retried if the target program is not in the scene (eg, because it hasn't been started yet) or if its input
+    /// is not available. Once the policy says that no more attempts should be made, the error from the last attempt is returned.
+    /// Other errors (including timeouts) are returned immediately, as are errors where the message was not returned by the stream.
+    ///
+    pub async fn send_with_retry<TMessageType>(&self, target: impl Into<StreamTarget>, message: TMessageType, policy: RetryPolicy) -> Result<(), ConnectionError>
+    where
+        TMessageType: 'static + SceneMessage,
+    {
+        let target              = target.into();
+        let mut message         = message;
+        let mut failed_attempts = 0;
+
+        loop {
+            // Try to send the message, recovering it if the send fails
+            let error = match self.send::<TMessageType>(target.clone()) {
+                Ok(mut stream) => {
+                    match stream.send(message).await {
+                        Ok(()) => { return Ok(()); }
+
+                        // The message can only be sent again if the error returned it
+                        Err(SceneSendError::TargetProgramEnded(returned_message))   => { message = returned_message; ConnectionError::TargetNotInScene }
+                        Err(SceneSendError::StreamDisconnected(returned_message))   => { message = returned_message; ConnectionError::TargetNotAvailable }
+                        Err(other_error)                                            => { return Err(other_error.into()); }
+                    }
+                }
+
+                Err(err) => err,
+            };
+
+            // Only errors where the target might become ready later on are retried
+            let can_retry = matches!(error, ConnectionError::TargetNotInScene | ConnectionError::TargetNotAvailable);
+
+            failed_attempts += 1;
+            match (can_retry, policy.delay_after_attempt(failed_attempts)) {
+                (true, Some(delay)) => { Delay::new(delay).await; }
+                _                   => { return Err(error); }
+            }
+        }
+    }
+
     ///
     /// Retrieves a stream for sending replies to the last message received by the current subprogram
     ///
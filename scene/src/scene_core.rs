@@ -8,6 +8,7 @@ use crate::scene::*;
 use crate::scene_message::*;
 use crate::stream_id::*;
 use crate::stream_source::*;
+use crate::stream_stats::*;
 use crate::stream_target::*;
 use crate::subprogram_core::*;
 use crate::subprogram_id::*;
@@ -20,6 +21,7 @@ use futures::channel::mpsc;
 
 use std::any::*;
 use std::collections::*;
+use std::panic::{AssertUnwindSafe};
 use std::sync::*;
 use std::sync::atomic::{AtomicUsize};
 
@@ -34,6 +36,27 @@ pub (crate) struct SceneCoreWaker {
     process_id: usize,
 }
 
+///
+/// Function used to copy a message for each of the targets of a fan-out connection
+///
+type CloneMessageFn<TMessage> = fn(&TMessage) -> TMessage;
+
+///
+/// The targets of a fan-out connection, along with the function used to copy the messages for them
+///
+type FanOutTargets<TMessage> = (Vec<StreamTarget>, CloneMessageFn<TMessage>);
+
+///
+/// A connection from a source to several target programs
+///
+struct FanOutConnection {
+    /// The targets that will receive a copy of each message (programs or filtered programs)
+    targets: Vec<StreamTarget>,
+
+    /// The `fn(&TMessage) -> TMessage` function used to copy the messages for each target
+    clone_message: Arc<dyn Send + Sync + Any>,
+}
+
 ///
 /// The scene core is used to store the shared state for all scenes
 ///
@@ -68,6 +91,9 @@ pub (crate) struct SceneCore {
     /// The connections to assign between programs. More specific sources override less specific sources.
     connections: HashMap<(StreamSource, StreamId), StreamTarget>,
 
+    /// Connections that send a copy of every message to several programs (a stream has either a fan-out connection or a normal connection)
+    fan_out_connections: HashMap<(StreamSource, StreamId), FanOutConnection>,
+
     /// Filters that can convert between a output stream type and an input stream type
     filter_conversions: HashMap<(StreamId, StreamId), FilterHandle>,
 
@@ -86,8 +112,28 @@ pub (crate) struct SceneCore {
 
     /// An output core where status updates are sent
     updates: Option<(SubProgramId, Arc<Mutex<OutputSinkCore<SceneUpdate>>>)>,
+
+    /// The number of messages that could not be delivered (sends that failed, and messages dropped by programs that have finished)
+    dropped_messages: usize,
+
+    /// Wakers to notify when every subprogram in the scene has finished
+    when_programs_finished: Vec<Waker>,
+
+    /// True if the scene is shutting down: any programs started before the existing programs have all finished will have their inputs closed
+    shutting_down: bool,
+
+    /// If set, the function used to forward messages for programs that are not in the scene to a dead-letter program
+    dead_letter_handler: Option<DeadLetterHandler>,
 }
 
+///
+/// Forwards a message that could not be delivered to a dead-letter program
+///
+/// The parameters are the scene core, the program that sent the message, the target that the message was intended for and the
+/// message itself. The message is returned if it can't be forwarded.
+///
+pub (crate) type DeadLetterHandler = Arc<dyn Send + Sync + Fn(&Arc<Mutex<SceneCore>>, SubProgramId, StreamTarget, Box<dyn Send + Any>) -> Result<(), Box<dyn Send + Any>>>;
+
 impl SceneCore {
     ///
     /// Creates an empty scene core
@@ -103,6 +149,7 @@ impl SceneCore {
             program_indexes:            HashMap::new(),
             awake_processes:            VecDeque::new(),
             connections:                HashMap::new(),
+            fan_out_connections:        HashMap::new(),
             filter_conversions:         HashMap::new(),
             filtered_targets:           HashMap::new(),
             thread_wakers:              vec![],
@@ -110,6 +157,10 @@ impl SceneCore {
             notify_when_idle:           false,
             when_idle:                  vec![],
             updates:                    None,
+            dropped_messages:           0,
+            when_programs_finished:     vec![],
+            shutting_down:              false,
+            dead_letter_handler:        None,
         }
     }
 
@@ -155,7 +206,10 @@ impl SceneCore {
 
         Self::initialise_message_type(scene_core, StreamId::with_message_type::<TMessage>());
 
-        let (subprogram, waker) = {
+        let input_counters  = input_core.lock().unwrap().counters();
+        let final_counters  = Arc::clone(&input_counters);
+
+        let (subprogram, waker, shutting_down) = {
             let start_core      = Arc::downgrade(scene_core);
             let process_core    = Arc::downgrade(scene_core);
             let mut core        = scene_core.lock().unwrap();
@@ -175,11 +229,17 @@ impl SceneCore {
                 }
                 mem::drop(start_core);
 
-                // Wait for the program to run
-                program.await;
+                // Wait for the program to run (a panic only stops this program, rather than whatever is running the scene)
+                let panic_message = AssertUnwindSafe(program).catch_unwind().await
+                    .err()
+                    .map(|panic_payload| panic_payload_message(&*panic_payload));
 
                 // Notify that the program has finished
                 if let Some(mut update_sink) = update_sink {
+                    if let Some(panic_message) = panic_message {
+                        update_sink.send(SceneUpdate::Panicked(program_id, panic_message)).await.ok();
+                    }
+
                     update_sink.send(SceneUpdate::Stopped(program_id)).await.ok();
                 }
 
@@ -192,8 +252,25 @@ impl SceneCore {
                     let old_input_core      = core.sub_program_inputs[handle].take();
                     core.next_subprogram    = core.next_subprogram.min(handle);
 
+                    // The handle can be reused by another program, so the ID no longer refers to it (unless the program has already been replaced)
+                    if core.program_indexes.get(&program_id) == Some(&handle) {
+                        core.program_indexes.remove(&program_id);
+                    }
+
+                    // Keep track of the messages that the program's input stream dropped
+                    core.dropped_messages   += final_counters.stats().dropped;
+
+                    // Notify anything waiting for the scene to finish if this was the last program (which also finishes shutting down the scene)
+                    let finished_wakers = if core.sub_programs.iter().all(|program| program.is_none()) {
+                        core.shutting_down = false;
+                        mem::take(&mut core.when_programs_finished)
+                    } else {
+                        vec![]
+                    };
+
                     // Drop in order: first release the core lock, then drop the subprograms (which may re-take it)
                     mem::drop(core);
+                    finished_wakers.into_iter().for_each(|waker| waker.wake());
 
                     if let Some(old_sub_program) = &old_sub_program {
                         old_sub_program.lock().unwrap().process_id = None;
@@ -214,6 +291,7 @@ impl SceneCore {
                 output_high_water:          0,
                 expected_input_type_name:   type_name::<TMessage>(),
                 next_command_sequence:      Arc::new(AtomicUsize::new(0)),
+                input_counters:             input_counters,
             };
 
             // Allocate space for the program
@@ -226,7 +304,7 @@ impl SceneCore {
             // Store the program details
             let subprogram                  = Arc::new(Mutex::new(subprogram));
             core.sub_programs[handle]       = Some(Arc::clone(&subprogram));
-            core.sub_program_inputs[handle] = Some((StreamId::with_message_type::<TMessage>(), input_core));
+            core.sub_program_inputs[handle] = Some((StreamId::with_message_type::<TMessage>(), input_core.clone()));
             core.program_indexes.insert(program_id, handle);
 
             // Update the 'next_subprogram' value to an empty slot
@@ -234,7 +312,7 @@ impl SceneCore {
                 core.next_subprogram += 1;
             }
 
-            (subprogram, waker)
+            (subprogram, waker, core.shutting_down)
         };
 
         // Safe to wake the waker once the core lock is released
@@ -242,6 +320,15 @@ impl SceneCore {
             waker.wake();
         }
 
+        // Programs started while the scene is shutting down have their input closed straight away, so they finish along with the others
+        if shutting_down {
+            let close_waker = input_core.lock().unwrap().close();
+
+            if let Some(close_waker) = close_waker {
+                close_waker.wake();
+            }
+        }
+
         // Result is the subprogram
         subprogram
     }
@@ -462,7 +549,8 @@ impl SceneCore {
         let sub_programs = {
             let mut core = core.lock().unwrap();
 
-            // Store the connection
+            // Store the connection (replacing any fan-out connection)
+            core.fan_out_connections.remove(&(source.clone(), stream_id.clone()));
             core.connections.insert((source.clone(), stream_id.clone()), target.clone());
 
             // Fetch the sub-programs to update
@@ -500,6 +588,221 @@ impl SceneCore {
         Ok(())
     }
 
+    ///
+    /// Connects the output of a source to several targets, which will each receive a copy of every message
+    ///
+    /// The targets can be programs (which must accept `TMessage` as their input) or filtered programs (where the filter must accept
+    /// `TMessage` as its input).
+    ///
+    pub (crate) fn connect_programs_fan_out<TMessage>(core: &Arc<Mutex<SceneCore>>, source: StreamSource, targets: Vec<StreamTarget>) -> Result<(), ConnectionError>
+    where
+        TMessage: 'static + SceneMessage + Clone,
+    {
+        use std::mem;
+
+        let stream_id = StreamId::with_message_type::<TMessage>();
+        Self::initialise_message_type(core, stream_id.clone());
+
+        // Filtered sources can't be fanned out (the filter has to be applied separately for each target)
+        if let StreamSource::Filtered(_) = &source {
+            return Err(ConnectionError::UnexpectedConnectionType);
+        }
+
+        let clone_message: CloneMessageFn<TMessage> = TMessage::clone;
+
+        // Check that the targets can be connected
+        let sub_programs = {
+            let core = core.lock().unwrap();
+            core.check_fan_out_targets::<TMessage>(&targets)?;
+
+            core.sub_programs.clone()
+        };
+
+        // Create the targets for the existing output sinks (each source program needs its own filters, so these are created separately)
+        let mut new_targets = vec![];
+
+        for sub_program in sub_programs.iter().flatten() {
+            let (sub_program_id, output_core) = {
+                let sub_program = sub_program.lock().unwrap();
+                (sub_program.id, sub_program.output_core::<TMessage>(&stream_id))
+            };
+
+            if !source.matches_subprogram(&sub_program_id) { continue; }
+
+            if let Some(output_core) = output_core {
+                let fan_out_target = Self::fan_out_target(core, sub_program_id, &targets, clone_message)?;
+                new_targets.push((sub_program_id, output_core, fan_out_target));
+            }
+        }
+
+        // Store the connection, replacing any normal connection for this stream
+        {
+            let mut core = core.lock().unwrap();
+
+            core.connections.remove(&(source.clone(), stream_id.clone()));
+            core.fan_out_connections.insert((source.clone(), stream_id.clone()), FanOutConnection { targets: targets.clone(), clone_message: Arc::new(clone_message) });
+        }
+
+        // Update the existing connections
+        let mut scene_updates = vec![];
+
+        for (sub_program_id, output_core, fan_out_target) in new_targets {
+            // Send the output to all of the targets
+            let (old_target, waker) = {
+                let mut output_core = output_core.lock().unwrap();
+
+                let old_target = mem::replace(&mut output_core.target, fan_out_target);
+                (old_target, output_core.when_target_changed.take())
+            };
+
+            // Dropping the old target may close a filter, so this is done after the output core is released
+            mem::drop(old_target);
+
+            scene_updates.extend(targets.iter()
+                .flat_map(|target| target.target_sub_program())
+                .map(|target| SceneUpdate::Connected(sub_program_id, target, stream_id.clone())));
+
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+
+        // Send the updates on how the connections have changed
+        SceneCore::send_scene_updates(core, scene_updates);
+
+        Ok(())
+    }
+
+    ///
+    /// Checks that the targets of a fan-out connection are in the scene and accept the message type (for filtered targets, the filter
+    /// must accept the message type)
+    ///
+    fn check_fan_out_targets<TMessage>(&self, targets: &[StreamTarget]) -> Result<(), ConnectionError>
+    where
+        TMessage: 'static + SceneMessage,
+    {
+        for target in targets {
+            match target {
+                StreamTarget::Program(target_program) => {
+                    self.fan_out_program_input::<TMessage>(target_program)?;
+                }
+
+                StreamTarget::Filtered(filter_handle, target_program) => {
+                    if filter_handle.source_stream_id_any()? != StreamId::with_message_type::<TMessage>() {
+                        return Err(ConnectionError::FilterInputDoesNotMatch);
+                    }
+
+                    let handle = *self.program_indexes.get(target_program).ok_or(ConnectionError::TargetNotInScene)?;
+                    self.sub_program_inputs[handle].as_ref().ok_or(ConnectionError::TargetNotAvailable)?;
+                }
+
+                StreamTarget::None | StreamTarget::Any => {
+                    return Err(ConnectionError::UnexpectedConnectionType);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    ///
+    /// Retrieves the input core for an unfiltered fan-out target
+    ///
+    fn fan_out_program_input<TMessage>(&self, target_program: &SubProgramId) -> Result<Arc<Mutex<InputStreamCore<TMessage>>>, ConnectionError>
+    where
+        TMessage: 'static + SceneMessage,
+    {
+        let stream_id                   = StreamId::with_message_type::<TMessage>();
+        let handle                      = *self.program_indexes.get(target_program).ok_or(ConnectionError::TargetNotInScene)?;
+        let (input_stream_id, input)    = self.sub_program_inputs[handle].as_ref().ok_or(ConnectionError::TargetNotAvailable)?;
+
+        // Unfiltered targets must accept the message type directly
+        Arc::clone(input).downcast::<Mutex<InputStreamCore<TMessage>>>()
+            .map_err(|_| ConnectionError::WrongInputType(SourceStreamMessageType(stream_id.message_type_name()), TargetInputMessageType(input_stream_id.message_type_name())))
+    }
+
+    ///
+    /// Creates an output sink target for a source program that sends copies of its messages to several targets
+    ///
+    fn fan_out_target<TMessage>(core: &Arc<Mutex<SceneCore>>, source_program: SubProgramId, targets: &[StreamTarget], clone_message: CloneMessageFn<TMessage>) -> Result<OutputSinkTarget<TMessage>, ConnectionError>
+    where
+        TMessage: 'static + SceneMessage,
+    {
+        use std::mem;
+
+        let mut inputs              = vec![];
+        let mut close_when_dropped  = vec![];
+
+        for target in targets {
+            // Targets that have finished running are dropped from the fan-out
+            if let Some(target_program) = target.target_sub_program() {
+                if core.lock().unwrap().get_input_stream_core(target_program).is_none() {
+                    continue;
+                }
+            }
+
+            let result = match target {
+                StreamTarget::Program(target_program)                   => core.lock().unwrap().fan_out_program_input::<TMessage>(target_program),
+                StreamTarget::Filtered(filter_handle, target_program)   => Self::filtered_input_for_program::<TMessage>(core, source_program, *filter_handle, *target_program),
+                StreamTarget::None | StreamTarget::Any                  => Err(ConnectionError::UnexpectedConnectionType),
+            };
+
+            match result {
+                Ok(input) => {
+                    // Filtered inputs run as a process which has to be closed when the target is disconnected
+                    if let StreamTarget::Filtered(_, _) = target {
+                        close_when_dropped.push(Arc::downgrade(&input));
+                    }
+
+                    inputs.push(Arc::downgrade(&input));
+                }
+
+                Err(err) => {
+                    // Close any filters that were created before the error (by dropping the output target)
+                    mem::drop(OutputSinkTarget::FanOut(inputs, clone_message, close_when_dropped));
+                    return Err(err);
+                }
+            }
+        }
+
+        // The connection can't be made if every target has finished
+        if inputs.is_empty() && !targets.is_empty() {
+            return Err(ConnectionError::TargetNotAvailable);
+        }
+
+        Ok(OutputSinkTarget::FanOut(inputs, clone_message, close_when_dropped))
+    }
+
+    ///
+    /// If the output of a program should be sent to a fan-out connection, returns the targets and the function for copying the messages for that connection
+    ///
+    fn fan_out_connection_for_source<TMessage>(&self, source: &SubProgramId) -> Option<Result<FanOutTargets<TMessage>, ConnectionError>>
+    where
+        TMessage: 'static + SceneMessage,
+    {
+        let stream_id   = StreamId::with_message_type::<TMessage>();
+        let source      = StreamSource::Program(*source);
+
+        // Connections for a specific source override connections for all sources
+        let fan_out = if self.connections.contains_key(&(source.clone(), stream_id.clone())) {
+            None
+        } else if let Some(fan_out) = self.fan_out_connections.get(&(source, stream_id.clone())) {
+            Some(fan_out)
+        } else if self.connections.contains_key(&(StreamSource::All, stream_id.clone())) {
+            None
+        } else {
+            self.fan_out_connections.get(&(StreamSource::All, stream_id))
+        }?;
+
+        let clone_message = fan_out.clone_message.downcast_ref::<CloneMessageFn<TMessage>>().copied();
+
+        if let Some(clone_message) = clone_message {
+            Some(Ok((fan_out.targets.clone(), clone_message)))
+        } else {
+            Some(Err(ConnectionError::UnexpectedConnectionType))
+        }
+    }
+
     ///
     /// Creates an InputStreamCore that reads the input type of a filter, then chains the output through another filter to generate an output
     ///
@@ -755,6 +1058,17 @@ impl SceneCore {
 
         // Map the target to get the real target
         let core            = scene_core.lock().unwrap();
+
+        // Streams without a specific target might be connected to several programs
+        if let StreamTarget::None | StreamTarget::Any = &target {
+            if let Some(fan_out) = core.fan_out_connection_for_source::<TMessageType>(source) {
+                mem::drop(core);
+
+                let (targets, clone_message) = fan_out?;
+                return Self::fan_out_target(scene_core, *source, &targets, clone_message);
+            }
+        }
+
         let mapped_target   = core.mapped_target_for_connection(&source.into(), &target, &StreamId::with_message_type::<TMessageType>())?;
 
         let output_sink_target = match (output_filter, mapped_target) {
@@ -762,6 +1076,11 @@ impl SceneCore {
             (_, StreamTarget::Any)  => OutputSinkTarget::Disconnected,
 
             (None, StreamTarget::Program(target_program_id)) => {
+                // Programs that are not in the scene can have their messages sent to a dead-letter program instead
+                if !core.program_indexes.contains_key(&target_program_id) && core.dead_letter_handler.is_some() {
+                    return Ok(OutputSinkTarget::DeadLetter(target_program_id, Self::send_dead_letter::<TMessageType>));
+                }
+
                 // Fetch the input for the target program
                 let target_program_handle   = core.program_indexes.get(&target_program_id).ok_or(ConnectionError::TargetNotInScene)?;
                 let target_program_input    = core.sub_program_inputs.get(*target_program_handle).ok_or(ConnectionError::TargetNotInScene)?.clone().ok_or(ConnectionError::TargetNotInScene)?;
@@ -854,6 +1173,96 @@ impl SceneCore {
         }
     }
 
+    ///
+    /// Returns the subprograms that are currently running in a scene, along with the stream ID of their input stream
+    ///
+    pub (crate) fn list_programs(core: &Arc<Mutex<SceneCore>>) -> Vec<(SubProgramId, StreamId)> {
+        // Fetch the active programs while the core is locked
+        let programs = {
+            let core = core.lock().unwrap();
+
+            core.sub_programs.iter()
+                .zip(core.sub_program_inputs.iter())
+                .flat_map(|(program, input)| Some((program.as_ref()?.clone(), input.as_ref()?.0.clone())))
+                .collect::<Vec<_>>()
+        };
+
+        // Read the program IDs once the core is released
+        programs.into_iter()
+            .map(|(program, input_stream_id)| (*program.lock().unwrap().program_id(), input_stream_id))
+            .collect()
+    }
+
+    ///
+    /// Returns the current message counts for the input stream of a subprogram, if it's running
+    ///
+    pub (crate) fn stream_stats(&self, program_id: SubProgramId) -> Option<StreamStats> {
+        let sub_program = self.get_sub_program(program_id)?;
+        let stats       = sub_program.lock().unwrap().input_counters.stats();
+
+        Some(stats)
+    }
+
+    ///
+    /// Records that a message could not be delivered to its target
+    ///
+    pub (crate) fn message_dropped(&mut self) {
+        self.dropped_messages += 1;
+    }
+
+    ///
+    /// Sets the function used to forward messages that can't be delivered to a dead-letter program
+    ///
+    #[cfg(feature = "serde_support")]
+    pub (crate) fn set_dead_letter_handler(&mut self, handler: Option<DeadLetterHandler>) {
+        self.dead_letter_handler = handler;
+    }
+
+    ///
+    /// Forwards a message that could not be delivered to the dead-letter program for this scene, returning the message if that isn't possible
+    ///
+    pub (crate) fn send_dead_letter<TMessageType>(scene_core: &Arc<Mutex<SceneCore>>, source: SubProgramId, target: StreamTarget, message: TMessageType) -> Result<(), TMessageType>
+    where
+        TMessageType: 'static + SceneMessage,
+    {
+        let handler = scene_core.lock().unwrap().dead_letter_handler.clone();
+
+        if let Some(handler) = handler {
+            (*handler)(scene_core, source, target, Box::new(message))
+                .map_err(|message| *message.downcast::<TMessageType>().expect("Dead-letter handler should return the original message"))
+        } else {
+            Err(message)
+        }
+    }
+
+    ///
+    /// Returns the total number of messages that could not be delivered in this scene
+    ///
+    pub (crate) fn dropped_message_count(&self) -> usize {
+        // Messages dropped by the input streams of running programs are read from their counters
+        let running_dropped = self.sub_programs.iter()
+            .flatten()
+            .map(|sub_program| sub_program.lock().unwrap().input_counters.stats().dropped)
+            .sum::<usize>();
+
+        self.dropped_messages + running_dropped
+    }
+
+    ///
+    /// Returns the connections that have been requested by `connect_programs()`
+    ///
+    pub (crate) fn list_connections(&self) -> Vec<(StreamSource, StreamTarget, StreamId)> {
+        let connections = self.connections.iter()
+            .map(|((source, stream_id), target)| (source.clone(), target.clone(), stream_id.clone()));
+
+        // Fan-out connections are listed once for each target
+        let fan_out_connections = self.fan_out_connections.iter()
+            .flat_map(|((source, stream_id), fan_out)| fan_out.targets.iter()
+                .map(move |target| (source.clone(), target.clone(), stream_id.clone())));
+
+        connections.chain(fan_out_connections).collect()
+    }
+
     ///
     /// Retrieves the input stream core for a subprogram, if it exists
     ///
@@ -871,12 +1280,68 @@ impl SceneCore {
     /// Stops this scene, returning the wakers that need to be invoked to finish stopping it
     ///
     pub (crate) fn stop(&mut self) -> Vec<Waker> {
+        use std::mem;
+
         self.stopped = true;
 
-        self.thread_wakers
+        let mut wakers = self.thread_wakers
             .iter_mut()
             .filter_map(|waker| waker.take())
-            .collect()
+            .collect::<Vec<_>>();
+
+        // Anything waiting for the programs to finish should stop waiting too
+        wakers.extend(mem::take(&mut self.when_programs_finished));
+
+        wakers
+    }
+
+    ///
+    /// Closes the input streams of every subprogram in the scene, so that they finish once they have processed their remaining messages
+    ///
+    /// Any programs that are started before the existing programs have all finished will have their inputs closed as they start.
+    ///
+    pub (crate) fn close_all_inputs(core: &Arc<Mutex<SceneCore>>) {
+        // Fetch the inputs while the core is locked, then close them once it's released
+        let inputs = {
+            let mut core = core.lock().unwrap();
+
+            // The scene stays in the 'shutting down' state until the last program finishes
+            core.shutting_down = core.sub_programs.iter().any(|program| program.is_some());
+
+            core.sub_program_inputs.iter()
+                .flatten()
+                .cloned()
+                .collect::<Vec<_>>()
+        };
+
+        for (stream_id, input_core) in inputs {
+            if let Ok(Some(waker)) = stream_id.close_input(&input_core) {
+                waker.wake();
+            }
+        }
+    }
+
+    ///
+    /// Returns a future that completes once every subprogram in the scene has finished (or the scene is stopped)
+    ///
+    pub (crate) fn wait_for_programs_to_finish(core: &Arc<Mutex<SceneCore>>) -> impl Future<Output=()> {
+        let core = Arc::downgrade(core);
+
+        poll_fn(move |context| {
+            if let Some(core) = core.upgrade() {
+                let mut core = core.lock().unwrap();
+
+                if core.stopped || core.sub_programs.iter().all(|program| program.is_none()) {
+                    Poll::Ready(())
+                } else {
+                    core.when_programs_finished.push(context.waker().clone());
+                    Poll::Pending
+                }
+            } else {
+                // The scene has been dropped
+                Poll::Ready(())
+            }
+        })
     }
 
     ///
@@ -1148,6 +1613,19 @@ impl ArcWake for SceneCoreWaker {
     }
 }
 
+///
+/// Retrieves the message from the payload of a panic, if it has one
+///
+fn panic_payload_message(panic_payload: &(dyn Send + Any)) -> String {
+    if let Some(message) = panic_payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = panic_payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "Subprogram panicked".to_string()
+    }
+}
+
 ///
 /// Runs the programs attached to a scene core
 ///
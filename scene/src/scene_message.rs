@@ -49,3 +49,5 @@ impl SceneMessage for i64 { }
 impl SceneMessage for u64 { }
 impl SceneMessage for i128 { }
 impl SceneMessage for u128 { }
+
+impl<TMessage: SceneMessage> SceneMessage for Vec<TMessage> { }
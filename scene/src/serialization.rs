@@ -1,12 +1,18 @@
+use crate::error::*;
 use crate::filter::*;
+use crate::input_stream::*;
 use crate::scene::*;
+use crate::scene_context::*;
+use crate::scene_core::*;
 use crate::scene_message::*;
 use crate::stream_source::*;
 use crate::stream_id::*;
+use crate::stream_target::*;
+use crate::subprogram_id::*;
+use crate::programs::*;
 
 use futures::prelude::*;
-use futures::stream;
-
+use futures::future::{BoxFuture};
 use once_cell::sync::{Lazy};
 use serde::*;
 
@@ -28,6 +34,15 @@ static CREATE_ANY_SERIALIZER: Lazy<RwLock<HashMap<TypeId, Arc<dyn Send + Sync +
 /// Stores the functions for transforming a value to and from its serialized representation
 static TYPED_SERIALIZERS: Lazy<RwLock<HashMap<(TypeId, TypeId), Arc<dyn Send + Sync + Any>>>> = Lazy::new(|| RwLock::new(HashMap::new()));
 
+/// Stores functions that serialize a message that's been boxed as an `Any` (these are used when the message type isn't known, such as when forwarding dead letters)
+static ANY_SERIALIZERS: Lazy<RwLock<AnySerializerTable>> = Lazy::new(|| RwLock::new(HashMap::new()));
+
+/// Maps a (message type, serialized type) pair to a function for that type (stored as an `Any`, eg an `AnySerializer`)
+type AnySerializerTable = HashMap<(TypeId, TypeId), Arc<dyn Send + Sync + Any>>;
+
+/// Stores functions that send a `Subscribe` message requesting that events are sent to a program as serialized messages
+static SERIALIZED_SUBSCRIBERS: Lazy<RwLock<AnySerializerTable>> = Lazy::new(|| RwLock::new(HashMap::new()));
+
 /// Stores the filters we've already created so we don't create extr
 static FILTERS_FOR_TYPE: Lazy<Mutex<HashMap<(TypeId, TypeId), FilterHandle>>> = Lazy::new(|| Mutex::new(HashMap::new()));
 
@@ -45,6 +60,33 @@ where
 {
 }
 
+///
+/// A message that could not be delivered to its target, which has been forwarded to the dead-letter program for the scene
+///
+/// The dead-letter program is set up using `SceneWithSerializer::with_dead_letter_target()`. Only message types that have been
+/// installed as serializable types can be sent as dead letters.
+///
+#[derive(Debug, PartialEq)]
+pub struct DeadLetter<TSerializedType> {
+    /// The target that the message was originally sent to
+    pub target: StreamTarget,
+
+    /// The message that could not be delivered
+    pub serialized_message: SerializedMessage<TSerializedType>,
+}
+
+impl<TSerializedType> SceneMessage for DeadLetter<TSerializedType> 
+where
+    TSerializedType: Send + Unpin,
+{
+}
+
+/// A serializer for a message that has been boxed as an `Any`, which returns the message if it's not of the expected type or can't be serialized
+type AnySerializer<TSerializedType> = Box<dyn Send + Sync + Fn(Box<dyn Send + Any>) -> Result<SerializedMessage<TSerializedType>, Box<dyn Send + Any>>>;
+
+/// Sends a `Subscribe` message from a context, requesting that the events are sent to the specified program as serialized messages
+type SerializedSubscriber = Box<dyn Send + Sync + Fn(&SceneContext, SubProgramId) -> BoxFuture<'static, Result<(), ConnectionError>>>;
+
 ///
 /// Adds a constructor for a serializer to the types that flo_scene knows about
 ///
@@ -94,17 +136,7 @@ where
 {
     // Store the name for this type (which must match the old name)
     let type_name = type_name.into();
-    {
-        let mut type_names = (*SERIALIZABLE_MESSAGE_TYPE_NAMES).write().unwrap();
-
-        if let Some(existing_type_name) = type_names.get(&TypeId::of::<TMessageType>()) {
-            if existing_type_name != &type_name {
-                return Err("Serialization type name has been used by another type");
-            }
-        } else {
-            type_names.insert(TypeId::of::<TMessageType>(), type_name.clone());
-        }
-    }
+    install_serializable_type_name::<TMessageType>(&type_name)?;
 
     // Fetch the serializer constructor function (this is what's set up by install_serializer)
     let new_serializer = (*CREATE_ANY_SERIALIZER).read().unwrap()
@@ -138,9 +170,206 @@ where
         }
     };
 
+    install_typed_serializers(type_name, typed_serializer, typed_deserializer);
+
+    // TODO: for any type where the type name does not begin with a known suffix (query:: or subscribe::), add the query and subscribe versios
+
+    Ok(())
+}
+
+///
+/// Creates the data structures needed to serialize a particular type to and from a binary format
+///
+/// Binary formats such as postcard or bincode write their output to a `Vec<u8>`, which can't be used as a `Deserializer` in the
+/// way that something like `serde_json::Value` can, so they can't be set up with `install_serializable_type()`. Instead, this takes
+/// a pair of functions that convert a message to and from its binary representation. Messages serialized this way are sent as
+/// `SerializedMessage<Vec<u8>>`, and the filters to convert them can be retrieved using `serializer_filter()` as for any other
+/// serializer.
+///
+/// The type name has the same restrictions as for `install_serializable_type()`: it must identify a single message type.
+///
+pub fn install_binary_serializable_type<TMessageType, TSerializeError, TDeserializeError>(
+    type_name:      impl Into<String>, 
+    serialize:      impl 'static + Send + Sync + Fn(&TMessageType) -> Result<Vec<u8>, TSerializeError>, 
+    deserialize:    impl 'static + Send + Sync + Fn(&[u8]) -> Result<TMessageType, TDeserializeError>
+) -> Result<(), &'static str>
+where
+    TMessageType: 'static + SceneMessage,
+{
+    // Store the name for this type
+    let type_name = type_name.into();
+    install_serializable_type_name::<TMessageType>(&type_name)?;
+
+    // The serializer and deserializer work with byte buffers instead of a serde Serializer
+    let typed_serializer = move |input: TMessageType| -> Result<SerializedMessage<Vec<u8>>, TMessageType> {
+        match serialize(&input) {
+            Ok(bytes)   => Ok(SerializedMessage(bytes, TypeId::of::<TMessageType>())),
+            Err(_)      => Err(input),
+        }
+    };
+
+    let typed_deserializer = move |input: SerializedMessage<Vec<u8>>| -> Result<TMessageType, SerializedMessage<Vec<u8>>> {
+        match deserialize(&input.0) {
+            Ok(val) => Ok(val),
+            Err(_)  => Err(input),
+        }
+    };
+
+    install_typed_serializers(type_name, typed_serializer, typed_deserializer);
+
+    Ok(())
+}
+
+///
+/// Installs a message type so that it can be serialized using the postcard binary format
+///
+/// Postcard messages are sent as `SerializedMessage<Vec<u8>>`. See `install_binary_serializable_type()` for details.
+///
+#[cfg(feature="postcard")]
+pub fn install_postcard_serializable_type<TMessageType>(type_name: impl Into<String>) -> Result<(), &'static str>
+where
+    TMessageType: 'static + SceneMessage,
+    TMessageType: for<'a> Deserialize<'a>,
+    TMessageType: Serialize,
+{
+    install_binary_serializable_type(type_name, 
+        |msg: &TMessageType| postcard::to_allocvec(msg), 
+        |bytes: &[u8]| postcard::from_bytes::<TMessageType>(bytes))
+}
+
+///
+/// Checks that a value can be serialized and deserialized again using the serializers installed for a type
+///
+/// The value is serialized using the serializer installed by `install_serializer()`, and then passed through the same deserializer
+/// that is used by the filters returned by `serializer_filter()`. The result must be the same as the original value. This is useful
+/// for finding types where serialization succeeds but deserialization fails, which would otherwise only be discovered when a message
+/// is dropped by a deserialization filter.
+///
+pub fn verify_serialization_roundtrip<TMessageType, TSerializer>(value: &TMessageType) -> Result<(), SerializationError>
+where
+    TMessageType:                   'static + SceneMessage + PartialEq,
+    TMessageType:                   for<'a> Deserialize<'a>,
+    TMessageType:                   Serialize,
+    TSerializer:                    'static + Send + Serializer,
+    TSerializer::Ok:                'static + Send + Unpin,
+    for<'a> &'a TSerializer::Ok:    Deserializer<'a>,
+{
+    // Create the serializer
+    let new_serializer = (*CREATE_ANY_SERIALIZER).read().unwrap()
+        .get(&TypeId::of::<TSerializer>())
+        .cloned()
+        .ok_or(SerializationError::SerializerNotInstalled)?;
+    let new_serializer = new_serializer().downcast::<Box<dyn Send + Sync + Fn() -> TSerializer>>()
+        .map_err(|_| SerializationError::SerializerNotInstalled)?;
+
+    // Fetch the deserializer used by the filters
+    let typed_deserializer = (*TYPED_SERIALIZERS).read().unwrap()
+        .get(&(TypeId::of::<SerializedMessage<TSerializer::Ok>>(), TypeId::of::<TMessageType>()))
+        .cloned()
+        .ok_or(SerializationError::TypeNotInstalled)?;
+    let typed_deserializer = typed_deserializer.downcast::<Box<dyn Send + Sync + Fn(SerializedMessage<TSerializer::Ok>) -> Result<TMessageType, SerializedMessage<TSerializer::Ok>>>>()
+        .map_err(|_| SerializationError::TypeNotInstalled)?;
+
+    // Serialize the value, then send it back through the deserializer
+    let serialized      = value.serialize(new_serializer()).map_err(|err| SerializationError::CannotSerialize(err.to_string()))?;
+    let deserialized    = (*typed_deserializer)(SerializedMessage(serialized, TypeId::of::<TMessageType>()))
+        .map_err(|_| SerializationError::CannotDeserialize)?;
+
+    if &deserialized == value {
+        Ok(())
+    } else {
+        Err(SerializationError::DeserializedValueDoesNotMatch)
+    }
+}
+
+///
+/// Removes a serializable type that was previously installed with `install_serializable_type()` or `install_binary_serializable_type()`
+///
+/// The type name and the serializers for the type are removed for every serializer they were installed for, after which the type
+/// name is free to be installed again, possibly for a different message type. Filters that have already been connected in a scene
+/// are unaffected: they will keep serializing using the old behaviour until they are reconnected, though `serializer_filter()` will
+/// return an error for the type until it is installed again.
+///
+pub fn uninstall_serializable_type(type_name: &str) -> Result<(), &'static str> {
+    // Remove the stream ID for the type, which tells us which message type it was
+    let stream_id = (*STREAM_ID_FOR_SERIALIZABLE_TYPE).write().unwrap().remove(type_name);
+    let stream_id = if let Some(stream_id) = stream_id { stream_id } else { return Err("Serialization type name is not installed"); };
+
+    let message_type = stream_id.message_type();
+
+    // Remove the type name, and the serializers and filters that convert to or from this type
+    (*SERIALIZABLE_MESSAGE_TYPE_NAMES).write().unwrap().remove(&message_type);
+    (*TYPED_SERIALIZERS).write().unwrap().retain(|(source_type, target_type), _| *source_type != message_type && *target_type != message_type);
+    (*ANY_SERIALIZERS).write().unwrap().retain(|(source_type, _), _| *source_type != message_type);
+    (*SERIALIZED_SUBSCRIBERS).write().unwrap().retain(|(source_type, _), _| *source_type != message_type);
+    (*FILTERS_FOR_TYPE).lock().unwrap().retain(|(source_type, target_type), _| *source_type != message_type && *target_type != message_type);
+
+    Ok(())
+}
+
+///
+/// Returns the serialization type name, type ID and Rust type name of every type that has been installed with `install_serializable_type()`
+/// or `install_binary_serializable_type()`
+///
+/// This can be used to list which message types can be serialized, for example to check that both ends of a connection agree on the
+/// types that are available. The list is sorted by the serialization type name.
+///
+pub fn registered_serializable_types() -> Vec<(String, TypeId, &'static str)> {
+    let mut types = (*STREAM_ID_FOR_SERIALIZABLE_TYPE).read().unwrap()
+        .iter()
+        .map(|(type_name, stream_id)| (type_name.clone(), stream_id.message_type(), stream_id.static_message_type_name()))
+        .collect::<Vec<_>>();
+
+    types.sort_by(|(name_a, _, _), (name_b, _, _)| name_a.cmp(name_b));
+
+    types
+}
+
+///
+/// Stores the name of a serializable type, returning an error if the type already has a different name
+///
+fn install_serializable_type_name<TMessageType>(type_name: &str) -> Result<(), &'static str> 
+where
+    TMessageType: 'static + SceneMessage,
+{
+    let mut type_names = (*SERIALIZABLE_MESSAGE_TYPE_NAMES).write().unwrap();
+
+    if let Some(existing_type_name) = type_names.get(&TypeId::of::<TMessageType>()) {
+        if existing_type_name != type_name {
+            return Err("Serialization type name has been used by another type");
+        }
+    } else {
+        type_names.insert(TypeId::of::<TMessageType>(), type_name.to_string());
+    }
+
+    Ok(())
+}
+
+///
+/// Stores the functions that convert a message type to and from its serialized form
+///
+fn install_typed_serializers<TMessageType, TSerializedType>(
+    type_name:          String, 
+    typed_serializer:   impl 'static + Send + Sync + Fn(TMessageType) -> Result<SerializedMessage<TSerializedType>, TMessageType>, 
+    typed_deserializer: impl 'static + Send + Sync + Fn(SerializedMessage<TSerializedType>) -> Result<TMessageType, SerializedMessage<TSerializedType>>)
+where
+    TMessageType:       'static + SceneMessage,
+    TSerializedType:    'static + Send + Unpin,
+{
+    use std::mem;
+
+    // The serializer is also used for messages that have been boxed as an 'Any'
+    let typed_serializer                                = Arc::new(typed_serializer);
+    let any_typed_serializer                            = Arc::clone(&typed_serializer);
+    let any_serializer: AnySerializer<TSerializedType>  = Box::new(move |message| {
+        let message = message.downcast::<TMessageType>()?;
+        (*any_typed_serializer)(*message).map_err(|message| -> Box<dyn Send + Any> { Box::new(message) })
+    });
+    let any_serializer: Arc<dyn Send + Sync + Any>      = Arc::new(any_serializer);
+
     // Convert to boxed functions
-    let typed_serializer: Box<dyn Send + Sync + Fn(TMessageType) -> Result<SerializedMessage<TSerializer::Ok>, TMessageType>>                           = Box::new(typed_serializer);
-    let typed_deserializer: Box<dyn Send + Sync + Fn(SerializedMessage<TSerializer::Ok>) -> Result<TMessageType, SerializedMessage<TSerializer::Ok>>>   = Box::new(typed_deserializer);
+    let typed_serializer: Box<dyn Send + Sync + Fn(TMessageType) -> Result<SerializedMessage<TSerializedType>, TMessageType>>                           = Box::new(move |message| (*typed_serializer)(message));
+    let typed_deserializer: Box<dyn Send + Sync + Fn(SerializedMessage<TSerializedType>) -> Result<TMessageType, SerializedMessage<TSerializedType>>>   = Box::new(typed_deserializer);
 
     // Set as an 'any' type for storage
     let typed_serializer: Arc<dyn Send + Sync + Any>    = Arc::new(typed_serializer);
@@ -149,14 +378,32 @@ where
     // Store the serializer and deserializer in the typed serializers list
     let mut typed_serializers = (*TYPED_SERIALIZERS).write().unwrap();
 
-    typed_serializers.insert((TypeId::of::<TMessageType>(), TypeId::of::<SerializedMessage<TSerializer::Ok>>()), typed_serializer);
-    typed_serializers.insert((TypeId::of::<SerializedMessage<TSerializer::Ok>>(), TypeId::of::<TMessageType>()), typed_deserializer);
+    typed_serializers.insert((TypeId::of::<TMessageType>(), TypeId::of::<SerializedMessage<TSerializedType>>()), typed_serializer);
+    typed_serializers.insert((TypeId::of::<SerializedMessage<TSerializedType>>(), TypeId::of::<TMessageType>()), typed_deserializer);
+    mem::drop(typed_serializers);
 
-    (*STREAM_ID_FOR_SERIALIZABLE_TYPE).write().unwrap().insert(type_name.clone(), StreamId::with_message_type::<TMessageType>());
+    (*ANY_SERIALIZERS).write().unwrap().insert((TypeId::of::<TMessageType>(), TypeId::of::<SerializedMessage<TSerializedType>>()), any_serializer);
 
-    // TODO: for any type where the type name does not begin with a known suffix (query:: or subscribe::), add the query and subscribe versios
+    // Subscriptions can be made by type name, with the events sent through a serializer filter
+    let serialized_subscriber: SerializedSubscriber     = Box::new(|context, target_program| {
+        let filter      = serializer_filter::<TMessageType, SerializedMessage<TSerializedType>>();
+        let subscribe   = context.send::<Subscribe<TMessageType>>(());
 
-    Ok(())
+        async move {
+            let filter          = filter.map_err(|_| ConnectionError::FilterMappingMissing)?;
+            let mut subscribe   = subscribe?;
+
+            subscribe.send(Subscribe::with_target(StreamTarget::Filtered(filter, target_program))).await?;
+
+            Ok(())
+        }.boxed()
+    });
+    let serialized_subscriber: Arc<dyn Send + Sync + Any> = Arc::new(serialized_subscriber);
+
+    (*SERIALIZED_SUBSCRIBERS).write().unwrap().insert((TypeId::of::<TMessageType>(), TypeId::of::<SerializedMessage<TSerializedType>>()), serialized_subscriber);
+
+    // Store the stream ID so the type can be looked up by name later on (this also registers the stream type functions for TMessageType)
+    (*STREAM_ID_FOR_SERIALIZABLE_TYPE).write().unwrap().insert(type_name, StreamId::with_message_type::<TMessageType>());
 }
 
 ///
@@ -188,11 +435,7 @@ where
         }?;
 
         // Create a filter that uses the stored serializer
-        let filter_handle = FilterHandle::for_filter(move |input_messages| {
-            let typed_serializer = Arc::clone(&typed_serializer);
-
-            input_messages.flat_map(move |msg| stream::iter((*typed_serializer)(msg).ok()))
-        });
+        let filter_handle = FilterHandle::filter_map(move |msg| (*typed_serializer)(msg).ok());
 
         // Store for future use
         filters_for_type.insert(message_type, filter_handle);
@@ -202,6 +445,31 @@ where
     }
 }
 
+///
+/// Sends a `Subscribe` message for a serializable stream, requesting that its events are sent to a program as `SerializedMessage<TSerializedType>` messages
+///
+/// The stream ID is usually found from a type name using `StreamId::with_serialization_type()`, which makes it possible to subscribe
+/// to events without knowing their type. The `Subscribe` message is sent from the program that owns the context to wherever the
+/// `Subscribe` stream for the message type is connected, and the events are converted using the same filter that `serializer_filter()`
+/// returns. The target program should have an input stream of `SerializedMessage<TSerializedType>`.
+///
+/// This returns `ConnectionError::StreamNotKnown` if the message type has not been installed as a serializable type for this serializer.
+///
+pub fn subscribe_serialized<TSerializedType>(context: &SceneContext, stream_id: &StreamId, target_program: SubProgramId) -> impl 'static + Send + Future<Output=Result<(), ConnectionError>>
+where
+    TSerializedType: 'static + Send + Unpin,
+{
+    let serialized_subscriber = (*SERIALIZED_SUBSCRIBERS).read().unwrap()
+        .get(&(stream_id.message_type(), TypeId::of::<SerializedMessage<TSerializedType>>()))
+        .cloned()
+        .and_then(|serialized_subscriber| serialized_subscriber.downcast::<SerializedSubscriber>().ok());
+
+    match serialized_subscriber {
+        Some(serialized_subscriber) => (*serialized_subscriber)(context, target_program),
+        None                        => future::ready(Err(ConnectionError::StreamNotKnown)).boxed(),
+    }
+}
+
 ///
 /// Like a scene but 
 ///
@@ -252,6 +520,114 @@ where
 
         self
     }
+
+    ///
+    /// Sets a program that receives the messages that could not be delivered in this scene, as `DeadLetter<TSerializer::Ok>` messages
+    ///
+    /// Once this is set, an output that is connected to a program that is not in the scene (for example because it has never been
+    /// started or because it has finished) will forward its messages to the dead-letter program instead of producing an error. This
+    /// only applies to message types that have been installed as serializable types for this serializer: other message types will
+    /// produce an error as usual.
+    ///
+    /// Senders never wait for the dead-letter program, so if its input stream is full the message is treated as undeliverable and
+    /// the sender gets an error. Use `InputStream::set_max_waiting()` in the dead-letter program to allow more messages to queue up.
+    ///
+    pub fn with_dead_letter_target(self, dead_letter_program: SubProgramId) -> Self {
+        let handler: DeadLetterHandler = Arc::new(move |scene_core, source, target, message| {
+            send_dead_letter::<TSerializer::Ok>(scene_core, dead_letter_program, source, target, message)
+        });
+
+        self.0.core().lock().unwrap().set_dead_letter_handler(Some(handler));
+
+        self
+    }
+}
+
+///
+/// Serializes a message and sends it to the input of a dead-letter program, returning the message if it can't be sent
+///
+fn send_dead_letter<TSerializedType>(scene_core: &Arc<Mutex<SceneCore>>, dead_letter_program: SubProgramId, source: SubProgramId, target: StreamTarget, message: Box<dyn Send + Any>) -> Result<(), Box<dyn Send + Any>>
+where
+    TSerializedType: 'static + Send + Unpin,
+{
+    use std::mem;
+
+    // Fetch the serializer for the message
+    let message_type    = (*message).type_id();
+    let any_serializer  = (*ANY_SERIALIZERS).read().unwrap()
+        .get(&(message_type, TypeId::of::<SerializedMessage<TSerializedType>>()))
+        .cloned()
+        .and_then(|any_serializer| any_serializer.downcast::<AnySerializer<TSerializedType>>().ok());
+    let any_serializer  = if let Some(any_serializer) = any_serializer { any_serializer } else { return Err(message); };
+
+    // Fetch the input stream for the dead-letter program
+    let input_core  = scene_core.lock().unwrap().get_input_stream_core(dead_letter_program)
+        .and_then(|input_core| input_core.downcast::<Mutex<InputStreamCore<DeadLetter<TSerializedType>>>>().ok());
+    let input_core  = if let Some(input_core) = input_core { input_core } else { return Err(message); };
+
+    // Dead letters can't wait for space, so the message is returned if the dead-letter program can't accept it right away (this keeps
+    // the dead-letter queue to the size set for its input stream). The input stays locked so the space can't be used up before we send.
+    let mut input_core = input_core.lock().unwrap();
+
+    if input_core.is_closed() || input_core.is_blocked() || input_core.is_queue_full() {
+        return Err(message);
+    }
+
+    // Serialize the message and send it to the dead-letter program
+    let serialized_message  = (*any_serializer)(message)?;
+    let waker               = input_core.send(source, DeadLetter { target, serialized_message });
+    mem::drop(input_core);
+
+    if let Ok(Some(waker)) = waker {
+        waker.wake();
+    }
+
+    Ok(())
+}
+
+impl Scene {
+    ///
+    /// Adds filters to support serializing and deserializing the specified message type to a binary format
+    ///
+    /// The message type is serialized to and from `SerializedMessage<Vec<u8>>` using the supplied functions. The name passed in
+    /// here must be unique for the message type, or an error will be produced
+    ///
+    pub fn with_binary_serializable_type<TMessageType, TSerializeError, TDeserializeError>(
+        &self, 
+        type_name:      impl Into<String>, 
+        serialize:      impl 'static + Send + Sync + Fn(&TMessageType) -> Result<Vec<u8>, TSerializeError>, 
+        deserialize:    impl 'static + Send + Sync + Fn(&[u8]) -> Result<TMessageType, TDeserializeError>
+    ) -> &Self
+    where
+        TMessageType: 'static + SceneMessage,
+    {
+        // Install the serializers for this type if they aren't already
+        install_binary_serializable_type(type_name, serialize, deserialize).unwrap();
+
+        // Create filters
+        let serialize_filter    = serializer_filter::<TMessageType, SerializedMessage<Vec<u8>>>().unwrap();
+        let deserialize_filter  = serializer_filter::<SerializedMessage<Vec<u8>>, TMessageType>().unwrap();
+
+        self.connect_programs(StreamSource::Filtered(serialize_filter), (), StreamId::with_message_type::<TMessageType>()).ok();
+        self.connect_programs(StreamSource::Filtered(deserialize_filter), (), StreamId::with_message_type::<SerializedMessage<Vec<u8>>>()).ok();
+
+        self
+    }
+
+    ///
+    /// Adds filters to support serializing and deserializing the specified message type using the postcard binary format
+    ///
+    #[cfg(feature="postcard")]
+    pub fn with_postcard_serializable_type<TMessageType>(&self, type_name: impl Into<String>) -> &Self
+    where
+        TMessageType: 'static + SceneMessage,
+        TMessageType: for<'a> Deserialize<'a>,
+        TMessageType: Serialize,
+    {
+        self.with_binary_serializable_type(type_name, 
+            |msg: &TMessageType| postcard::to_allocvec(msg), 
+            |bytes: &[u8]| postcard::from_bytes::<TMessageType>(bytes))
+    }
 }
 
 impl<'a, TSerializer> Deref for SceneWithSerializer<'a, TSerializer> {
@@ -273,6 +649,10 @@ impl StreamId {
     ///
     /// Changes a serialization name into a stream ID
     ///
+    /// This is the reverse of `serialization_type_name()`: the name must have been registered with `install_serializable_type()`,
+    /// and the result is the same stream ID as would be returned by `StreamId::with_message_type()` for the type that was
+    /// registered. `None` is returned for names that are not known.
+    ///
     pub fn with_serialization_type(type_name: impl Into<String>) -> Option<Self> {
         (*STREAM_ID_FOR_SERIALIZABLE_TYPE).read().unwrap().get(&type_name.into()).cloned()
     }
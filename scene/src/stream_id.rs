@@ -177,8 +177,17 @@ impl StreamTypeFunctions {
     where
         TMessageType: 'static + SceneMessage,
     {
-        let type_id                     = TypeId::of::<TMessageType>();
-        let mut stream_type_functions   = STREAM_TYPE_FUNCTIONS.write().unwrap();
+        let type_id = TypeId::of::<TMessageType>();
+
+        // Types are usually already registered, which only needs a read lock to check
+        if STREAM_TYPE_FUNCTIONS.read().unwrap().contains_key(&type_id) {
+            return;
+        }
+
+        #[cfg(test)]
+        test::WRITE_LOCKS.with(|write_locks| write_locks.set(write_locks.get() + 1));
+
+        let mut stream_type_functions = STREAM_TYPE_FUNCTIONS.write().unwrap();
 
         stream_type_functions.entry(type_id)
             .or_insert_with(|| StreamTypeFunctions::for_message_type::<TMessageType>());
@@ -239,6 +248,20 @@ impl StreamTypeFunctions {
 }
 
 impl StreamId {
+    ///
+    /// Registers the functions used to connect streams of a particular message type
+    ///
+    /// This happens automatically the first time `with_message_type()` is called for a type, which needs to briefly take a
+    /// global write lock. Calling this up front (for example, while a scene is being set up) means that creating stream IDs
+    /// later on will only ever need a read lock.
+    ///
+    pub fn preregister<TMessageType>()
+    where
+        TMessageType: 'static + SceneMessage,
+    {
+        StreamTypeFunctions::add::<TMessageType>();
+    }
+
     ///
     /// ID of a stream that generates a particular type of data
     ///
@@ -306,6 +329,13 @@ impl StreamId {
         self.message_type_name.into()
     }
 
+    ///
+    /// The name of the Rust type for this stream, as a static string
+    ///
+    pub (crate) fn static_message_type_name(&self) -> &'static str {
+        self.message_type_name
+    }
+
     ///
     /// Returns the default target defined for the message type represented by this stream ID
     ///
@@ -427,3 +457,41 @@ impl StreamId {
         }
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    use std::cell::{Cell};
+
+    thread_local! {
+        /// The number of times the stream type functions were locked for writing by the current thread
+        pub (super) static WRITE_LOCKS: Cell<usize> = const { Cell::new(0) };
+    }
+
+    #[test]
+    fn first_use_registers_type() {
+        struct FirstUseMessage;
+        impl SceneMessage for FirstUseMessage { }
+
+        let initial_locks = WRITE_LOCKS.with(|write_locks| write_locks.get());
+        StreamId::with_message_type::<FirstUseMessage>();
+
+        assert!(WRITE_LOCKS.with(|write_locks| write_locks.get()) == initial_locks + 1);
+    }
+
+    #[test]
+    fn preregistered_type_does_not_write_lock() {
+        struct PreregisteredMessage;
+        impl SceneMessage for PreregisteredMessage { }
+
+        StreamId::preregister::<PreregisteredMessage>();
+
+        let initial_locks = WRITE_LOCKS.with(|write_locks| write_locks.get());
+        for _ in 0..10 {
+            StreamId::with_message_type::<PreregisteredMessage>();
+        }
+
+        assert!(WRITE_LOCKS.with(|write_locks| write_locks.get()) == initial_locks);
+    }
+}
@@ -0,0 +1,70 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+///
+/// A snapshot of the traffic that has passed through the input stream for a subprogram
+///
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct StreamStats {
+    /// The number of messages that have been read from the stream by the subprogram
+    pub delivered: usize,
+
+    /// The number of messages that were discarded by the stream (when it's using one of the lossy `InputStreamMode`s)
+    pub dropped: usize,
+
+    /// The number of messages that are currently waiting to be read
+    pub pending: usize,
+}
+
+///
+/// Counters that track the messages passing through an input stream
+///
+/// These are atomic so that they can be read without locking the input stream core.
+///
+#[derive(Default)]
+pub (crate) struct StreamCounters {
+    delivered:  AtomicUsize,
+    dropped:    AtomicUsize,
+    pending:    AtomicUsize,
+}
+
+impl StreamCounters {
+    ///
+    /// Records that a message has been added to the waiting queue
+    ///
+    #[inline]
+    pub (crate) fn message_queued(&self) {
+        self.pending.fetch_add(1, Ordering::Relaxed);
+    }
+
+    ///
+    /// Records that a message has been removed from the waiting queue and returned to the subprogram
+    ///
+    #[inline]
+    pub (crate) fn message_delivered(&self) {
+        self.pending.fetch_sub(1, Ordering::Relaxed);
+        self.delivered.fetch_add(1, Ordering::Relaxed);
+    }
+
+    ///
+    /// Records that a message was discarded (`was_queued` is true if the message was removed from the waiting queue)
+    ///
+    #[inline]
+    pub (crate) fn message_dropped(&self, was_queued: bool) {
+        if was_queued {
+            self.pending.fetch_sub(1, Ordering::Relaxed);
+        }
+
+        self.dropped.fetch_add(1, Ordering::Relaxed);
+    }
+
+    ///
+    /// Reads the current values of the counters
+    ///
+    pub (crate) fn stats(&self) -> StreamStats {
+        StreamStats {
+            delivered:  self.delivered.load(Ordering::Relaxed),
+            dropped:    self.dropped.load(Ordering::Relaxed),
+            pending:    self.pending.load(Ordering::Relaxed),
+        }
+    }
+}
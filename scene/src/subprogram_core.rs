@@ -2,6 +2,7 @@ use crate::output_sink::*;
 use crate::process_core::*;
 use crate::scene_message::*;
 use crate::stream_id::*;
+use crate::stream_stats::*;
 use crate::subprogram_id::*;
 
 use futures::task::{Waker};
@@ -40,6 +41,9 @@ pub (crate) struct SubProgramCore {
 
     /// The ID assigned to the next command that this subprogram will launch (shared with any commands launched by this program)
     pub (super) next_command_sequence: Arc<AtomicUsize>,
+
+    /// The counters for the messages passing through the input stream of this subprogram
+    pub (super) input_counters: Arc<StreamCounters>,
 }
 
 impl SubProgramCore {
@@ -1,7 +1,11 @@
+use crate::error::*;
+
 use uuid::{Uuid};
 use once_cell::sync::{OnceCell, Lazy};
 
+use std::fmt;
 use std::ops::{Deref};
+use std::str::{FromStr};
 use std::collections::*;
 use std::sync::*;
 
@@ -91,11 +95,64 @@ impl SubProgramId {
     ///
     /// Creates a subprogram ID with a well-known name
     ///
+    /// Names are interned for the lifetime of the process: every distinct name that's passed to this function uses a small amount
+    /// of memory that is never reclaimed. This is fine for the usual case where subprograms have a fixed set of well-known names,
+    /// but code that generates many unique subprograms (for example, long-running processes or large test suites) should prefer
+    /// `SubProgramId::new()`, which does not allocate a name.
+    ///
     #[inline]
     pub fn called(name: &str) -> SubProgramId {
         SubProgramId(SubProgramIdValue::Named(id_for_name(name)))
     }
 
+    ///
+    /// Returns the number of distinct names that have been registered using `SubProgramId::called()`
+    ///
+    /// As names are never released, this can be used to detect code that is creating an unbounded number of named subprograms.
+    ///
+    pub fn registered_name_count() -> usize {
+        (*NAMES_FOR_IDS).read().unwrap().len()
+    }
+
+    ///
+    /// True if this identifies a task launched by a subprogram (eg, by `SceneContext::spawn_command()`)
+    ///
+    pub fn is_task(&self) -> bool {
+        match self.0 {
+            SubProgramIdValue::NamedTask(_, _)  |
+            SubProgramIdValue::GuidTask(_, _)   => true,
+
+            SubProgramIdValue::Named(_)         |
+            SubProgramIdValue::Guid(_)          => false,
+        }
+    }
+
+    ///
+    /// If this identifies a task, returns the ID of the subprogram that launched it
+    ///
+    pub fn parent_program(&self) -> Option<SubProgramId> {
+        match self.0 {
+            SubProgramIdValue::NamedTask(name_num, _)   => Some(SubProgramId(SubProgramIdValue::Named(name_num))),
+            SubProgramIdValue::GuidTask(guid, _)        => Some(SubProgramId(SubProgramIdValue::Guid(guid))),
+
+            SubProgramIdValue::Named(_)                 |
+            SubProgramIdValue::Guid(_)                  => None,
+        }
+    }
+
+    ///
+    /// If this identifies a task, returns the serial number that distinguishes it from the other tasks launched by the same subprogram
+    ///
+    pub fn task_serial(&self) -> Option<usize> {
+        match self.0 {
+            SubProgramIdValue::NamedTask(_, serial) |
+            SubProgramIdValue::GuidTask(_, serial)  => Some(serial),
+
+            SubProgramIdValue::Named(_)             |
+            SubProgramIdValue::Guid(_)              => None,
+        }
+    }
+
     ///
     /// Creates a command subprogram ID (with a particular sequence number)
     ///
@@ -110,6 +167,86 @@ impl SubProgramId {
     }
 }
 
+impl fmt::Display for SubProgramNameId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if let Some(name) = name_for_id(*self) {
+            write!(f, "{}", name)
+        } else {
+            // Names are never released, so this should only happen if an ID is somehow created without registering it
+            write!(f, "#{}", self.0)
+        }
+    }
+}
+
+impl fmt::Display for SubProgramIdValue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SubProgramIdValue::Named(name)              => write!(f, "named:{}", name),
+            SubProgramIdValue::Guid(guid)               => write!(f, "guid:{}", guid),
+            SubProgramIdValue::NamedTask(name, serial)  => write!(f, "task({},{})", name, serial),
+            SubProgramIdValue::GuidTask(guid, serial)   => write!(f, "guidtask({},{})", guid, serial),
+        }
+    }
+}
+
+///
+/// Subprogram IDs are displayed as `named:<name>`, `guid:<uuid>`, `task(<name>,<serial>)` or `guidtask(<uuid>,<serial>)`
+///
+impl fmt::Display for SubProgramId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl FromStr for SubProgramId {
+    type Err = SubProgramIdParseError;
+
+    ///
+    /// Parses a subprogram ID in the format generated by its `Display` implementation
+    ///
+    /// Names are registered as they're parsed, so this will return the same ID as `SubProgramId::called()` for a `named:` string
+    ///
+    fn from_str(id: &str) -> Result<SubProgramId, SubProgramIdParseError> {
+        fn parse_name(name: &str) -> Result<SubProgramNameId, SubProgramIdParseError> {
+            if name.is_empty() {
+                Err(SubProgramIdParseError::MissingName)
+            } else {
+                Ok(id_for_name(name))
+            }
+        }
+
+        fn parse_guid(guid: &str) -> Result<Uuid, SubProgramIdParseError> {
+            Uuid::parse_str(guid).map_err(|_| SubProgramIdParseError::InvalidGuid(guid.into()))
+        }
+
+        fn parse_task<'a>(id: &str, task: &'a str) -> Result<(&'a str, usize), SubProgramIdParseError> {
+            // Tasks are formatted as '(parent,serial)': the parent might be a name with a ',' in it, so the serial is after the last comma
+            let (parent, serial) = task.strip_prefix('(')
+                .and_then(|task| task.strip_suffix(')'))
+                .and_then(|task| task.rsplit_once(','))
+                .ok_or_else(|| SubProgramIdParseError::UnrecognisedFormat(id.into()))?;
+
+            let serial = serial.parse::<usize>().map_err(|_| SubProgramIdParseError::InvalidSerial(serial.into()))?;
+
+            Ok((parent, serial))
+        }
+
+        if let Some(name) = id.strip_prefix("named:") {
+            Ok(SubProgramId(SubProgramIdValue::Named(parse_name(name)?)))
+        } else if let Some(guid) = id.strip_prefix("guid:") {
+            Ok(SubProgramId(SubProgramIdValue::Guid(parse_guid(guid)?)))
+        } else if let Some(task) = id.strip_prefix("task") {
+            let (name, serial) = parse_task(id, task)?;
+            Ok(SubProgramId(SubProgramIdValue::NamedTask(parse_name(name)?, serial)))
+        } else if let Some(task) = id.strip_prefix("guidtask") {
+            let (guid, serial) = parse_task(id, task)?;
+            Ok(SubProgramId(SubProgramIdValue::GuidTask(parse_guid(guid)?, serial)))
+        } else {
+            Err(SubProgramIdParseError::UnrecognisedFormat(id.into()))
+        }
+    }
+}
+
 impl StaticSubProgramId {
     ///
     /// Creates a subprogram ID with a well-known name
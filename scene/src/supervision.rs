@@ -0,0 +1,103 @@
+use crate::input_stream::*;
+use crate::scene_context::*;
+use crate::scene_message::*;
+
+use futures::prelude::*;
+use futures_timer::{Delay};
+
+use std::collections::{VecDeque};
+use std::panic::{AssertUnwindSafe};
+use std::time::{Duration, Instant};
+
+///
+/// Describes when a supervised subprogram should be restarted after it fails
+///
+/// A supervised program fails if its future returns an error or panics. Programs that finish successfully, or whose input
+/// stream has been closed, are never restarted.
+///
+/// Restarts are delayed so that a program that keeps failing can't starve the rest of the scene: the delay starts at 1ms and
+/// doubles each time the program fails again, up to a maximum of 1s. It's reset once a program has run for longer than the
+/// maximum delay before failing.
+///
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RestartPolicy {
+    /// The program is not restarted when it fails
+    Never,
+
+    /// The program is always restarted when it fails
+    Always,
+
+    /// The program is restarted at most the specified number of times within the specified time window
+    MaxRetries(usize, Duration),
+}
+
+impl RestartPolicy {
+    ///
+    /// Returns true if a program should be restarted, given the times of its previous restarts (which will be updated if it should)
+    ///
+    fn should_restart(&self, restarts: &mut VecDeque<Instant>) -> bool {
+        match self {
+            RestartPolicy::Never    => false,
+            RestartPolicy::Always   => true,
+
+            RestartPolicy::MaxRetries(max_retries, window) => {
+                // Forget about any restarts that are outside of the window
+                let now = Instant::now();
+                while restarts.front().map(|restart_time| now.duration_since(*restart_time) > *window).unwrap_or(false) {
+                    restarts.pop_front();
+                }
+
+                if restarts.len() < *max_retries {
+                    restarts.push_back(now);
+                    true
+                } else {
+                    false
+                }
+            }
+        }
+    }
+}
+
+/// The delay before a failed program is restarted for the first time
+const MIN_RESTART_DELAY: Duration = Duration::from_millis(1);
+
+/// The longest delay before restarting a program that keeps failing
+const MAX_RESTART_DELAY: Duration = Duration::from_secs(1);
+
+///
+/// Runs a supervised subprogram, restarting it according to the policy whenever it fails
+///
+pub (crate) async fn run_supervised<TProgramFn, TInputMessage, TFuture, TError>(input: InputStream<TInputMessage>, context: SceneContext, program: TProgramFn, policy: RestartPolicy)
+where
+    TFuture:        Send + Future<Output=Result<(), TError>>,
+    TInputMessage:  SceneMessage,
+    TProgramFn:     Send + Fn(InputStream<TInputMessage>, SceneContext) -> TFuture,
+{
+    let mut restarts        = VecDeque::new();
+    let mut restart_delay   = MIN_RESTART_DELAY;
+
+    loop {
+        // Each run of the program reads from the same input, which stays open if the program fails
+        let started         = Instant::now();
+        let program_input   = input.reattach();
+        let succeeded       = matches!(AssertUnwindSafe(program(program_input, context.clone())).catch_unwind().await, Ok(Ok(())));
+
+        // Programs that finished successfully are not restarted
+        if succeeded {
+            break;
+        }
+
+        // There's no point restarting a program that can't receive any more messages
+        if input.is_closed() || !policy.should_restart(&mut restarts) {
+            break;
+        }
+
+        // Back off if the program keeps failing, so it doesn't stop the other programs in the scene from running
+        if started.elapsed() > MAX_RESTART_DELAY {
+            restart_delay = MIN_RESTART_DELAY;
+        }
+
+        Delay::new(restart_delay).await;
+        restart_delay = (restart_delay * 2).min(MAX_RESTART_DELAY);
+    }
+}
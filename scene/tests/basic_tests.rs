@@ -9,12 +9,14 @@ use flo_scene::*;
 use flo_scene::programs::*;
 
 use futures::prelude::*;
-use futures::future::{select, join};
+use futures::future::{select, join, Either};
 use futures::executor;
+use futures::channel::oneshot;
 use futures_timer::*;
 
 use std::time::{Duration};
 use std::sync::*;
+use std::any::{TypeId, type_name};
 
 #[test]
 fn run_subprogram_and_stop_when_scene_is_empty() {
@@ -471,3 +473,642 @@ fn send_message_with_thread_stealing() {
     assert!(*received_immediate.lock().unwrap() == 3, "Expected to have processed 3 messages immediated (processed: {:?})", *received_immediate.lock().unwrap());
     assert!(finished, "Scene did not finish");
 }
+
+#[test]
+fn full_input_buffer_blocks_sender() {
+    // Counts the messages that the sender has sent, and records the messages the receiver got
+    let num_sent    = Arc::new(Mutex::new(0));
+    let received    = Arc::new(Mutex::new(vec![]));
+
+    // Create a scene with two subprograms. The receiver can buffer 4 messages, and doesn't read anything until it's told to
+    let scene                       = Scene::empty();
+    let receiver                    = SubProgramId::new();
+    let sender                      = SubProgramId::new();
+    let (start_reading, can_read)   = oneshot::channel::<()>();
+
+    let recv_messages = received.clone();
+    scene.add_subprogram(receiver,
+        move |input: InputStream<usize>, _| async move {
+            can_read.await.unwrap();
+
+            let mut input = input;
+            for _ in 0..10 {
+                let message = input.next().await.unwrap();
+                recv_messages.lock().unwrap().push(message);
+            }
+        },
+        4);
+
+    let sent_messages = num_sent.clone();
+    scene.add_subprogram(sender,
+        move |_: InputStream<()>, context| async move {
+            let mut send_usize = context.send::<usize>(receiver).unwrap();
+
+            for message in 0..10 {
+                send_usize.send(message).await.unwrap();
+                *sent_messages.lock().unwrap() += 1;
+            }
+        },
+        0);
+
+    // Run the scene, and start the receiver once the sender has had time to fill up the buffer
+    let mut num_sent_before_reading = 0;
+    executor::block_on(select(join(scene.run_scene(), async {
+        Delay::new(Duration::from_millis(100)).await;
+
+        num_sent_before_reading = *num_sent.lock().unwrap();
+        start_reading.send(()).unwrap();
+    }).boxed(), Delay::new(Duration::from_millis(5000))));
+
+    // The buffer holds one more message than the maximum waiting count, and then the sender should wait for the receiver
+    assert!(num_sent_before_reading == 5, "Sent {} messages before the receiver started reading", num_sent_before_reading);
+
+    // Everything should be received once the receiver starts reading
+    assert!(*num_sent.lock().unwrap() == 10, "Sent {} messages", *num_sent.lock().unwrap());
+    assert!(*received.lock().unwrap() == (0..10).collect::<Vec<_>>(), "Received {:?}", *received.lock().unwrap());
+}
+
+#[test]
+fn increase_input_buffer_size() {
+    // Counts the messages that the sender has sent
+    let num_sent    = Arc::new(Mutex::new(0));
+
+    // Create a receiver that's added with no buffer but then increases its buffer size before it starts reading
+    let scene                       = Scene::empty();
+    let receiver                    = SubProgramId::new();
+    let sender                      = SubProgramId::new();
+    let (start_reading, can_read)   = oneshot::channel::<()>();
+
+    scene.add_subprogram(receiver,
+        move |input: InputStream<usize>, _| async move {
+            input.set_max_waiting(9);
+            can_read.await.unwrap();
+
+            let mut input = input;
+            for _ in 0..10 {
+                input.next().await.unwrap();
+            }
+        },
+        0);
+
+    let sent_messages = num_sent.clone();
+    scene.add_subprogram(sender,
+        move |_: InputStream<()>, context| async move {
+            let mut send_usize = context.send::<usize>(receiver).unwrap();
+
+            for message in 0..10 {
+                send_usize.send(message).await.unwrap();
+                *sent_messages.lock().unwrap() += 1;
+            }
+        },
+        0);
+
+    // All of the messages should fit in the buffer without the receiver reading anything
+    let mut num_sent_before_reading = 0;
+    executor::block_on(select(join(scene.run_scene(), async {
+        Delay::new(Duration::from_millis(100)).await;
+
+        num_sent_before_reading = *num_sent.lock().unwrap();
+        start_reading.send(()).unwrap();
+    }).boxed(), Delay::new(Duration::from_millis(5000))));
+
+    assert!(num_sent_before_reading == 10, "Sent {} messages before the receiver started reading", num_sent_before_reading);
+}
+
+///
+/// Sends 100 messages to a program with a lossy input stream that only starts reading after they've all been sent, returning the messages it receives
+///
+fn flood_lossy_input(mode: InputStreamMode) -> Vec<usize> {
+    let received    = Arc::new(Mutex::new(vec![]));
+    let sent_all    = Arc::new(Mutex::new(false));
+
+    // The receiver has space for 5 messages, and waits until the sender has finished before reading them
+    let scene                           = Scene::empty();
+    let receiver                        = SubProgramId::new();
+    let sender                          = SubProgramId::new();
+    let (finished_sending, can_read)    = oneshot::channel::<()>();
+
+    let recv_messages = received.clone();
+    scene.add_subprogram(receiver,
+        move |input: InputStream<usize>, _| {
+            // Set the mode before anything can be sent to the program
+            input.set_input_mode(mode);
+
+            async move {
+                can_read.await.unwrap();
+
+                let mut input = input;
+                for _ in 0..5 {
+                    let message = input.next().await.unwrap();
+                    recv_messages.lock().unwrap().push(message);
+                }
+            }
+        },
+        4);
+
+    // The sender should never have to wait for the receiver
+    let sent_all_messages = sent_all.clone();
+    scene.add_subprogram(sender,
+        move |_: InputStream<()>, context| async move {
+            let mut send_usize = context.send::<usize>(receiver).unwrap();
+
+            for message in 0..100 {
+                send_usize.send(message).await.unwrap();
+            }
+
+            *sent_all_messages.lock().unwrap() = true;
+            finished_sending.send(()).unwrap();
+        },
+        0);
+
+    executor::block_on(select(scene.run_scene().boxed(), Delay::new(Duration::from_millis(5000))));
+
+    assert!(*sent_all.lock().unwrap(), "Sender was blocked");
+
+    let received = received.lock().unwrap().clone();
+    received
+}
+
+#[test]
+fn drop_oldest_input() {
+    // Only the most recent messages should be kept
+    let received = flood_lossy_input(InputStreamMode::DropOldest);
+    assert!(received == vec![95, 96, 97, 98, 99], "Received {:?}", received);
+}
+
+#[test]
+fn drop_newest_input() {
+    // Only the messages that fitted in the buffer should be kept
+    let received = flood_lossy_input(InputStreamMode::DropNewest);
+    assert!(received == vec![0, 1, 2, 3, 4], "Received {:?}", received);
+}
+
+#[test]
+fn send_timeout_when_target_never_reads() {
+    let send_result = Arc::new(Mutex::new(None));
+
+    // Create a receiver that never reads its input, and has no space to buffer messages
+    let scene       = Scene::default();
+    let receiver    = SubProgramId::new();
+    scene.add_subprogram(receiver,
+        |input: InputStream<usize>, _| async move {
+            let _input = input;
+            future::pending::<()>().await;
+        },
+        0);
+
+    let result = send_result.clone();
+    scene.add_subprogram(SubProgramId::new(),
+        move |_: InputStream<()>, context| async move {
+            let mut send_usize = context.send::<usize>(receiver).unwrap();
+
+            // The first message fits in the input stream, but the second one will never be accepted
+            send_usize.send_timeout(1, Duration::from_millis(100)).await.unwrap();
+            *result.lock().unwrap() = Some(send_usize.send_timeout(2, Duration::from_millis(100)).await);
+
+            context.send_message(SceneControl::StopScene).await.unwrap();
+        },
+        0);
+
+    executor::block_on(select(scene.run_scene().boxed(), Delay::new(Duration::from_millis(5000))));
+
+    // The sender should get the message back in a timeout error
+    assert!(*send_result.lock().unwrap() == Some(Err(SceneSendError::Timeout(2))), "{:?}", *send_result.lock().unwrap());
+}
+
+#[test]
+fn list_running_programs() {
+    let programs = Arc::new(Mutex::new(vec![]));
+
+    // Create a scene with two programs that wait for input, and a program that lists what's running
+    let scene       = Scene::default();
+    let program_1   = SubProgramId::new();
+    let program_2   = SubProgramId::new();
+    let lister      = SubProgramId::new();
+
+    scene.add_subprogram(program_1, |mut input: InputStream<u32>, _| async move { while input.next().await.is_some() { } }, 0);
+    scene.add_subprogram(program_2, |mut input: InputStream<String>, _| async move { while input.next().await.is_some() { } }, 0);
+
+    let listed_programs = programs.clone();
+    scene.add_subprogram(lister,
+        move |_: InputStream<()>, context| async move {
+            *listed_programs.lock().unwrap() = context.list_programs();
+
+            context.send_message(SceneControl::StopScene).await.unwrap();
+        }, 0);
+
+    // Run this scene
+    executor::block_on(select(async {
+        scene.run_scene().await;
+    }.boxed(), Delay::new(Duration::from_millis(5000))));
+
+    // Check that the programs were listed with their input types
+    let programs = programs.lock().unwrap();
+    assert!(programs.contains(&(program_1, TypeId::of::<u32>(), type_name::<u32>())), "Program 1 missing: {:?}", programs);
+    assert!(programs.contains(&(program_2, TypeId::of::<String>(), type_name::<String>())), "Program 2 missing: {:?}", programs);
+    assert!(programs.contains(&(lister, TypeId::of::<()>(), type_name::<()>())), "Lister missing: {:?}", programs);
+    assert!(programs.iter().any(|(program_id, _, _)| *program_id == *SCENE_CONTROL_PROGRAM), "Scene control missing: {:?}", programs);
+}
+
+#[test]
+fn list_connections() {
+    let connections = Arc::new(Mutex::new(vec![]));
+
+    // Create a scene with a program that receives strings and a program that lists the connections
+    let scene       = Scene::default();
+    let program_1   = SubProgramId::new();
+    let program_2   = SubProgramId::new();
+    let lister      = SubProgramId::new();
+
+    scene.add_subprogram(program_1, |mut input: InputStream<String>, _| async move { while input.next().await.is_some() { } }, 0);
+
+    // Connect the strings from program_2 to program_1
+    scene.connect_programs(program_2, program_1, StreamId::with_message_type::<String>()).unwrap();
+
+    let listed_connections = connections.clone();
+    scene.add_subprogram(lister,
+        move |_: InputStream<()>, context| async move {
+            *listed_connections.lock().unwrap() = context.list_connections();
+
+            context.send_message(SceneControl::StopScene).await.unwrap();
+        }, 0);
+
+    // Run this scene
+    executor::block_on(select(async {
+        scene.run_scene().await;
+    }.boxed(), Delay::new(Duration::from_millis(5000))));
+
+    // The connection should be in the list
+    let connections = connections.lock().unwrap();
+    assert!(connections.contains(&(StreamSource::Program(program_2), StreamTarget::Program(program_1), StreamId::with_message_type::<String>())), "Connection missing: {:?}", connections);
+}
+
+#[test]
+fn fan_out_to_two_programs() {
+    let received = Arc::new(Mutex::new(vec![]));
+
+    // Create a scene with one program that sends to two others
+    let scene       = Scene::empty();
+    let receiver_1  = SubProgramId::new();
+    let receiver_2  = SubProgramId::new();
+    let sender      = SubProgramId::new();
+
+    for receiver in [receiver_1, receiver_2] {
+        let received = received.clone();
+
+        scene.add_subprogram(receiver, move |input: InputStream<usize>, _| async move {
+            let mut input = input;
+
+            for _ in 0..5 {
+                let message = input.next().await.unwrap();
+                received.lock().unwrap().push((receiver, message));
+            }
+        }, 0);
+    }
+
+    scene.add_subprogram(sender, |_: InputStream<()>, context| async move {
+        let mut output = context.send::<usize>(()).unwrap();
+
+        for message in 0..5 {
+            output.send(message).await.unwrap();
+        }
+    }, 0);
+
+    // Send the usize output of every program to both receivers
+    scene.connect_programs_fan_out::<usize>((), [receiver_1, receiver_2]).unwrap();
+
+    // Run this scene
+    executor::block_on(select(async {
+        scene.run_scene().await;
+    }.boxed(), Delay::new(Duration::from_millis(5000))));
+
+    // Both receivers should have received every message, in order
+    let received        = received.lock().unwrap();
+    let received_by_1   = received.iter().filter(|(id, _)| *id == receiver_1).map(|(_, msg)| *msg).collect::<Vec<_>>();
+    let received_by_2   = received.iter().filter(|(id, _)| *id == receiver_2).map(|(_, msg)| *msg).collect::<Vec<_>>();
+
+    assert!(received_by_1 == vec![0, 1, 2, 3, 4], "Receiver 1: {:?}", received);
+    assert!(received_by_2 == vec![0, 1, 2, 3, 4], "Receiver 2: {:?}", received);
+}
+
+#[test]
+fn fan_out_slow_target_does_not_block_current_message() {
+    let received = Arc::new(Mutex::new(vec![]));
+
+    // Create a scene with one program that sends to a fast receiver and a slow receiver
+    let scene       = Scene::empty();
+    let fast        = SubProgramId::new();
+    let slow        = SubProgramId::new();
+    let sender      = SubProgramId::new();
+
+    // The slow receiver waits until the fast one has received two messages before it starts reading
+    let (start_slow, wait_for_fast) = oneshot::channel::<()>();
+
+    let fast_received = received.clone();
+    scene.add_subprogram(fast, move |input: InputStream<usize>, _| async move {
+        let mut input       = input;
+        let mut start_slow  = Some(start_slow);
+
+        for count in 0..5 {
+            let message = input.next().await.unwrap();
+            fast_received.lock().unwrap().push((fast, message));
+
+            if count == 1 {
+                start_slow.take().unwrap().send(()).unwrap();
+            }
+        }
+    }, 0);
+
+    let slow_received = received.clone();
+    scene.add_subprogram(slow, move |input: InputStream<usize>, _| async move {
+        let mut input = input;
+
+        wait_for_fast.await.unwrap();
+
+        for _ in 0..5 {
+            let message = input.next().await.unwrap();
+            slow_received.lock().unwrap().push((slow, message));
+        }
+    }, 0);
+
+    scene.add_subprogram(sender, |_: InputStream<()>, context| async move {
+        let mut output = context.send::<usize>(()).unwrap();
+
+        for message in 0..5 {
+            output.send(message).await.unwrap();
+        }
+    }, 0);
+
+    scene.connect_programs_fan_out::<usize>(sender, [fast, slow]).unwrap();
+
+    // Run this scene
+    executor::block_on(select(async {
+        scene.run_scene().await;
+    }.boxed(), Delay::new(Duration::from_millis(5000))));
+
+    // The fast receiver gets its first two messages before the slow receiver reads anything, then both receive everything
+    let received        = received.lock().unwrap();
+    let received_fast   = received.iter().filter(|(id, _)| *id == fast).map(|(_, msg)| *msg).collect::<Vec<_>>();
+    let received_slow   = received.iter().filter(|(id, _)| *id == slow).map(|(_, msg)| *msg).collect::<Vec<_>>();
+
+    assert!(received[0..2] == [(fast, 0), (fast, 1)], "Received: {:?}", received);
+    assert!(received_fast == vec![0, 1, 2, 3, 4], "Fast: {:?}", received);
+    assert!(received_slow == vec![0, 1, 2, 3, 4], "Slow: {:?}", received);
+}
+
+#[test]
+fn fan_out_skips_finished_targets() {
+    let received = Arc::new(Mutex::new(vec![]));
+
+    // Create a scene with a receiver and a program that finishes straight away
+    let scene       = Scene::empty();
+    let finished    = SubProgramId::new();
+    let receiver    = SubProgramId::new();
+    let sender      = SubProgramId::new();
+    let starter     = SubProgramId::new();
+
+    let (has_finished, wait_for_finished) = oneshot::channel::<()>();
+
+    scene.add_subprogram(finished, move |_: InputStream<usize>, _| async move {
+        has_finished.send(()).unwrap();
+    }, 0);
+
+    let recv_messages = received.clone();
+    scene.add_subprogram(receiver, move |input: InputStream<usize>, _| async move {
+        let mut input = input;
+
+        for _ in 0..5 {
+            let message = input.next().await.unwrap();
+            recv_messages.lock().unwrap().push(message);
+        }
+    }, 0);
+
+    scene.connect_programs_fan_out::<usize>(sender, [finished, receiver]).unwrap();
+
+    // Start the sender once the first program has finished (so it reuses that program's slot in the scene)
+    let sender_scene = scene.clone();
+    scene.add_subprogram(starter, move |_: InputStream<()>, _| async move {
+        wait_for_finished.await.unwrap();
+        Delay::new(Duration::from_millis(50)).await;
+
+        sender_scene.add_subprogram(sender, |_: InputStream<()>, context| async move {
+            let mut output = context.send::<usize>(()).unwrap();
+
+            for message in 0..5 {
+                output.send(message).await.unwrap();
+            }
+        }, 0);
+    }, 0);
+
+    // Run this scene
+    executor::block_on(select(async {
+        scene.run_scene().await;
+    }.boxed(), Delay::new(Duration::from_millis(5000))));
+
+    // The receiver should get every message even though the other target has finished
+    let received = received.lock().unwrap();
+    assert!(*received == vec![0, 1, 2, 3, 4], "Received: {:?}", received);
+}
+
+#[test]
+fn priority_input() {
+    let received = Arc::new(Mutex::new(vec![]));
+
+    // The receiver treats messages >= 100 as high priority, and waits until the sender has finished before reading them
+    let scene                           = Scene::empty();
+    let receiver                        = SubProgramId::new();
+    let sender                          = SubProgramId::new();
+    let (finished_sending, can_read)    = oneshot::channel::<()>();
+
+    let recv_messages = received.clone();
+    scene.add_subprogram(receiver,
+        move |input: InputStream<usize>, _| {
+            input.set_priority(|message| if *message >= 100 { 1 } else { 0 });
+
+            async move {
+                can_read.await.unwrap();
+
+                let mut input = input;
+                for _ in 0..6 {
+                    let message = input.next().await.unwrap();
+                    recv_messages.lock().unwrap().push(message);
+                }
+            }
+        },
+        10);
+
+    // Interleave high and low priority messages
+    scene.add_subprogram(sender,
+        move |_: InputStream<()>, context| async move {
+            let mut send_usize = context.send::<usize>(receiver).unwrap();
+
+            for message in [0, 100, 1, 101, 2, 102] {
+                send_usize.send(message).await.unwrap();
+            }
+
+            finished_sending.send(()).unwrap();
+        },
+        0);
+
+    executor::block_on(select(scene.run_scene().boxed(), Delay::new(Duration::from_millis(5000))));
+
+    // High priority messages arrive first, and messages with the same priority arrive in order
+    let received = received.lock().unwrap().clone();
+    assert!(received == vec![100, 101, 102, 0, 1, 2], "Received {:?}", received);
+}
+
+#[test]
+fn one_shot_program_ends_after_one_message() {
+    let received = Arc::new(Mutex::new(vec![]));
+
+    // Create a scene with a program that receives a single message and a program that sends it
+    let scene       = Scene::empty();
+    let one_shot    = SubProgramId::new();
+    let sender      = SubProgramId::new();
+
+    let recv_messages = received.clone();
+    scene.add_subprogram(one_shot,
+        move |input: InputStream<usize>, _| async move {
+            if let Some(message) = input.recv_one().await {
+                recv_messages.lock().unwrap().push(message);
+            }
+        },
+        0);
+
+    scene.add_subprogram(sender,
+        move |_: InputStream<()>, context| async move {
+            context.send::<usize>(one_shot).unwrap().send(42).await.unwrap();
+        },
+        0);
+
+    // The scene should finish once both programs have finished (and not time out)
+    let finished = executor::block_on(select(scene.run_scene().boxed(), Delay::new(Duration::from_millis(5000)).boxed()));
+
+    assert!(matches!(finished, Either::Left(_)), "Scene did not finish");
+    assert!(*received.lock().unwrap() == vec![42], "Received {:?}", received.lock().unwrap());
+}
+
+#[test]
+fn reconnect_output_while_running() {
+    let received = Arc::new(Mutex::new(vec![]));
+
+    // Create a scene with a sender program that's initially connected to 'first_target'
+    let scene           = Scene::default();
+    let sender          = SubProgramId::new();
+    let first_target    = SubProgramId::new();
+    let second_target   = SubProgramId::new();
+
+    // The first target signals when it has received its message
+    let (first_received, wait_for_first) = oneshot::channel();
+    let recv_messages = received.clone();
+    scene.add_subprogram(first_target,
+        move |input: InputStream<String>, _| async move {
+            let mut input           = input;
+            let mut first_received  = Some(first_received);
+
+            while let Some(message) = input.next().await {
+                recv_messages.lock().unwrap().push((first_target, message));
+                first_received.take().map(|first_received| first_received.send(()));
+            }
+        },
+        0);
+
+    // The second target stops the scene once it has received a message
+    let recv_messages = received.clone();
+    scene.add_subprogram(second_target,
+        move |input: InputStream<String>, context| async move {
+            let mut input = input;
+
+            if let Some(message) = input.next().await {
+                recv_messages.lock().unwrap().push((second_target, message));
+            }
+
+            context.send_message(SceneControl::StopScene).await.unwrap();
+        },
+        0);
+
+    // The sender creates its output stream once, and sends a message each time it's triggered
+    scene.add_subprogram(sender,
+        move |input: InputStream<()>, context| async move {
+            let mut input   = input;
+            let mut output  = context.send::<String>(()).unwrap();
+            let mut count   = 0;
+
+            while let Some(()) = input.next().await {
+                count += 1;
+                output.send(format!("Message {}", count)).await.unwrap();
+            }
+        },
+        0);
+
+    scene.connect_programs(sender, first_target, StreamId::with_message_type::<String>()).unwrap();
+
+    // Send a message, then rewire the sender to the second target while the scene is running and send another
+    let mut trigger = scene.send_to_scene::<()>(sender).unwrap();
+
+    executor::block_on(select(async {
+        join(scene.run_scene(), async {
+            trigger.send(()).await.unwrap();
+            wait_for_first.await.unwrap();
+
+            scene.connect_programs(sender, second_target, StreamId::with_message_type::<String>()).unwrap();
+
+            trigger.send(()).await.unwrap();
+        }).await;
+    }.boxed(), Delay::new(Duration::from_millis(5000))));
+
+    let received = received.lock().unwrap().clone();
+    assert!(received == vec![(first_target, "Message 1".to_string()), (second_target, "Message 2".to_string())], "Received {:?}", received);
+}
+
+#[test]
+fn stream_stats_count_messages() {
+    let stats = Arc::new(Mutex::new(vec![]));
+
+    // The receiver has space for 5 messages, and drops the oldest message when it's full
+    let scene                           = Scene::empty();
+    let receiver                        = SubProgramId::new();
+    let sender                          = SubProgramId::new();
+    let (finished_sending, can_read)    = oneshot::channel::<()>();
+
+    let receiver_stats = stats.clone();
+    scene.add_subprogram(receiver,
+        move |input: InputStream<usize>, context| {
+            input.set_input_mode(InputStreamMode::DropOldest);
+
+            async move {
+                can_read.await.unwrap();
+
+                let mut input = input;
+                for _ in 0..5 {
+                    input.next().await.unwrap();
+                }
+
+                receiver_stats.lock().unwrap().push(context.stream_stats(receiver));
+            }
+        },
+        4);
+
+    // The sender sends 20 messages, then checks the stats before the receiver reads anything
+    let sender_stats = stats.clone();
+    scene.add_subprogram(sender,
+        move |_: InputStream<()>, context| async move {
+            let mut send_usize = context.send::<usize>(receiver).unwrap();
+
+            for message in 0..20 {
+                send_usize.send(message).await.unwrap();
+            }
+
+            sender_stats.lock().unwrap().push(context.stream_stats(receiver));
+            finished_sending.send(()).unwrap();
+        },
+        0);
+
+    executor::block_on(select(scene.run_scene().boxed(), Delay::new(Duration::from_millis(5000))));
+
+    let stats = stats.lock().unwrap().clone();
+    assert!(stats == vec![
+        Some(StreamStats { delivered: 0, dropped: 15, pending: 5 }),
+        Some(StreamStats { delivered: 5, dropped: 15, pending: 0 }),
+    ], "Stats: {:?}", stats);
+}
@@ -384,3 +384,52 @@ fn query_control_program() {
             })
         .run_in_scene_with_threads(&scene, test_program, 5);
 }
+
+#[test]
+fn panicking_program_generates_update() {
+    let scene           = Scene::default();
+    let panic_program   = SubProgramId::new();
+    let later_program   = SubProgramId::new();
+
+    // Create a program to monitor the updates for the scene, which sends a message to 'later_program' once the panic is reported
+    let update_monitor  = SubProgramId::new();
+    let recv_panics     = Arc::new(Mutex::new(vec![]));
+    let send_panics     = recv_panics.clone();
+    scene.add_subprogram(update_monitor,
+        move |mut input: InputStream<SceneUpdate>, context| async move {
+            while let Some(input) = input.next().await {
+                if let SceneUpdate::Panicked(program_id, message) = input {
+                    send_panics.lock().unwrap().push((program_id, message));
+
+                    context.send::<()>(later_program).unwrap().send(()).await.unwrap();
+                }
+            }
+        },
+        0);
+    scene.connect_programs((), update_monitor, StreamId::with_message_type::<SceneUpdate>()).unwrap();
+
+    // This program panics as soon as it starts
+    scene.add_subprogram(panic_program,
+        move |_: InputStream<()>, _| async move {
+            panic!("Program panicked");
+        },
+        0);
+
+    // The scene should keep running after the panic, so this program can stop it
+    let later_ran       = Arc::new(Mutex::new(false));
+    let set_later_ran   = later_ran.clone();
+    scene.add_subprogram(later_program,
+        move |mut input: InputStream<()>, context| async move {
+            input.next().await;
+            *set_later_ran.lock().unwrap() = true;
+
+            context.send_message(SceneControl::StopScene).await.unwrap();
+        },
+        0);
+
+    executor::block_on(select(scene.run_scene().boxed(), Delay::new(Duration::from_millis(5000))));
+
+    let recv_panics = recv_panics.lock().unwrap().clone();
+    assert!(recv_panics == vec![(panic_program, "Program panicked".to_string())], "Unexpected panics: {:?}", recv_panics);
+    assert!(*later_ran.lock().unwrap(), "Scene stopped after the panic");
+}
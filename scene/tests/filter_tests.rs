@@ -1125,3 +1125,157 @@ fn chain_with_direct_target() {
         .expect_message(|msg2: String| if msg2 != "Goodbyte".to_string() { Err(format!("Expected 'Goodbyte'")) } else { Ok(()) })
         .run_in_scene(&scene, test_program);
 }
+
+#[test]
+fn filtered_broadcast_to_two_programs() {
+    let recv_strings    = Arc::new(Mutex::new(vec![]));
+    let recv_totals     = Arc::new(Mutex::new(vec![]));
+
+    // Create a scene with one program that sends numbers to two programs that want them in different forms
+    let scene           = Scene::empty();
+    let string_program  = SubProgramId::new();
+    let total_program   = SubProgramId::new();
+    let sender          = SubProgramId::new();
+
+    let sent_strings = recv_strings.clone();
+    scene.add_subprogram(string_program, move |input: InputStream<String>, _| async move {
+        let mut input = input;
+
+        for _ in 0..4 {
+            let message = input.next().await.unwrap();
+            sent_strings.lock().unwrap().push(message);
+        }
+    }, 0);
+
+    let sent_totals = recv_totals.clone();
+    scene.add_subprogram(total_program, move |input: InputStream<u64>, _| async move {
+        let mut input = input;
+
+        for _ in 0..4 {
+            let message = input.next().await.unwrap();
+            sent_totals.lock().unwrap().push(message);
+        }
+    }, 0);
+
+    scene.add_subprogram(sender, |_: InputStream<()>, context| async move {
+        let mut output = context.send::<usize>(()).unwrap();
+
+        for message in 1..=4 {
+            output.send(message).await.unwrap();
+        }
+    }, 0);
+
+    // One target gets the numbers as strings, the other gets a running total
+    let usize_to_string = FilterHandle::for_filter(|number_stream: InputStream<usize>| number_stream.map(|num| num.to_string()));
+    let running_total   = FilterHandle::for_filter(|number_stream: InputStream<usize>| number_stream.scan(0u64, |total, num| { *total += num as u64; future::ready(Some(*total)) }));
+
+    scene.connect_filtered_broadcast::<usize>(sender, vec![(string_program.into(), usize_to_string), (total_program.into(), running_total)]).unwrap();
+
+    // Run this scene
+    executor::block_on(select(async {
+        scene.run_scene().await;
+    }.boxed(), Delay::new(Duration::from_millis(5000))));
+
+    // Each target should receive every message, passed through its own filter
+    let recv_strings    = recv_strings.lock().unwrap().clone();
+    let recv_totals     = recv_totals.lock().unwrap().clone();
+
+    assert!(recv_strings == vec!["1".to_string(), "2".to_string(), "3".to_string(), "4".to_string()], "Strings: {:?}", recv_strings);
+    assert!(recv_totals == vec![1, 3, 6, 10], "Totals: {:?}", recv_totals);
+}
+
+#[test]
+fn filtered_broadcast_rejects_mismatched_filter() {
+    let scene           = Scene::empty();
+    let string_program  = SubProgramId::new();
+    let sender          = SubProgramId::new();
+
+    scene.add_subprogram(string_program, |_: InputStream<String>, _| async move { }, 0);
+    scene.add_subprogram(sender, |_: InputStream<()>, _| async move { }, 0);
+
+    // The filter reads u32 values, but the broadcast sends usize values
+    let u32_to_string = FilterHandle::map(|num: u32| num.to_string());
+    let result        = scene.connect_filtered_broadcast::<usize>(sender, vec![(string_program.into(), u32_to_string)]);
+
+    assert!(result == Err(ConnectionError::FilterInputDoesNotMatch), "{:?}", result);
+
+    // Targets that are already filtered are not supported
+    let usize_to_string = FilterHandle::map(|num: usize| num.to_string());
+    let result          = scene.connect_filtered_broadcast::<usize>(sender, vec![(StreamTarget::Filtered(usize_to_string, string_program), usize_to_string)]);
+
+    assert!(result == Err(ConnectionError::UnexpectedConnectionType), "{:?}", result);
+}
+
+#[test]
+fn write_to_map_filter() {
+    let recv_messages = Arc::new(Mutex::new(vec![]));
+
+    // Create a scene with a program that receives strings, and a program that sends numbers to it through a map filter
+    let scene           = Scene::empty();
+    let string_program  = SubProgramId::new();
+    let number_program  = SubProgramId::new();
+    let usize_to_string = FilterHandle::map(|num: usize| num.to_string());
+
+    let sent_messages = recv_messages.clone();
+    scene.add_subprogram(string_program, move |input: InputStream<String>, _| async move {
+        let mut input = input;
+
+        for _ in 0..4 {
+            let message = input.next().await.unwrap();
+            sent_messages.lock().unwrap().push(message);
+        }
+    }, 0);
+
+    scene.add_subprogram(number_program, move |_: InputStream<()>, context| async move {
+        let mut filtered_output = context.send::<usize>(StreamTarget::Filtered(usize_to_string, string_program)).unwrap();
+
+        for num in 1..=4 {
+            filtered_output.send(num).await.unwrap();
+        }
+    }, 0);
+
+    executor::block_on(select(async {
+        scene.run_scene().await;
+    }.boxed(), Delay::new(Duration::from_millis(5000))));
+
+    // Every number should be converted to a string
+    let recv_messages = recv_messages.lock().unwrap().clone();
+    assert!(recv_messages == vec!["1".to_string(), "2".to_string(), "3".to_string(), "4".to_string()], "Received {:?}", recv_messages);
+}
+
+#[test]
+fn write_to_filter_map_filter() {
+    let recv_messages = Arc::new(Mutex::new(vec![]));
+
+    // Create a scene with a program that receives strings, and a program that sends numbers to it through a filter_map filter
+    let scene           = Scene::empty();
+    let string_program  = SubProgramId::new();
+    let number_program  = SubProgramId::new();
+    let even_to_string  = FilterHandle::filter_map(|num: usize| if num % 2 == 0 { Some(num.to_string()) } else { None });
+
+    let sent_messages = recv_messages.clone();
+    scene.add_subprogram(string_program, move |input: InputStream<String>, _| async move {
+        let mut input = input;
+
+        for _ in 0..3 {
+            let message = input.next().await.unwrap();
+            sent_messages.lock().unwrap().push(message);
+        }
+    }, 0);
+
+    scene.add_subprogram(number_program, move |_: InputStream<()>, context| async move {
+        let mut filtered_output = context.send::<usize>(StreamTarget::Filtered(even_to_string, string_program)).unwrap();
+
+        for num in 1..=6 {
+            filtered_output.send(num).await.unwrap();
+        }
+    }, 0);
+
+    executor::block_on(select(async {
+        scene.run_scene().await;
+    }.boxed(), Delay::new(Duration::from_millis(5000))));
+
+    // Only the even numbers should get through the filter
+    let recv_messages = recv_messages.lock().unwrap().clone();
+    assert!(recv_messages == vec!["2".to_string(), "4".to_string(), "6".to_string()], "Received {:?}", recv_messages);
+}
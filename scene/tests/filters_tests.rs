@@ -0,0 +1,247 @@
+//!
+//! Tests for the standard filters in the `filters` module
+//!
+
+use flo_scene::*;
+use flo_scene::filters::*;
+use flo_scene::programs::*;
+
+use futures::prelude::*;
+use futures::future::{select};
+use futures::executor;
+use futures_timer::*;
+
+use std::time::{Duration, Instant};
+use std::sync::*;
+
+#[test]
+fn rate_limit_delays_burst() {
+    let received = Arc::new(Mutex::new(vec![]));
+
+    // Create a scene with a program that receives a burst of messages through a rate limit of 100 messages per second
+    let scene       = Scene::empty();
+    let receiver    = SubProgramId::new();
+    let sender      = SubProgramId::new();
+    let rate_limit  = rate_limit_filter::<usize>(100, RateLimitMode::DelayExcess);
+
+    let recv_messages = received.clone();
+    scene.add_subprogram(receiver, move |input: InputStream<usize>, _| async move {
+        let mut input = input;
+
+        for _ in 0..20 {
+            let message = input.next().await.unwrap();
+            recv_messages.lock().unwrap().push((message, Instant::now()));
+        }
+    }, 0);
+
+    scene.add_subprogram(sender, move |_: InputStream<()>, context| async move {
+        let mut output = context.send::<usize>(StreamTarget::Filtered(rate_limit, receiver)).unwrap();
+
+        for message in 0..20 {
+            output.send(message).await.unwrap();
+        }
+    }, 0);
+
+    executor::block_on(select(scene.run_scene().boxed(), Delay::new(Duration::from_millis(5000))));
+
+    // Every message should be delivered, but should be spaced out by at least 10ms
+    let received    = received.lock().unwrap().clone();
+    let messages    = received.iter().map(|(message, _)| *message).collect::<Vec<_>>();
+
+    assert!(messages == (0..20).collect::<Vec<_>>(), "Received {:?}", messages);
+
+    let elapsed = received[19].1 - received[0].1;
+    assert!(elapsed >= Duration::from_millis(190), "Burst took {:?}", elapsed);
+}
+
+#[test]
+fn rate_limit_drops_burst() {
+    let received = Arc::new(Mutex::new(vec![]));
+
+    // Create a scene with a program that receives a burst of messages through a rate limit of 10 messages per second
+    let scene       = Scene::empty();
+    let receiver    = SubProgramId::new();
+    let sender      = SubProgramId::new();
+    let rate_limit  = rate_limit_filter::<usize>(10, RateLimitMode::DropExcess);
+
+    let recv_messages = received.clone();
+    scene.add_subprogram(receiver, move |input: InputStream<usize>, _| async move {
+        let mut input = input;
+
+        while let Some(message) = input.next().await {
+            recv_messages.lock().unwrap().push(message);
+
+            if message == 100 { break; }
+        }
+    }, 0);
+
+    scene.add_subprogram(sender, move |_: InputStream<()>, context| async move {
+        let mut output = context.send::<usize>(StreamTarget::Filtered(rate_limit, receiver)).unwrap();
+
+        // Send a burst of messages (only the first one should get through)
+        for message in 0..20 {
+            output.send(message).await.unwrap();
+        }
+
+        // Once the rate limit has passed, another message can be sent
+        Delay::new(Duration::from_millis(200)).await;
+        output.send(100).await.unwrap();
+    }, 0);
+
+    executor::block_on(select(scene.run_scene().boxed(), Delay::new(Duration::from_millis(5000))));
+
+    let received = received.lock().unwrap().clone();
+    assert!(received == vec![0, 100], "Received {:?}", received);
+}
+
+///
+/// Sends some messages through a filter to a program that stops reading when it receives `usize::MAX`, and returns the messages that were received
+///
+fn send_through_filter(filter: FilterHandle, messages: Vec<usize>) -> Vec<usize> {
+    let received = Arc::new(Mutex::new(vec![]));
+
+    let scene       = Scene::empty();
+    let receiver    = SubProgramId::new();
+    let sender      = SubProgramId::new();
+
+    let recv_messages = received.clone();
+    scene.add_subprogram(receiver, move |input: InputStream<usize>, _| async move {
+        let mut input = input;
+
+        while let Some(message) = input.next().await {
+            if message == usize::MAX { break; }
+
+            recv_messages.lock().unwrap().push(message);
+        }
+    }, 0);
+
+    scene.add_subprogram(sender, move |_: InputStream<()>, context| async move {
+        let mut output = context.send::<usize>(StreamTarget::Filtered(filter, receiver)).unwrap();
+
+        for message in messages {
+            output.send(message).await.unwrap();
+        }
+        output.send(usize::MAX).await.unwrap();
+    }, 0);
+
+    executor::block_on(select(scene.run_scene().boxed(), Delay::new(Duration::from_millis(5000))));
+
+    let received = received.lock().unwrap().clone();
+    received
+}
+
+#[test]
+fn dedupe_consecutive_messages() {
+    let dedupe      = dedupe_filter(|message: &usize| *message);
+    let received    = send_through_filter(dedupe, vec![1, 1, 2, 2, 2, 3, 1, 1, 3]);
+
+    assert!(received == vec![1, 2, 3, 1, 3], "Received {:?}", received);
+}
+
+#[test]
+fn dedupe_messages_by_key() {
+    // Messages with the same value divided by 10 are considered duplicates
+    let dedupe      = dedupe_filter(|message: &usize| *message / 10);
+    let received    = send_through_filter(dedupe, vec![10, 11, 12, 20, 25, 13]);
+
+    assert!(received == vec![10, 20, 13], "Received {:?}", received);
+}
+
+#[test]
+fn dedupe_messages_in_window() {
+    // Messages that match any of the last 3 delivered messages are discarded
+    let dedupe      = dedupe_window_filter(3, |message: &usize| *message);
+    let received    = send_through_filter(dedupe, vec![1, 2, 1, 3, 2, 1, 4, 1, 3, 2, 1]);
+
+    assert!(received == vec![1, 2, 3, 4, 1, 2], "Received {:?}", received);
+}
+
+///
+/// Runs a scene where a sender program sends messages through a batch filter, returning the batches that were received
+///
+/// The sender runs `send_messages` and the receiver reads `num_batches` batches before stopping the scene
+///
+fn receive_batches<TFuture>(filter: FilterHandle, num_batches: usize, send_messages: impl 'static + Send + FnOnce(OutputSink<usize>) -> TFuture) -> Vec<Vec<usize>>
+where
+    TFuture: 'static + Send + Future<Output=()>,
+{
+    let received = Arc::new(Mutex::new(vec![]));
+
+    let scene       = Scene::default();
+    let receiver    = SubProgramId::new();
+    let sender      = SubProgramId::new();
+
+    let recv_batches = received.clone();
+    scene.add_subprogram(receiver, move |input: InputStream<Vec<usize>>, context| async move {
+        let mut input = input;
+
+        for _ in 0..num_batches {
+            let batch = input.next().await.unwrap();
+            recv_batches.lock().unwrap().push(batch);
+        }
+
+        context.send_message(SceneControl::StopScene).await.unwrap();
+    }, 0);
+
+    scene.add_subprogram(sender, move |_: InputStream<()>, context| async move {
+        let output = context.send::<usize>(StreamTarget::Filtered(filter, receiver)).unwrap();
+
+        send_messages(output).await;
+    }, 0);
+
+    executor::block_on(select(scene.run_scene().boxed(), Delay::new(Duration::from_millis(5000))));
+
+    let received = received.lock().unwrap().clone();
+    received
+}
+
+#[test]
+fn batch_when_full() {
+    // The delay is long enough that only the batch size will cause a batch to be sent
+    let batch       = batch_filter::<usize>(4, Duration::from_secs(60));
+    let received    = receive_batches(batch, 2, |mut output| async move {
+        for message in 0..8 {
+            output.send(message).await.unwrap();
+        }
+
+        // Keep the output open until the receiver has finished
+        future::pending::<()>().await;
+    });
+
+    assert!(received == vec![vec![0, 1, 2, 3], vec![4, 5, 6, 7]], "Received {:?}", received);
+}
+
+#[test]
+fn batch_after_delay() {
+    // The batch size is large enough that only the delay will cause a batch to be sent
+    let batch       = batch_filter::<usize>(100, Duration::from_millis(50));
+    let received    = receive_batches(batch, 2, |mut output| async move {
+        for message in 0..3 {
+            output.send(message).await.unwrap();
+        }
+
+        Delay::new(Duration::from_millis(200)).await;
+
+        for message in 3..5 {
+            output.send(message).await.unwrap();
+        }
+
+        // Keep the output open until the receiver has finished
+        future::pending::<()>().await;
+    });
+
+    assert!(received == vec![vec![0, 1, 2], vec![3, 4]], "Received {:?}", received);
+}
+
+#[test]
+fn batch_flushed_when_input_closes() {
+    // The partial batch should be sent as soon as the sender finishes (and closes its output)
+    let batch       = batch_filter::<usize>(100, Duration::from_secs(60));
+    let received    = receive_batches(batch, 1, |mut output| async move {
+        for message in 0..3 {
+            output.send(message).await.unwrap();
+        }
+    });
+
+    assert!(received == vec![vec![0, 1, 2]], "Received {:?}", received);
+}
@@ -45,3 +45,53 @@ pub fn call_launcher_command() {
         })
         .run_in_scene(&scene, test_program);
 }
+
+#[test]
+pub fn describe_launcher_commands() {
+    let test_program        = SubProgramId::new();
+    let launcher_program    = SubProgramId::new();
+
+    // The response can be a command description as well as a command name
+    #[derive(Debug, PartialEq)]
+    pub enum TestResponse {
+        Command(String),
+        Description(String, String),
+        Error(String),
+    }
+
+    impl From<ListCommandResponse> for TestResponse {
+        fn from(value: ListCommandResponse) -> Self {
+            Self::Command(value.0)
+        }
+    }
+
+    impl From<CommandDescription> for TestResponse {
+        fn from(value: CommandDescription) -> Self {
+            Self::Description(value.name, value.description)
+        }
+    }
+
+    impl SceneMessage for TestResponse { }
+
+    impl From<CommandError> for TestResponse {
+        fn from(value: CommandError) -> Self {
+            Self::Error(format!("{:?}", value))
+        }
+    }
+
+    // Create a launcher with one described command and one without a description
+    let scene       = Scene::default();
+    let launcher    = CommandLauncher::<String, TestResponse>::empty()
+        .with_command_described("described_command", "A command with a description", |_, _| async move { })
+        .with_command("other_command", |_, _| async move { });
+    scene.add_subprogram(launcher_program, launcher.to_subprogram(), 0);
+
+    // Only the described command is returned by the describe commands command
+    TestBuilder::new()
+        .run_query(ReadCommand::default(), RunCommand::<String, TestResponse>::new((), DESCRIBE_COMMANDS, ""), launcher_program, |response| {
+            if response != vec![TestResponse::Description("described_command".into(), "A command with a description".into())] { return Err(format!("{:?}", response)); }
+
+            Ok(())
+        })
+        .run_in_scene(&scene, test_program);
+}
@@ -0,0 +1,36 @@
+//!
+//! The recording program stores the messages it receives so that they can be checked after the scene has run
+//!
+
+use flo_scene::*;
+use flo_scene::programs::*;
+
+use futures::prelude::*;
+
+#[test]
+fn record_messages_from_producer() {
+    let scene           = Scene::default();
+    let test_program    = SubProgramId::new();
+    let recorder        = SubProgramId::new();
+
+    // The recording program stores the messages sent by the producer
+    let (recording_program, messages) = RecordingProgram::<usize>::new();
+    scene.add_subprogram(recorder, recording_program, 20);
+
+    scene.add_subprogram(SubProgramId::new(), move |_: InputStream<()>, context| async move {
+        let mut output = context.send::<usize>(recorder).unwrap();
+
+        for message in 0..10 {
+            output.send(message).await.unwrap();
+        }
+    }, 0);
+
+    // Wait for the scene to become idle, at which point the producer will have sent all of its messages
+    TestBuilder::new()
+        .send_message(IdleRequest::WhenIdle(test_program))
+        .expect_message(|IdleNotification| { Ok(()) })
+        .run_in_scene(&scene, test_program);
+
+    let messages = messages.lock().unwrap().clone();
+    assert!(messages == (0..10).collect::<Vec<_>>(), "Recorded {:?}", messages);
+}
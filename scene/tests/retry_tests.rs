@@ -0,0 +1,68 @@
+//!
+//! Messages can be sent with a retry policy, for when the target might not be ready to receive them yet
+//!
+
+use flo_scene::*;
+use flo_scene::programs::*;
+
+use futures::prelude::*;
+
+use std::thread;
+use std::time::{Duration};
+
+#[derive(Debug, PartialEq)]
+struct Ping(usize);
+impl SceneMessage for Ping {}
+
+#[derive(Debug, PartialEq)]
+struct SendResult(Result<(), ConnectionError>);
+impl SceneMessage for SendResult {}
+
+#[test]
+fn send_succeeds_once_target_starts() {
+    let scene           = Scene::default();
+    let sender_program  = SubProgramId::new();
+    let ping_program    = SubProgramId::new();
+    let test_program    = SubProgramId::new();
+
+    // The sender retries until the ping program exists
+    scene.add_subprogram(sender_program, move |_: InputStream<()>, context| async move {
+        context.send_with_retry(ping_program, Ping(42), RetryPolicy::Fixed(100, Duration::from_millis(10))).await.unwrap();
+    }, 0);
+
+    // Start the ping program after a short delay: it passes on the messages it receives to the test program
+    let delayed_scene = scene.clone();
+    thread::spawn(move || {
+        thread::sleep(Duration::from_millis(50));
+
+        delayed_scene.add_subprogram(ping_program, move |input: InputStream<Ping>, context| async move {
+            let mut input = input;
+
+            while let Some(ping) = input.next().await {
+                context.send(test_program).unwrap().send(ping).await.unwrap();
+            }
+        }, 0);
+    });
+
+    TestBuilder::new()
+        .expect_message(|msg: Ping| if msg == Ping(42) { Ok(()) } else { Err(format!("Unexpected message: {:?}", msg)) })
+        .run_in_scene(&scene, test_program);
+}
+
+#[test]
+fn send_gives_up_after_max_attempts() {
+    let scene           = Scene::default();
+    let sender_program  = SubProgramId::new();
+    let missing_program = SubProgramId::new();
+    let test_program    = SubProgramId::new();
+
+    // The target never starts, so the last error is returned once the attempts run out
+    scene.add_subprogram(sender_program, move |_: InputStream<()>, context| async move {
+        let result = context.send_with_retry(missing_program, Ping(42), RetryPolicy::Exponential(3, Duration::from_millis(1), Duration::from_millis(4))).await;
+        context.send_with_retry(test_program, SendResult(result), RetryPolicy::Fixed(100, Duration::from_millis(10))).await.unwrap();
+    }, 0);
+
+    TestBuilder::new()
+        .expect_message(|msg: SendResult| if msg == SendResult(Err(ConnectionError::TargetNotInScene)) { Ok(()) } else { Err(format!("Unexpected result: {:?}", msg)) })
+        .run_in_scene(&scene, test_program);
+}
@@ -8,6 +8,11 @@ mod with_serde_support {
     use serde::*;
     use serde_json;
 
+    use futures_timer::{Delay};
+
+    use std::any::{TypeId};
+    use std::time::{Duration};
+
     #[test]
     fn serialize_deserialize() {
         #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
@@ -135,4 +140,402 @@ mod with_serde_support {
             })
             .run_in_scene(&scene, test_program);
     }
+
+    #[test]
+    fn send_to_missing_program_generates_dead_letter() {
+        #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+        enum TestMessage {
+            StringValue(String)
+        }
+
+        impl SceneMessage for TestMessage { }
+
+        let test_program        = SubProgramId::new();
+        let dead_letter_program = SubProgramId::new();
+        let sender_program      = SubProgramId::new();
+        let missing_program     = SubProgramId::new();
+
+        // Messages that can't be delivered are sent to the dead-letter program
+        let scene = Scene::default();
+        scene.with_serializer(|| serde_json::value::Serializer)
+            .with_serializable_type::<TestMessage>("flo_scene::test::DeadLetterMessage")
+            .with_dead_letter_target(dead_letter_program);
+
+        // The dead-letter program passes on anything it receives to the test program
+        scene.add_subprogram(dead_letter_program, move |input_stream, context| async move {
+            let mut input_stream = input_stream;
+
+            while let Some(message) = input_stream.next().await {
+                let message: DeadLetter<serde_json::Value> = message;
+
+                context.send(test_program).unwrap()
+                    .send(message)
+                    .await
+                    .unwrap();
+            }
+        }, 0);
+
+        // The sender tries to send a message to a program that was never started
+        scene.add_subprogram(sender_program, move |_: InputStream<()>, context| async move {
+            context.send(missing_program).unwrap()
+                .send(TestMessage::StringValue("Test".to_string()))
+                .await
+                .unwrap();
+        }, 0);
+
+        TestBuilder::new()
+            .expect_message(move |msg: DeadLetter<serde_json::Value>| {
+                if msg.target != StreamTarget::Program(missing_program) { return Err(format!("Unexpected target {:?}", msg.target)); }
+                if msg.serialized_message.1 != TypeId::of::<TestMessage>() { return Err("Unexpected message type".to_string()); }
+
+                let message = TestMessage::deserialize(&msg.serialized_message.0).map_err(|err| format!("{:?}", err))?;
+                if message != TestMessage::StringValue("Test".to_string()) { Err(format!("Expected 'Test' (got {:?})", message)) } else { Ok(()) }
+            })
+            .run_in_scene(&scene, test_program);
+    }
+
+    #[test]
+    fn send_to_finished_program_generates_dead_letter() {
+        #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+        enum TestMessage {
+            StringValue(String)
+        }
+
+        impl SceneMessage for TestMessage { }
+
+        let test_program        = SubProgramId::new();
+        let dead_letter_program = SubProgramId::new();
+        let sender_program      = SubProgramId::new();
+        let finished_program    = SubProgramId::new();
+
+        let scene = Scene::default();
+        scene.with_serializer(|| serde_json::value::Serializer)
+            .with_serializable_type::<TestMessage>("flo_scene::test::FinishedDeadLetterMessage")
+            .with_dead_letter_target(dead_letter_program);
+
+        // The dead-letter program passes on anything it receives to the test program
+        scene.add_subprogram(dead_letter_program, move |input_stream, context| async move {
+            let mut input_stream = input_stream;
+
+            while let Some(message) = input_stream.next().await {
+                let message: DeadLetter<serde_json::Value> = message;
+
+                context.send(test_program).unwrap()
+                    .send(message)
+                    .await
+                    .unwrap();
+            }
+        }, 0);
+
+        // This program finishes as soon as it starts
+        scene.add_subprogram(finished_program, |_: InputStream<TestMessage>, _| async move { }, 0);
+
+        // The sender waits for the program to stop, then sends it a message
+        scene.add_subprogram(sender_program, move |input: InputStream<SceneUpdate>, context| async move {
+            let mut input = input;
+
+            while let Some(update) = input.next().await {
+                if update == SceneUpdate::Stopped(finished_program) { break; }
+            }
+
+            // The program is removed from the scene just after the 'stopped' update is sent
+            Delay::new(Duration::from_millis(10)).await;
+
+            context.send(finished_program).unwrap()
+                .send(TestMessage::StringValue("Test".to_string()))
+                .await
+                .unwrap();
+        }, 0);
+        scene.connect_programs((), sender_program, StreamId::with_message_type::<SceneUpdate>()).unwrap();
+
+        TestBuilder::new()
+            .expect_message(move |msg: DeadLetter<serde_json::Value>| {
+                if msg.target != StreamTarget::Program(finished_program) { return Err(format!("Unexpected target {:?}", msg.target)); }
+
+                let message = TestMessage::deserialize(&msg.serialized_message.0).map_err(|err| format!("{:?}", err))?;
+                if message != TestMessage::StringValue("Test".to_string()) { Err(format!("Expected 'Test' (got {:?})", message)) } else { Ok(()) }
+            })
+            .run_in_scene(&scene, test_program);
+    }
+
+    #[test]
+    fn dead_letter_queue_is_bounded() {
+        #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+        enum TestMessage {
+            StringValue(String)
+        }
+
+        impl SceneMessage for TestMessage { }
+
+        #[derive(Debug, PartialEq)]
+        struct SendResults(Vec<bool>);
+        impl SceneMessage for SendResults { }
+
+        let test_program        = SubProgramId::new();
+        let dead_letter_program = SubProgramId::new();
+        let sender_program      = SubProgramId::new();
+        let missing_program     = SubProgramId::new();
+
+        let scene = Scene::default();
+        scene.with_serializer(|| serde_json::value::Serializer)
+            .with_serializable_type::<TestMessage>("flo_scene::test::BoundedDeadLetterMessage")
+            .with_dead_letter_target(dead_letter_program);
+
+        // The dead-letter program never reads its input, so its queue fills up
+        scene.add_subprogram(dead_letter_program, |input: InputStream<DeadLetter<serde_json::Value>>, _| async move {
+            let _input = input;
+            future::pending::<()>().await
+        }, 0);
+
+        // The sender sends several messages to a missing program and reports which ones were accepted
+        scene.add_subprogram(sender_program, move |_: InputStream<()>, context| async move {
+            let mut results = vec![];
+
+            for _ in 0..4 {
+                let result = context.send(missing_program).unwrap()
+                    .send(TestMessage::StringValue("Test".to_string()))
+                    .await;
+
+                results.push(result.is_ok());
+            }
+
+            context.send(test_program).unwrap().send(SendResults(results)).await.unwrap();
+        }, 0);
+
+        // Only as many messages as the dead-letter program's input can hold are accepted
+        TestBuilder::new()
+            .expect_message(|msg: SendResults| if msg == SendResults(vec![true, false, false, false]) { Ok(()) } else { Err(format!("Unexpected results: {:?}", msg)) })
+            .run_in_scene(&scene, test_program);
+    }
+
+    #[test]
+    fn stream_id_from_serialization_type_name() {
+        #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+        enum TestMessage {
+            StringValue(String)
+        }
+
+        impl SceneMessage for TestMessage { }
+
+        install_serializer(|| serde_json::value::Serializer);
+        install_serializable_type::<TestMessage, serde_json::value::Serializer>("flo_scene::test::StreamIdFromName").unwrap();
+
+        // The name can be turned back into a stream ID
+        let stream_id = StreamId::with_serialization_type("flo_scene::test::StreamIdFromName").unwrap();
+
+        assert!(stream_id == StreamId::with_message_type::<TestMessage>());
+        assert!(stream_id.serialization_type_name() == Some("flo_scene::test::StreamIdFromName".to_string()));
+
+        // Unknown names have no stream ID
+        assert!(StreamId::with_serialization_type("flo_scene::test::NotARealType").is_none());
+    }
+
+    #[test]
+    fn binary_serializer() {
+        #[derive(PartialEq, Eq, Debug)]
+        struct TestMessage(String);
+
+        impl SceneMessage for TestMessage { }
+
+        // Serialize the message to UTF-8 bytes
+        let scene = Scene::default();
+        scene.with_binary_serializable_type::<TestMessage, _, _>("flo_scene::test::BinaryTestMessage", 
+            |msg| Ok::<_, ()>(msg.0.as_bytes().to_vec()), 
+            |bytes| String::from_utf8(bytes.to_vec()).map(TestMessage));
+
+        // The serialized_resender receives the bytes and sends them on to the deserialized_receiver, which turns them back into a TestMessage
+        let test_program            = SubProgramId::new();
+        let serialized_resender     = SubProgramId::new();
+        let deserialized_receiver   = SubProgramId::new();
+
+        scene.add_subprogram(serialized_resender, 
+            move |input_stream, context| async move {
+                let mut input_stream = input_stream;
+
+                while let Some(message) = input_stream.next().await {
+                    let message: SerializedMessage<Vec<u8>> = message;
+
+                    println!("Serialized: {:?}", message.0);
+                    assert!(message.0 == "Test".as_bytes().to_vec());
+
+                    context.send(deserialized_receiver).unwrap()
+                        .send(message)
+                        .await
+                        .unwrap();
+                }
+            }, 0);
+
+        scene.add_subprogram(deserialized_receiver, move |input_stream, context| async move {
+            let mut input_stream = input_stream;
+
+            while let Some(message) = input_stream.next().await {
+                let message: TestMessage = message;
+
+                context.send(test_program).unwrap()
+                    .send(message)
+                    .await
+                    .unwrap();
+            }
+        }, 0);
+
+        TestBuilder::new()
+            .send_message_to_target(serialized_resender, TestMessage("Test".to_string()))
+            .expect_message(|msg: TestMessage| {
+                if msg != TestMessage("Test".to_string()) { Err(format!("Expected 'Test' (got {:?})", msg)) } else { Ok(()) }
+            })
+            .run_in_scene(&scene, test_program);
+    }
+
+    #[test]
+    #[cfg(feature = "postcard")]
+    fn postcard_serializer() {
+        #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+        enum TestMessage {
+            StringValue(String),
+            Number(u32),
+        }
+
+        impl SceneMessage for TestMessage { }
+
+        let scene = Scene::default();
+        scene.with_postcard_serializable_type::<TestMessage>("flo_scene::test::PostcardTestMessage");
+
+        let test_program            = SubProgramId::new();
+        let serialized_resender     = SubProgramId::new();
+        let deserialized_receiver   = SubProgramId::new();
+
+        scene.add_subprogram(serialized_resender, 
+            move |input_stream, context| async move {
+                let mut input_stream = input_stream;
+
+                while let Some(message) = input_stream.next().await {
+                    let message: SerializedMessage<Vec<u8>> = message;
+
+                    println!("Serialized: {:?}", message.0);
+
+                    context.send(deserialized_receiver).unwrap()
+                        .send(message)
+                        .await
+                        .unwrap();
+                }
+            }, 0);
+
+        scene.add_subprogram(deserialized_receiver, move |input_stream, context| async move {
+            let mut input_stream = input_stream;
+
+            while let Some(message) = input_stream.next().await {
+                let message: TestMessage = message;
+
+                context.send(test_program).unwrap()
+                    .send(message)
+                    .await
+                    .unwrap();
+            }
+        }, 0);
+
+        TestBuilder::new()
+            .send_message_to_target(serialized_resender, TestMessage::StringValue("Test".to_string()))
+            .expect_message(|msg: TestMessage| {
+                if msg != TestMessage::StringValue("Test".to_string()) { Err(format!("Expected 'Test' (got {:?})", msg)) } else { Ok(()) }
+            })
+            .send_message_to_target(serialized_resender, TestMessage::Number(42))
+            .expect_message(|msg: TestMessage| {
+                if msg != TestMessage::Number(42) { Err(format!("Expected 42 (got {:?})", msg)) } else { Ok(()) }
+            })
+            .run_in_scene(&scene, test_program);
+    }
+
+    #[test]
+    fn list_registered_types() {
+        #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+        struct ListedMessage(String);
+
+        impl SceneMessage for ListedMessage { }
+
+        install_serializer(|| serde_json::value::Serializer);
+        install_serializable_type::<ListedMessage, serde_json::value::Serializer>("flo_scene::test::ListedMessage").unwrap();
+
+        let registered_types = registered_serializable_types();
+        let listed_message   = registered_types.iter().find(|(name, _, _)| name == "flo_scene::test::ListedMessage");
+
+        assert!(listed_message.is_some(), "{:?}", registered_types);
+
+        let (_, type_id, rust_type_name) = listed_message.unwrap();
+        assert!(*type_id == TypeId::of::<ListedMessage>());
+        assert!(*rust_type_name == std::any::type_name::<ListedMessage>());
+    }
+
+    #[test]
+    fn uninstall_and_reuse_type_name() {
+        #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+        struct FirstMessage(String);
+
+        #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+        struct SecondMessage(u32);
+
+        impl SceneMessage for FirstMessage { }
+        impl SceneMessage for SecondMessage { }
+
+        install_serializer(|| serde_json::value::Serializer);
+        install_serializable_type::<FirstMessage, serde_json::value::Serializer>("flo_scene::test::UninstalledMessage").unwrap();
+        assert!(serializer_filter::<FirstMessage, SerializedMessage<serde_json::Value>>().is_ok());
+
+        // Removing the type should remove the name and the filters
+        uninstall_serializable_type("flo_scene::test::UninstalledMessage").unwrap();
+
+        assert!(StreamId::with_serialization_type("flo_scene::test::UninstalledMessage").is_none());
+        assert!(StreamId::with_message_type::<FirstMessage>().serialization_type_name().is_none());
+        assert!(serializer_filter::<FirstMessage, SerializedMessage<serde_json::Value>>().is_err());
+        assert!(uninstall_serializable_type("flo_scene::test::UninstalledMessage").is_err());
+
+        // The name can be used for a different type after it's uninstalled
+        install_serializable_type::<SecondMessage, serde_json::value::Serializer>("flo_scene::test::UninstalledMessage").unwrap();
+        assert!(StreamId::with_serialization_type("flo_scene::test::UninstalledMessage") == Some(StreamId::with_message_type::<SecondMessage>()));
+    }
+
+    #[test]
+    fn verify_roundtrip() {
+        #[derive(Serialize, Deserialize, PartialEq, Eq, Debug)]
+        enum TestMessage {
+            StringValue(String)
+        }
+
+        impl SceneMessage for TestMessage { }
+
+        install_serializer(|| serde_json::value::Serializer);
+        install_serializable_type::<TestMessage, serde_json::value::Serializer>("flo_scene::test::RoundTripMessage").unwrap();
+
+        assert!(verify_serialization_roundtrip::<_, serde_json::value::Serializer>(&TestMessage::StringValue("Test".to_string())) == Ok(()));
+    }
+
+    #[test]
+    fn verify_roundtrip_with_lossy_type() {
+        // Serializes as just its value, so the name is lost when deserializing
+        #[derive(PartialEq, Eq, Debug)]
+        struct LossyMessage(String, u32);
+
+        impl Serialize for LossyMessage {
+            fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                self.1.serialize(serializer)
+            }
+        }
+
+        impl<'de> Deserialize<'de> for LossyMessage {
+            fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                Ok(LossyMessage(String::new(), u32::deserialize(deserializer)?))
+            }
+        }
+
+        impl SceneMessage for LossyMessage { }
+
+        install_serializer(|| serde_json::value::Serializer);
+
+        // Serializer is available but the type is not installed yet
+        assert!(verify_serialization_roundtrip::<_, serde_json::value::Serializer>(&LossyMessage("Test".to_string(), 42)) == Err(SerializationError::TypeNotInstalled));
+
+        install_serializable_type::<LossyMessage, serde_json::value::Serializer>("flo_scene::test::LossyMessage").unwrap();
+
+        assert!(verify_serialization_roundtrip::<_, serde_json::value::Serializer>(&LossyMessage(String::new(), 42)) == Ok(()));
+        assert!(verify_serialization_roundtrip::<_, serde_json::value::Serializer>(&LossyMessage("Test".to_string(), 42)) == Err(SerializationError::DeserializedValueDoesNotMatch));
+    }
 }
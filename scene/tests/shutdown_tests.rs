@@ -0,0 +1,90 @@
+//!
+//! Shutting down a scene closes the inputs of its programs, so they can finish what they're doing before it stops
+//!
+
+use flo_scene::*;
+
+use futures::prelude::*;
+use futures::future::{select};
+use futures::channel::oneshot;
+use futures::executor;
+use futures_timer::*;
+
+use std::time::{Duration};
+use std::sync::*;
+
+#[test]
+fn shutdown_lets_programs_clean_up() {
+    let scene       = Scene::default();
+    let program     = SubProgramId::new();
+    let cleaned_up  = Arc::new(Mutex::new(false));
+    let finished    = Arc::new(Mutex::new(false));
+
+    // This program tidies up after its input stream is closed
+    let (started, when_started) = oneshot::channel();
+    let program_cleaned_up      = cleaned_up.clone();
+    scene.add_subprogram(program, move |input: InputStream<()>, _| async move {
+        started.send(()).ok();
+
+        let mut input = input;
+        while input.next().await.is_some() { }
+
+        *program_cleaned_up.lock().unwrap() = true;
+    }, 0);
+
+    // Shut the scene down once the program has started
+    let shutdown_finished = finished.clone();
+    executor::block_on(select(async {
+        future::join(scene.run_scene(), async {
+            when_started.await.unwrap();
+            scene.shutdown().await;
+
+            *shutdown_finished.lock().unwrap() = true;
+        }).await;
+    }.boxed(), Delay::new(Duration::from_millis(5000))));
+
+    assert!(*cleaned_up.lock().unwrap(), "Program did not clean up");
+    assert!(*finished.lock().unwrap(), "Shutdown did not finish");
+}
+
+#[test]
+fn shutdown_closes_programs_started_while_shutting_down() {
+    let scene           = Scene::default();
+    let program         = SubProgramId::new();
+    let late_program    = SubProgramId::new();
+    let cleaned_up      = Arc::new(Mutex::new(false));
+    let finished        = Arc::new(Mutex::new(false));
+
+    // This program is already running when the scene is shut down
+    let (started, when_started) = oneshot::channel();
+    scene.add_subprogram(program, move |input: InputStream<()>, _| async move {
+        started.send(()).ok();
+
+        let mut input = input;
+        while input.next().await.is_some() { }
+    }, 0);
+
+    // Start a second program after the shutdown has begun: its input should be closed too, so the shutdown can finish
+    let shutdown_finished   = finished.clone();
+    let late_cleaned_up     = cleaned_up.clone();
+    executor::block_on(select(async {
+        future::join(scene.run_scene(), async {
+            when_started.await.unwrap();
+            let shutdown = scene.shutdown();
+
+            scene.add_subprogram(late_program, move |input: InputStream<()>, _| async move {
+                let mut input = input;
+                while input.next().await.is_some() { }
+
+                *late_cleaned_up.lock().unwrap() = true;
+            }, 0);
+
+            shutdown.await;
+
+            *shutdown_finished.lock().unwrap() = true;
+        }).await;
+    }.boxed(), Delay::new(Duration::from_millis(5000))));
+
+    assert!(*cleaned_up.lock().unwrap(), "Late program did not clean up");
+    assert!(*finished.lock().unwrap(), "Shutdown did not finish");
+}
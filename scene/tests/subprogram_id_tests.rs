@@ -0,0 +1,264 @@
+use flo_scene::*;
+use flo_scene::programs::*;
+use flo_scene::commands::*;
+
+use futures::prelude::*;
+
+use std::sync::*;
+
+#[test]
+fn registered_name_count_increases_for_new_names() {
+    let initial_count = SubProgramId::registered_name_count();
+
+    SubProgramId::called("registered_name_count_increases_for_new_names::one");
+    SubProgramId::called("registered_name_count_increases_for_new_names::two");
+    SubProgramId::called("registered_name_count_increases_for_new_names::one");
+    SubProgramId::new();
+
+    // Other tests might be registering names at the same time, so we can only check that at least our two names were added
+    assert!(SubProgramId::registered_name_count() >= initial_count + 2);
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct TaskInfo {
+    is_task:        bool,
+    parent_program: Option<SubProgramId>,
+    task_serial:    Option<usize>,
+}
+
+impl SceneMessage for TaskInfo { }
+
+///
+/// Creates a command that reports the task information for its own subprogram ID
+///
+fn report_task_info() -> FnCommand<(), TaskInfo> {
+    FnCommand::<(), TaskInfo>::new(|_input, context| async move {
+        let task_id     = context.current_program_id().unwrap();
+        let mut output  = context.send::<TaskInfo>(()).unwrap();
+
+        output.send(TaskInfo {
+            is_task:        task_id.is_task(),
+            parent_program: task_id.parent_program(),
+            task_serial:    task_id.task_serial(),
+        }).await.unwrap();
+    })
+}
+
+#[test]
+fn programs_are_not_tasks() {
+    let named   = SubProgramId::called("programs_are_not_tasks");
+    let guid    = SubProgramId::new();
+
+    assert!(!named.is_task());
+    assert!(!guid.is_task());
+    assert!(named.parent_program().is_none());
+    assert!(guid.parent_program().is_none());
+    assert!(named.task_serial().is_none());
+    assert!(guid.task_serial().is_none());
+}
+
+#[test]
+fn guid_task_reports_parent() {
+    let scene           = Scene::default();
+    let test_program    = SubProgramId::new();
+
+    TestBuilder::new()
+        .run_command(report_task_info(), vec![], move |output| {
+            if output.len() != 1 { return Err(format!("Unexpected command output: {:?}", output)); }
+
+            if !output[0].is_task                                   { return Err(format!("Not a task: {:?}", output)); }
+            if output[0].parent_program != Some(test_program)       { return Err(format!("Wrong parent: {:?} (expected {:?})", output, test_program)); }
+            if output[0].task_serial.is_none()                      { return Err(format!("No serial: {:?}", output)); }
+
+            Ok(())
+        })
+        .run_in_scene_with_threads(&scene, test_program, 5);
+}
+
+#[test]
+fn named_task_reports_parent_and_serial() {
+    let scene           = Scene::default();
+    let test_program    = SubProgramId::called("named_task_reports_parent_and_serial");
+    let serials         = Arc::new(Mutex::new(vec![]));
+
+    let first_serials   = Arc::clone(&serials);
+    let second_serials  = Arc::clone(&serials);
+
+    TestBuilder::new()
+        .run_command(report_task_info(), vec![], move |output| {
+            if output.len() != 1 { return Err(format!("Unexpected command output: {:?}", output)); }
+
+            if !output[0].is_task                                   { return Err(format!("Not a task: {:?}", output)); }
+            if output[0].parent_program != Some(test_program)       { return Err(format!("Wrong parent: {:?} (expected {:?})", output, test_program)); }
+
+            first_serials.lock().unwrap().extend(output[0].task_serial);
+            Ok(())
+        })
+        .run_command(report_task_info(), vec![], move |output| {
+            if output.len() != 1 { return Err(format!("Unexpected command output: {:?}", output)); }
+
+            if !output[0].is_task                                   { return Err(format!("Not a task: {:?}", output)); }
+            if output[0].parent_program != Some(test_program)       { return Err(format!("Wrong parent: {:?} (expected {:?})", output, test_program)); }
+
+            second_serials.lock().unwrap().extend(output[0].task_serial);
+            Ok(())
+        })
+        .run_in_scene_with_threads(&scene, test_program, 5);
+
+    // Each task started by the same program gets a different serial number
+    let serials = serials.lock().unwrap().clone();
+    assert!(serials.len() == 2, "Serials: {:?}", serials);
+    assert!(serials[0] != serials[1], "Serials: {:?}", serials);
+}
+
+///
+/// Creates a command that reports the display string for its own subprogram ID
+///
+fn report_task_display() -> FnCommand<(), String> {
+    FnCommand::<(), String>::new(|_input, context| async move {
+        let task_id     = context.current_program_id().unwrap();
+        let mut output  = context.send::<String>(()).unwrap();
+
+        output.send(task_id.to_string()).await.unwrap();
+    })
+}
+
+///
+/// Checks that a task display string is of the form `<prefix>(<parent>,<serial>)`
+///
+fn check_task_display(output: Vec<String>, prefix: &str, parent: &str) -> Result<(), String> {
+    if output.len() != 1 { return Err(format!("Unexpected command output: {:?}", output)); }
+
+    let expected_start  = format!("{}({},", prefix, parent);
+    let serial          = output[0].strip_prefix(&expected_start).and_then(|rest| rest.strip_suffix(')'));
+
+    match serial {
+        Some(serial) if serial.parse::<usize>().is_ok()     => Ok(()),
+        _                                                   => Err(format!("Unexpected display string: {:?} (expected {}<serial>))", output[0], expected_start)),
+    }
+}
+
+#[test]
+fn display_named() {
+    let named = SubProgramId::called("display_named");
+
+    assert!(named.to_string() == "named:display_named", "{}", named);
+}
+
+#[test]
+fn display_guid() {
+    let guid            = SubProgramId::new();
+    let guid_string     = guid.to_string();
+    let uuid            = guid_string.strip_prefix("guid:");
+
+    assert!(uuid.is_some(), "{}", guid_string);
+    assert!(uuid.unwrap().len() == 36, "{}", guid_string);
+}
+
+#[test]
+fn display_named_task() {
+    let scene           = Scene::default();
+    let test_program    = SubProgramId::called("display_named_task");
+
+    TestBuilder::new()
+        .run_command(report_task_display(), vec![], |output| check_task_display(output, "task", "display_named_task"))
+        .run_in_scene_with_threads(&scene, test_program, 5);
+}
+
+#[test]
+fn display_guid_task() {
+    let scene           = Scene::default();
+    let test_program    = SubProgramId::new();
+    let uuid            = test_program.to_string().strip_prefix("guid:").unwrap().to_string();
+
+    TestBuilder::new()
+        .run_command(report_task_display(), vec![], move |output| check_task_display(output, "guidtask", &uuid))
+        .run_in_scene_with_threads(&scene, test_program, 5);
+}
+
+///
+/// Checks that a task display string parses back to a task with the expected parent
+///
+fn check_task_parse(output: Vec<String>, parent: SubProgramId) -> Result<(), String> {
+    if output.len() != 1 { return Err(format!("Unexpected command output: {:?}", output)); }
+
+    let parsed = output[0].parse::<SubProgramId>().map_err(|err| format!("Could not parse {:?}: {:?}", output[0], err))?;
+
+    if !parsed.is_task()                        { return Err(format!("Not a task: {:?}", parsed)); }
+    if parsed.parent_program() != Some(parent)  { return Err(format!("Wrong parent: {:?} (expected {:?})", parsed, parent)); }
+    if parsed.to_string() != output[0]          { return Err(format!("Does not round-trip: {:?} (original {:?})", parsed, output[0])); }
+
+    Ok(())
+}
+
+#[test]
+fn parse_named() {
+    let parsed = "named:parse_named".parse::<SubProgramId>();
+
+    assert!(parsed == Ok(SubProgramId::called("parse_named")), "{:?}", parsed);
+}
+
+#[test]
+fn parse_guid() {
+    let guid    = SubProgramId::new();
+    let parsed  = guid.to_string().parse::<SubProgramId>();
+
+    assert!(parsed == Ok(guid), "{:?}", parsed);
+}
+
+#[test]
+fn parse_named_task() {
+    let parsed = "task(parse_named_task,3)".parse::<SubProgramId>().unwrap();
+
+    assert!(parsed.is_task());
+    assert!(parsed.parent_program() == Some(SubProgramId::called("parse_named_task")), "{:?}", parsed);
+    assert!(parsed.task_serial() == Some(3), "{:?}", parsed);
+}
+
+#[test]
+fn parse_named_task_with_comma() {
+    let parsed = "task(parse,named,task,4)".parse::<SubProgramId>().unwrap();
+
+    assert!(parsed.parent_program() == Some(SubProgramId::called("parse,named,task")), "{:?}", parsed);
+    assert!(parsed.task_serial() == Some(4), "{:?}", parsed);
+}
+
+#[test]
+fn parse_guid_task() {
+    let guid    = SubProgramId::new();
+    let uuid    = guid.to_string().strip_prefix("guid:").unwrap().to_string();
+    let parsed  = format!("guidtask({},3)", uuid).parse::<SubProgramId>().unwrap();
+
+    assert!(parsed.is_task());
+    assert!(parsed.parent_program() == Some(guid), "{:?}", parsed);
+    assert!(parsed.task_serial() == Some(3), "{:?}", parsed);
+}
+
+#[test]
+fn parse_running_named_task() {
+    let scene           = Scene::default();
+    let test_program    = SubProgramId::called("parse_running_named_task");
+
+    TestBuilder::new()
+        .run_command(report_task_display(), vec![], move |output| check_task_parse(output, test_program))
+        .run_in_scene_with_threads(&scene, test_program, 5);
+}
+
+#[test]
+fn parse_running_guid_task() {
+    let scene           = Scene::default();
+    let test_program    = SubProgramId::new();
+
+    TestBuilder::new()
+        .run_command(report_task_display(), vec![], move |output| check_task_parse(output, test_program))
+        .run_in_scene_with_threads(&scene, test_program, 5);
+}
+
+#[test]
+fn parse_errors() {
+    assert!("something:else".parse::<SubProgramId>() == Err(SubProgramIdParseError::UnrecognisedFormat("something:else".into())));
+    assert!("named:".parse::<SubProgramId>() == Err(SubProgramIdParseError::MissingName));
+    assert!("guid:not-a-guid".parse::<SubProgramId>() == Err(SubProgramIdParseError::InvalidGuid("not-a-guid".into())));
+    assert!("task(name,x)".parse::<SubProgramId>() == Err(SubProgramIdParseError::InvalidSerial("x".into())));
+    assert!("task(name)".parse::<SubProgramId>() == Err(SubProgramIdParseError::UnrecognisedFormat("task(name)".into())));
+}
@@ -0,0 +1,157 @@
+use flo_scene::*;
+use flo_scene::programs::*;
+
+use futures::prelude::*;
+use futures::future::{select};
+use futures::executor;
+use futures_timer::*;
+
+use std::time::{Duration};
+use std::sync::*;
+
+#[derive(Clone, Debug, PartialEq)]
+struct TestEvent(usize);
+
+impl SceneMessage for TestEvent { }
+
+///
+/// Adds a subprogram that records the events it receives in a shared list
+///
+fn add_event_receiver(scene: &Scene, program_id: SubProgramId, received: Arc<Mutex<Vec<(SubProgramId, usize)>>>) {
+    scene.add_subprogram(program_id, move |input: InputStream<TestEvent>, _| async move {
+        let mut input = input;
+
+        while let Some(TestEvent(evt)) = input.next().await {
+            received.lock().unwrap().push((program_id, evt));
+        }
+    }, 0);
+}
+
+#[test]
+fn unsubscribe_stops_delivery() {
+    let scene       = Scene::default();
+    let received    = Arc::new(Mutex::new(vec![]));
+    let counts      = Arc::new(Mutex::new(vec![]));
+
+    let receiver_1  = SubProgramId::new();
+    let receiver_2  = SubProgramId::new();
+    let publisher   = SubProgramId::new();
+
+    add_event_receiver(&scene, receiver_1, Arc::clone(&received));
+    add_event_receiver(&scene, receiver_2, Arc::clone(&received));
+
+    let publisher_counts = Arc::clone(&counts);
+    scene.add_subprogram(publisher, move |_: InputStream<()>, context| async move {
+        let mut subscribers = EventSubscribers::<TestEvent>::new();
+
+        subscribers.subscribe(&context, receiver_1);
+        subscribers.subscribe(&context, receiver_2);
+        publisher_counts.lock().unwrap().push(subscribers.subscriber_count());
+
+        subscribers.send(TestEvent(1)).await;
+
+        subscribers.unsubscribe(receiver_1);
+        publisher_counts.lock().unwrap().push(subscribers.subscriber_count());
+
+        subscribers.send(TestEvent(2)).await;
+
+        context.send_message(SceneControl::StopSceneWhenIdle).await.unwrap();
+    }, 0);
+
+    executor::block_on(select(scene.run_scene().boxed(), Delay::new(Duration::from_millis(5000))));
+
+    let received = received.lock().unwrap().clone();
+
+    assert!(*counts.lock().unwrap() == vec![2, 1], "Counts: {:?}", counts.lock().unwrap());
+    assert!(received.contains(&(receiver_1, 1)), "Received: {:?}", received);
+    assert!(received.contains(&(receiver_2, 1)), "Received: {:?}", received);
+    assert!(received.contains(&(receiver_2, 2)), "Received: {:?}", received);
+    assert!(!received.contains(&(receiver_1, 2)), "Received: {:?}", received);
+}
+
+#[test]
+fn unsubscribe_message_reports_program() {
+    let program     = SubProgramId::new();
+    let message     = unsubscribe::<TestEvent>(program);
+
+    assert!(message.program() == program);
+}
+
+#[test]
+fn filtered_and_unfiltered_subscribers() {
+    let scene       = Scene::default();
+    let received    = Arc::new(Mutex::new(vec![]));
+
+    let everything  = SubProgramId::new();
+    let even        = SubProgramId::new();
+    let large       = SubProgramId::new();
+    let publisher   = SubProgramId::new();
+
+    add_event_receiver(&scene, everything, Arc::clone(&received));
+    add_event_receiver(&scene, even, Arc::clone(&received));
+    add_event_receiver(&scene, large, Arc::clone(&received));
+
+    scene.add_subprogram(publisher, move |_: InputStream<()>, context| async move {
+        let mut subscribers = EventSubscribers::<TestEvent>::new();
+
+        subscribers.subscribe(&context, everything);
+        subscribers.subscribe_filtered(&context, even, |TestEvent(evt)| evt % 2 == 0);
+        subscribers.subscribe_filtered(&context, large, |TestEvent(evt)| *evt >= 100);
+
+        for evt in 0..5 {
+            subscribers.send(TestEvent(evt)).await;
+        }
+
+        // Only the 'large' subscriber wants this event
+        subscribers.unsubscribe(everything);
+        subscribers.send(TestEvent(100)).await;
+
+        // Nothing wants this event
+        assert!(!subscribers.send(TestEvent(3)).await);
+
+        context.send_message(SceneControl::StopSceneWhenIdle).await.unwrap();
+    }, 0);
+
+    executor::block_on(select(scene.run_scene().boxed(), Delay::new(Duration::from_millis(5000))));
+
+    let received = received.lock().unwrap().clone();
+    let events_for = |program| received.iter().filter(|(id, _)| *id == program).map(|(_, evt)| *evt).collect::<Vec<_>>();
+
+    assert!(events_for(everything) == vec![0, 1, 2, 3, 4], "Received: {:?}", received);
+    assert!(events_for(even) == vec![0, 2, 4, 100], "Received: {:?}", received);
+    assert!(events_for(large) == vec![100], "Received: {:?}", received);
+}
+
+#[test]
+fn filtered_subscriber_is_pruned_when_it_stops() {
+    let scene       = Scene::default();
+    let counts      = Arc::new(Mutex::new(vec![]));
+
+    let stops       = SubProgramId::new();
+    let publisher   = SubProgramId::new();
+
+    // Subprogram that reads a single event and then stops
+    scene.add_subprogram(stops, move |input: InputStream<TestEvent>, _| async move {
+        let mut input = input;
+        input.next().await;
+    }, 0);
+
+    let publisher_counts = Arc::clone(&counts);
+    scene.add_subprogram(publisher, move |_: InputStream<()>, context| async move {
+        let mut subscribers = EventSubscribers::<TestEvent>::new();
+
+        subscribers.subscribe_filtered(&context, stops, |_| true);
+        publisher_counts.lock().unwrap().push(subscribers.subscriber_count());
+
+        for evt in 0..5 {
+            subscribers.send(TestEvent(evt)).await;
+        }
+        publisher_counts.lock().unwrap().push(subscribers.subscriber_count());
+
+        context.send_message(SceneControl::StopSceneWhenIdle).await.unwrap();
+    }, 0);
+
+    executor::block_on(select(scene.run_scene().boxed(), Delay::new(Duration::from_millis(5000))));
+
+    assert!(*counts.lock().unwrap() == vec![1, 0], "Counts: {:?}", counts.lock().unwrap());
+}
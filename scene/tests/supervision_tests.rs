@@ -0,0 +1,146 @@
+//!
+//! Supervised subprograms are restarted according to a policy when they fail
+//!
+
+use flo_scene::*;
+use flo_scene::programs::*;
+
+use futures::prelude::*;
+use futures_timer::{Delay};
+
+use std::sync::*;
+use std::time::{Duration};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+#[derive(Debug, PartialEq)]
+struct Pong(usize);
+impl SceneMessage for Pong {}
+
+///
+/// Adds a supervised program that fails the first time it's started, and otherwise responds to () messages with a Pong containing the number of times it has been started
+///
+fn add_failing_ping_program(scene: &Scene, policy: RestartPolicy) -> Arc<AtomicUsize> {
+    let ping_program    = SubProgramId::new();
+    let start_count     = Arc::new(AtomicUsize::new(0));
+    let program_count   = start_count.clone();
+
+    scene.add_supervised_subprogram(ping_program, move |input: InputStream<()>, context| {
+        let start_count = program_count.fetch_add(1, Ordering::Relaxed) + 1;
+
+        async move {
+            let mut input = input.messages_with_sources();
+
+            while let Some((source, _)) = input.next().await {
+                // The first instance of the program fails after receiving a message
+                if start_count == 1 { return Err("Failed".to_string()); }
+
+                context.send(source).unwrap().send(Pong(start_count)).await.unwrap();
+            }
+
+            Ok(())
+        }
+    }, policy, 10);
+    scene.connect_programs((), ping_program, StreamId::with_message_type::<()>()).unwrap();
+
+    start_count
+}
+
+#[test]
+fn restart_failed_program() {
+    let scene       = Scene::default();
+    let start_count = add_failing_ping_program(&scene, RestartPolicy::Always);
+
+    // The first message causes the program to fail, and the second is processed by the restarted program
+    TestBuilder::new()
+        .send_message(())
+        .send_message(())
+        .expect_message(|msg: Pong| if msg == Pong(2) { Ok(()) } else { Err(format!("Unexpected message: {:?}", msg)) })
+        .run_in_scene(&scene, SubProgramId::new());
+
+    assert!(start_count.load(Ordering::Relaxed) == 2, "Program was started {} times", start_count.load(Ordering::Relaxed));
+}
+
+#[test]
+fn never_restart_failed_program() {
+    let scene       = Scene::default();
+    let start_count = add_failing_ping_program(&scene, RestartPolicy::Never);
+
+    // Once the program has failed, it stops receiving messages
+    TestBuilder::new()
+        .send_message(())
+        .expect_no_dropped_messages()
+        .run_in_scene(&scene, SubProgramId::new());
+
+    assert!(start_count.load(Ordering::Relaxed) == 1, "Program was started {} times", start_count.load(Ordering::Relaxed));
+}
+
+#[test]
+fn restart_panicked_program() {
+    let scene           = Scene::default();
+    let ping_program    = SubProgramId::new();
+
+    // This program panics the first time it receives a message, and can be restarted once
+    let start_count     = Arc::new(AtomicUsize::new(0));
+    let program_count   = start_count.clone();
+
+    scene.add_supervised_subprogram(ping_program, move |input: InputStream<()>, context| {
+        let start_count = program_count.fetch_add(1, Ordering::Relaxed) + 1;
+
+        async move {
+            let mut input = input.messages_with_sources();
+
+            while let Some((source, _)) = input.next().await {
+                if start_count == 1 { panic!("Program failed"); }
+
+                context.send(source).unwrap().send(Pong(start_count)).await.unwrap();
+            }
+
+            Ok::<_, ()>(())
+        }
+    }, RestartPolicy::MaxRetries(1, Duration::from_secs(60)), 10);
+    scene.connect_programs((), ping_program, StreamId::with_message_type::<()>()).unwrap();
+
+    TestBuilder::new()
+        .send_message(())
+        .send_message(())
+        .expect_message(|msg: Pong| if msg == Pong(2) { Ok(()) } else { Err(format!("Unexpected message: {:?}", msg)) })
+        .run_in_scene(&scene, SubProgramId::new());
+}
+
+#[test]
+fn always_failing_program_does_not_block_scene() {
+    let scene           = Scene::default();
+    let failing_program = SubProgramId::new();
+    let ping_program    = SubProgramId::new();
+
+    // This program fails as soon as it starts, and is always restarted
+    let start_count     = Arc::new(AtomicUsize::new(0));
+    let program_count   = start_count.clone();
+
+    scene.add_supervised_subprogram(failing_program, move |_: InputStream<()>, _context| {
+        program_count.fetch_add(1, Ordering::Relaxed);
+
+        async move { Err::<(), _>("Failed".to_string()) }
+    }, RestartPolicy::Always, 10);
+
+    // Another program that takes a while to respond
+    scene.add_subprogram(ping_program, move |input: InputStream<()>, context| async move {
+        let mut input = input.messages_with_sources();
+
+        while let Some((source, _)) = input.next().await {
+            Delay::new(Duration::from_millis(100)).await;
+            context.send(source).unwrap().send(Pong(0)).await.unwrap();
+        }
+    }, 0);
+    scene.connect_programs((), ping_program, StreamId::with_message_type::<()>()).unwrap();
+
+    // The failing program should not stop the ping program from responding
+    TestBuilder::new()
+        .send_message(())
+        .expect_message(|msg: Pong| if msg == Pong(0) { Ok(()) } else { Err(format!("Unexpected message: {:?}", msg)) })
+        .run_in_scene(&scene, SubProgramId::new());
+
+    // The failing program is restarted, but with a delay between each attempt
+    let start_count = start_count.load(Ordering::Relaxed);
+    assert!(start_count > 1 && start_count < 20, "Program was started {} times", start_count);
+}
@@ -3,6 +3,8 @@ use flo_scene::programs::*;
 
 use futures::prelude::*;
 
+use std::time::{Duration};
+
 #[test]
 pub fn simple_ping_test_with_test_builder() {
     #[derive(Debug)]
@@ -64,3 +66,185 @@ pub fn multithreaded_ping() {
         .expect_message(|_: Ping| { Ok(()) })
         .run_in_scene_with_threads(&scene, SubProgramId::new(), 5);
 }
+
+#[test]
+pub fn no_dropped_messages() {
+    #[derive(Debug)]
+    struct Ping;
+    impl SceneMessage for Ping {}
+
+    let scene = Scene::default();
+
+    // Add a ping subprogram that responds to () messages with a 'Ping' response
+    let ping_program = SubProgramId::new();
+    scene.add_subprogram(ping_program, 
+        |input: InputStream<()>, context| async move {
+            let mut input = input.messages_with_sources();
+
+            while let Some((program_id, _)) = input.next().await {
+                let mut target = context.send(program_id).unwrap();
+
+                target.send(Ping).await.unwrap();
+            }
+        },
+        100);
+    scene.connect_programs((), ping_program, StreamId::with_message_type::<()>()).unwrap();
+
+    // Every message is delivered, so the assertion should pass
+    TestBuilder::new()
+        .send_message(())
+        .expect_message(|_: Ping| { Ok(()) })
+        .expect_no_dropped_messages()
+        .run_in_scene(&scene, SubProgramId::new());
+}
+
+#[test]
+#[should_panic(expected = "1 message(s) were dropped")]
+pub fn dropped_message_fails_test() {
+    #[derive(Debug)]
+    struct Unconnected;
+    impl SceneMessage for Unconnected {}
+
+    let scene = Scene::default();
+
+    // Nothing accepts 'Unconnected' messages, so sending one immediately will fail
+    scene.add_subprogram(SubProgramId::new(), 
+        |_: InputStream<()>, context| async move {
+            let mut target = context.send::<Unconnected>(()).unwrap();
+
+            target.send_immediate(Unconnected).ok();
+        },
+        0);
+
+    TestBuilder::new()
+        .expect_no_dropped_messages()
+        .run_in_scene(&scene, SubProgramId::new());
+}
+
+#[test]
+#[should_panic(expected = "Timed out after 100ms waiting for a message")]
+pub fn expect_message_within_times_out() {
+    #[derive(Debug)]
+    struct NeverSent;
+    impl SceneMessage for NeverSent {}
+
+    let scene = Scene::default();
+
+    // The message never arrives, so this should fail well before the overall test timeout
+    TestBuilder::new()
+        .expect_message_within(Duration::from_millis(100), |_: NeverSent| { Ok(()) })
+        .timeout_after(Duration::from_secs(60))
+        .run_in_scene_with_threads(&scene, SubProgramId::new(), 5);
+}
+
+#[test]
+pub fn expect_message_within_receives_message() {
+    #[derive(Debug)]
+    struct Ping;
+    impl SceneMessage for Ping {}
+
+    let scene = Scene::default();
+
+    // Add a ping subprogram that responds to () messages with a 'Ping' response
+    let ping_program = SubProgramId::new();
+    scene.add_subprogram(ping_program, 
+        |input: InputStream<()>, context| async move {
+            let mut input = input.messages_with_sources();
+
+            while let Some((program_id, _)) = input.next().await {
+                let mut target = context.send(program_id).unwrap();
+
+                target.send(Ping).await.unwrap();
+            }
+        },
+        100);
+    scene.connect_programs((), ping_program, StreamId::with_message_type::<()>()).unwrap();
+
+    TestBuilder::new()
+        .send_message(())
+        .expect_message_within(Duration::from_millis(1000), |_: Ping| { Ok(()) })
+        .run_in_scene(&scene, SubProgramId::new());
+}
+
+#[test]
+pub fn expect_messages_in_either_order() {
+    #[derive(Debug, PartialEq)]
+    enum Result { First, Second }
+    impl SceneMessage for Result {}
+
+    // Sends the results in the specified order
+    fn send_in_order(order: Vec<Result>) {
+        let scene = Scene::default();
+
+        let sender_program = SubProgramId::new();
+        scene.add_subprogram(sender_program, 
+            move |input: InputStream<()>, context| async move {
+                let mut input = input.messages_with_sources();
+
+                if let Some((program_id, _)) = input.next().await {
+                    let mut target = context.send(program_id).unwrap();
+
+                    for result in order {
+                        target.send(result).await.unwrap();
+                    }
+                }
+            },
+            100);
+        scene.connect_programs((), sender_program, StreamId::with_message_type::<()>()).unwrap();
+
+        TestBuilder::new()
+            .send_message(())
+            .expect_messages_unordered(vec![
+                Box::new(|msg: &Result| *msg == Result::First),
+                Box::new(|msg: &Result| *msg == Result::Second),
+            ])
+            .run_in_scene(&scene, SubProgramId::new());
+    }
+
+    send_in_order(vec![Result::First, Result::Second]);
+    send_in_order(vec![Result::Second, Result::First]);
+}
+
+#[test]
+pub fn inject_message_into_echo_program() {
+    #[derive(Debug, PartialEq)]
+    struct Echo(String);
+    impl SceneMessage for Echo {}
+
+    let scene = Scene::default();
+
+    // The echo program sends back any message it receives, and isn't connected to anything
+    let echo_program = SubProgramId::new();
+    scene.add_subprogram(echo_program, 
+        |input: InputStream<Echo>, context| async move {
+            let mut input = input.messages_with_sources();
+
+            while let Some((source, message)) = input.next().await {
+                let mut target = context.send(source).unwrap();
+
+                target.send(message).await.unwrap();
+            }
+        },
+        0);
+
+    TestBuilder::new()
+        .inject_message(echo_program, Echo("Hello".to_string()))
+        .expect_message(|msg: Echo| { if msg == Echo("Hello".to_string()) { Ok(()) } else { Err(format!("Unexpected message: {:?}", msg)) } })
+        .run_in_scene(&scene, SubProgramId::new());
+}
+
+#[test]
+#[should_panic(expected = "Could not inject a message")]
+pub fn inject_message_with_wrong_type() {
+    let scene = Scene::default();
+
+    let program = SubProgramId::new();
+    scene.add_subprogram(program, |input: InputStream<()>, _| async move {
+        let mut input = input;
+        while input.next().await.is_some() { }
+    }, 0);
+
+    TestBuilder::new()
+        .inject_message(program, 42usize)
+        .run_in_scene(&scene, SubProgramId::new());
+}